@@ -0,0 +1,65 @@
+use clap::Parser;
+
+use mtk::query;
+use mtk::RepoPathBuf;
+
+#[derive(Parser)]
+pub enum FeedSubcommand {
+    /// Videos that were started but not finished, most recently viewed first
+    ContinueWatching {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        num_entries: usize,
+    },
+    /// Files under a path, most recently modified first (only files that have been listed)
+    Recent {
+        /// Repo path to search under (defaults to the whole repo)
+        #[arg(default_value = "")]
+        path: String,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        num_entries: usize,
+    },
+}
+
+impl FeedSubcommand {
+    pub fn run(&self, stash: &mtk::Vault) {
+        let file_tree = stash.new_file_tree();
+        let mut catalog = stash.open_catalog().expect("open_catalog");
+
+        match self {
+            FeedSubcommand::ContinueWatching { num_entries } => {
+                let history_db = stash.open_history_db();
+                let entries = query::feeds::continue_watching(
+                    &file_tree,
+                    &mut catalog,
+                    &history_db,
+                    *num_entries,
+                );
+                for entry in entries {
+                    let history = history_db.get(entry.db.id).expect("get history");
+                    let ratio = history.video_history.map(|vh| vh.farthest_ts_ratio).unwrap_or(0.0);
+                    println!(
+                        "{:>3.0}%\t{}\t{}",
+                        ratio * 100.0,
+                        history.last_viewed_date.unwrap_or_default(),
+                        entry.fs.repo_path
+                    );
+                }
+            }
+            FeedSubcommand::Recent { path, num_entries } => {
+                let entries = query::feeds::recently_added(
+                    &RepoPathBuf::from(path.as_str()),
+                    &file_tree,
+                    &catalog,
+                    *num_entries,
+                );
+                for entry in entries {
+                    println!(
+                        "{}\t{}",
+                        entry.fs.mod_time.format("%Y-%m-%d %H:%M"),
+                        entry.fs.repo_path
+                    );
+                }
+            }
+        }
+    }
+}
@@ -41,7 +41,9 @@ fn run_job_scan_media(stash: &mtk::Vault, job_type: &str, search_root: &RepoPath
     let mut catalog = stash.open_catalog().expect("open_catalog");
 
     println!("Scanning for media files in {}", search_root);
-    let listing = mtk::browse::list_recursive(&mut catalog, &file_tree, search_root).expect("list_recursive");
+    // Jobs also run on pages in archives, e.g. to make their previews
+    let listing = mtk::browse::list_recursive_with_archives(&mut catalog, &file_tree, search_root)
+        .expect("list_recursive_with_archives");
 
     println!("Running jobs on {} entries", listing.visible.len());
     run_job_on_entries(stash, job_type, listing.visible.into_iter().filter(|entry| entry.fs.file_type.is_file));
@@ -2,7 +2,9 @@ use std::path::PathBuf;
 
 use clap::{command, Parser};
 
+mod feeds;
 mod jobs;
+mod similar;
 mod test;
 
 #[derive(clap::Args)]
@@ -33,6 +35,12 @@ impl InitCommand {
 enum Commands {
     Init(InitCommand),
     RunJobs(jobs::RunJobsCommand),
+    Feed {
+        #[command(subcommand)]
+        command: feeds::FeedSubcommand,
+    },
+    /// Find files that look like the given image or video
+    Similar(similar::SimilarCommand),
     Test {
         #[command(subcommand)]
         command: test::TestSubcommand,
@@ -52,6 +60,8 @@ fn main() {
     match &cli.command {
         Commands::Init(init) => init.run(),
         Commands::RunJobs(run_jobs) => run_jobs.run(&mtk::Vault::from_cwd()),
+        Commands::Feed { command } => command.run(&mtk::Vault::from_cwd()),
+        Commands::Similar(similar) => similar.run(&mtk::Vault::from_cwd()),
         Commands::Test { command } => command.run(),
     }
 }
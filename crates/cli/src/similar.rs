@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use mtk::media::phash;
+use mtk::query;
+
+#[derive(clap::Args)]
+pub struct SimilarCommand {
+    /// Max Hamming distance between perceptual hashes
+    #[arg(short = 'd', long, default_value_t = query::visual::DEFAULT_MAX_DISTANCE)]
+    max_distance: u32,
+
+    file: PathBuf,
+}
+
+impl SimilarCommand {
+    pub fn run(&self, stash: &mtk::Vault) {
+        let file_tree = stash.new_file_tree();
+        let catalog = stash.open_catalog().expect("open_catalog");
+
+        // TODO: don't use canonicalize
+        let file_path = self.file.canonicalize().expect("canonicalize");
+        let target_path = file_tree.full_to_repo_path(&file_path);
+        let target_hash = target_path
+            .as_ref()
+            .and_then(|repo_path| catalog.get_by_path(repo_path))
+            .and_then(|db_entry| query::visual::read_phash(&db_entry));
+
+        // Files outside the repo, or that haven't been through the "phash" job, are hashed now
+        let target_hash = match target_hash {
+            Some(hash) => hash,
+            None => phash::compute_dhash(&file_path).expect("compute_dhash"),
+        };
+
+        let index = query::visual::VisualIndex::build(&catalog);
+        for (entry, distance) in query::visual::visually_similar(target_hash, &index, &file_tree, &catalog, self.max_distance) {
+            if Some(&entry.fs.repo_path) == target_path.as_ref() {
+                continue;
+            }
+            println!("{}\t{}", distance, entry.fs.repo_path);
+        }
+    }
+}
@@ -43,7 +43,9 @@ impl TestSubcommand {
                 if mtk::filetype::is_image(in_path) {
                     image::make_preview_image(&in_path, &out_path);
                 } else {
-                    video::video_preview::make_preview_image(&in_path, &out_path);
+                    let frame = video::video_preview::make_preview_image(in_path, out_path, None)
+                        .expect("make_preview_image");
+                    println!("{:?}", frame);
                 }
             }
             TestSubcommand::AltFormat {
@@ -51,9 +53,10 @@ impl TestSubcommand {
                 out_path,
                 profile: profile_str,
             } => {
-                let profile = video::transcode::TranscodeProfile::from_str(profile_str)
+                let profile = video::transcode::TranscodeProfile::builtin(profile_str)
                     .expect("profile");
-                video::transcode::transcode_alt_format(&in_path, &out_path, profile);
+                video::transcode::transcode_alt_format(in_path, out_path, &profile)
+                    .expect("transcode_alt_format");
             }
             TestSubcommand::DownloadYtDlp { url, dest_dir } => {
                 println!("Downloading with yt-dlp: {}", url);
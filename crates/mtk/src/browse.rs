@@ -1,3 +1,7 @@
+pub mod classify;
 mod scanner;
+pub mod sort;
 pub use scanner::listdir;
 pub use scanner::list_recursive;
+pub use scanner::list_recursive_with_archives;
+pub(crate) use scanner::should_hide_entry;
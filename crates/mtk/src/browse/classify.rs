@@ -0,0 +1,139 @@
+//! Works out whether a directory is a series (numbered episodes) or a gallery (mostly
+//! images), so that it can be shown with a template that suits it.
+
+use crate::catalog::SpecialEntryType;
+use crate::{filetype, FsEntry};
+
+/// Galleries need at least this many images...
+const GALLERY_MIN_IMAGES: usize = 4;
+/// ...making up at least this ratio of the files.
+const GALLERY_MIN_IMAGE_RATIO: f32 = 0.75;
+/// Series need at least this ratio of their videos to have episode numbers.
+const SERIES_MIN_NUMBERED_RATIO: f32 = 0.75;
+
+/// Written by gallery-dl next to what it downloads
+const GALLERY_DL_INFO_FILE: &str = "info.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EpisodeNumber {
+    pub season: Option<u32>,
+    pub episode: u32,
+}
+
+const EPISODE_PATTERNS: [&str; 5] = [
+    // "Show S01E02", "Show s1 e2"
+    r"(?i)\bs(?P<season>\d{1,2})[\s.]?e(?P<episode>\d{1,3})\b",
+    // "Show 1x02"
+    r"(?i)\b(?P<season>\d{1,2})x(?P<episode>\d{2,3})\b",
+    // "Show Episode 2", "Show Ep.02", "Show E02"
+    r"(?i)\b(?:episode|ep|e)[\s._-]*(?P<episode>\d{1,3})\b",
+    // "[Group] Show - 02 [1080p]", "Show - 02v2"
+    r"\s-\s*(?P<episode>\d{1,3})(?:v\d)?(?:[\s._\[(]|$)",
+    // "02 - Title", "02"
+    r"^(?P<episode>\d{1,3})(?:[\s._-]|$)",
+];
+
+/// The episode number in a file name, if it has one.
+pub fn episode_number(file_name: &str) -> Option<EpisodeNumber> {
+    let stem = std::path::Path::new(file_name).file_stem()?.to_str()?;
+    EPISODE_PATTERNS.iter().find_map(|pattern| {
+        let captures = regex::Regex::new(pattern).expect("regex").captures(stem)?;
+        Some(EpisodeNumber {
+            season: captures.name("season").and_then(|m| m.as_str().parse().ok()),
+            episode: captures.name("episode")?.as_str().parse().ok()?,
+        })
+    })
+}
+
+/// The special type for a directory with the given children, if it's a series or
+/// a gallery.
+pub fn classify_dir(dir: &FsEntry, children: &[FsEntry]) -> Option<SpecialEntryType> {
+    let files: Vec<&FsEntry> = children
+        .iter()
+        .filter(|child| !child.file_type.is_dir && !child.is_metadata_file)
+        .collect();
+    let num_images = files.iter().filter(|child| filetype::is_image(&child.file_path)).count();
+    let videos: Vec<&FsEntry> = files.iter().copied().filter(|child| filetype::is_video(&child.file_path)).collect();
+
+    let mut episodes: Vec<EpisodeNumber> = videos.iter().filter_map(|video| episode_number(&video.file_name)).collect();
+    let num_numbered = episodes.len();
+    episodes.sort();
+    episodes.dedup();
+    let is_series = episodes.len() >= 2
+        && num_numbered as f32 >= videos.len() as f32 * SERIES_MIN_NUMBERED_RATIO
+        && videos.len() >= num_images;
+    if is_series {
+        return Some(SpecialEntryType::SeriesDir);
+    }
+
+    let is_gallery_dl = num_images > 0 && dir.file_path.join(GALLERY_DL_INFO_FILE).is_file();
+    let is_gallery = num_images >= GALLERY_MIN_IMAGES
+        && num_images as f32 >= files.len() as f32 * GALLERY_MIN_IMAGE_RATIO;
+    if is_gallery_dl || is_gallery {
+        return Some(SpecialEntryType::GalleryDir);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    fn ep(season: Option<u32>, episode: u32) -> Option<EpisodeNumber> {
+        Some(EpisodeNumber { season, episode })
+    }
+
+    #[test]
+    fn test_episode_number() {
+        assert_eq!(episode_number("Show.S01E02.1080p.mkv"), ep(Some(1), 2));
+        assert_eq!(episode_number("Show s2 e10.mp4"), ep(Some(2), 10));
+        assert_eq!(episode_number("Show 3x04.mp4"), ep(Some(3), 4));
+        assert_eq!(episode_number("Show Episode 7.mp4"), ep(None, 7));
+        assert_eq!(episode_number("Show Ep.07.mp4"), ep(None, 7));
+        assert_eq!(episode_number("[Group] Show - 12v2 [1080p].mkv"), ep(None, 12));
+        assert_eq!(episode_number("Show - 12.mkv"), ep(None, 12));
+        assert_eq!(episode_number("03 - The Title.mp4"), ep(None, 3));
+
+        assert_eq!(episode_number("Holiday 2019 1080p.mp4"), None);
+        assert_eq!(episode_number("berlin_wall.mp4"), None);
+    }
+
+    fn children_named(dir: &std::path::Path, names: &[&str]) -> Vec<FsEntry> {
+        names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, "").unwrap();
+                FsEntry::from_path(&path, dir).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_dir() -> testing::TestResult {
+        let tempdir = tempfile::tempdir()?;
+        let dir = FsEntry::from_path(tempdir.path(), tempdir.path())?;
+
+        let series = children_named(tempdir.path(), &["Show S01E01.mkv", "Show S01E02.mkv", "Show S01E03.mkv", "cover.jpg"]);
+        assert_eq!(classify_dir(&dir, &series), Some(SpecialEntryType::SeriesDir));
+
+        // The same episode twice doesn't make a series
+        let repeats = children_named(tempdir.path(), &["Show S01E01.mkv", "Show S01E01 (copy).mkv"]);
+        assert_eq!(classify_dir(&dir, &repeats), None);
+
+        let gallery = children_named(tempdir.path(), &["1.jpg", "2.jpg", "3.png", "4.webp", "notes.txt"]);
+        assert_eq!(classify_dir(&dir, &gallery), Some(SpecialEntryType::GalleryDir));
+
+        let mixed = children_named(tempdir.path(), &["1.jpg", "2.jpg", "a.mp4", "b.mp4", "c.txt"]);
+        assert_eq!(classify_dir(&dir, &mixed), None);
+
+        // gallery-dl downloads are galleries however many images there are
+        let downloaded = children_named(tempdir.path(), &["1.jpg", "info.json"]);
+        assert_eq!(classify_dir(&dir, &downloaded), Some(SpecialEntryType::GalleryDir));
+
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ pub struct ScanListing {
     pub hidden: Vec<HalfEntry>,
 }
 
-fn should_hide_entry(entry: &DbEntry) -> bool {
+pub(crate) fn should_hide_entry(entry: &DbEntry) -> bool {
     let hidden_special_entry_type = entry.special_type.as_ref().is_some_and(|t| match t {
         crate::catalog::SpecialEntryType::SeriesDir => false,
         crate::catalog::SpecialEntryType::GalleryDir => false,
@@ -73,6 +73,12 @@ pub fn list_recursive(catalog: &mut Catalog, file_tree: &FileTree, root: &RepoPa
     scanner.list_iterator_to_result(Box::new(file_tree.list_recursive(root)?))
 }
 
+/// Like list_recursive, but also lists (and catalogs) the members of archives.
+pub fn list_recursive_with_archives(catalog: &mut Catalog, file_tree: &FileTree, root: &RepoPathBuf) -> Result<ScanListing, Box<dyn std::error::Error>> {
+    let mut scanner = Scanner { catalog };
+    scanner.list_iterator_to_result(Box::new(file_tree.list_recursive_with_archives(root)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
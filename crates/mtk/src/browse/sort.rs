@@ -0,0 +1,150 @@
+use std::cmp::Ordering;
+
+use chrono::NaiveDateTime;
+
+use crate::media::image::image_info;
+use crate::Entry;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    /// By display title, case-insensitive
+    Name,
+    /// Most recently modified first
+    Modified,
+    /// Photos by when they were taken (oldest first), falling back to the
+    /// modification time for entries without a capture date
+    Captured,
+}
+
+impl SortOrder {
+    pub fn parse(s: &str) -> SortOrder {
+        match s {
+            "modified" => SortOrder::Modified,
+            "captured" => SortOrder::Captured,
+            _ => SortOrder::Name,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Modified => "modified",
+            SortOrder::Captured => "captured",
+        }
+    }
+}
+
+/// Compare names so that runs of digits sort by their value, e.g. "page2" before
+/// "page10". Otherwise compares case-insensitively.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let a_number = take_number(&mut a_chars);
+                let b_number = take_number(&mut b_chars);
+                // Without leading zeros, a longer number is a bigger one
+                let ordering = a_number.len().cmp(&b_number.len()).then_with(|| a_number.cmp(&b_number));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a_char), Some(b_char)) => {
+                let ordering = a_char.to_lowercase().cmp(b_char.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn capture_or_mod_time(entry: &Entry) -> NaiveDateTime {
+    image_info::read_image_info(&entry.db)
+        .and_then(|info| info.capture_time())
+        .unwrap_or_else(|| entry.fs.mod_time.naive_utc())
+}
+
+/// Sort entries, always with directories before files.
+pub fn sort_entries(entries: &mut [Entry], order: SortOrder) {
+    let name = |entry: &Entry| entry.display_title();
+    entries.sort_by(|a, b| {
+        // First, sort folders before files
+        let dirs_first = b.fs.file_type.is_dir.cmp(&a.fs.file_type.is_dir);
+        if dirs_first != Ordering::Equal {
+            return dirs_first;
+        }
+
+        let ordering = match order {
+            SortOrder::Name => Ordering::Equal,
+            SortOrder::Modified => b.fs.mod_time.cmp(&a.fs.mod_time),
+            SortOrder::Captured => capture_or_mod_time(a).cmp(&capture_or_mod_time(b)),
+        };
+        // Then by name
+        ordering.then_with(|| natural_cmp(&name(a), &name(b)))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::catalog::generated_notes;
+    use crate::testing;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec!["page10.jpg", "Page2.jpg", "page1.jpg", "page003.jpg", "cover.jpg", "page"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, vec!["cover.jpg", "page", "page1.jpg", "Page2.jpg", "page003.jpg", "page10.jpg"]);
+    }
+
+    #[test]
+    fn test_sort_entries() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::create_dir(file_root.path().join("dir"))?;
+        for path in ["b_old_photo.jpg", "A_new_photo.jpg", "c_no_info.jpg"] {
+            std::fs::write(file_root.path().join(path), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        for (path, capture_date) in [("b_old_photo.jpg", "2001-01-01T00:00:00"), ("A_new_photo.jpg", "2020-01-01T00:00:00")] {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            let info = image_info::ImageInfo { capture_date: Some(capture_date.to_string()), ..Default::default() };
+            generated_notes::update(&mut catalog, entry.db.id, image_info::IMAGE_INFO_GROUP_NAME, &info);
+        }
+
+        let mut entries = Vec::new();
+        for path in ["c_no_info.jpg", "b_old_photo.jpg", "dir", "A_new_photo.jpg"] {
+            entries.push(testing::entry_for(path, &file_tree, &mut catalog)?);
+        }
+        let paths = |entries: &[Entry]| -> Vec<String> { entries.iter().map(|e| e.fs.repo_path.0.clone()).collect() };
+
+        sort_entries(&mut entries, SortOrder::Name);
+        assert_eq!(paths(&entries), vec!["dir", "A_new_photo.jpg", "b_old_photo.jpg", "c_no_info.jpg"]);
+
+        // The file without a capture date was just modified, so it sorts last
+        sort_entries(&mut entries, SortOrder::Captured);
+        assert_eq!(paths(&entries), vec!["dir", "b_old_photo.jpg", "A_new_photo.jpg", "c_no_info.jpg"]);
+
+        assert_eq!(SortOrder::parse("captured"), SortOrder::Captured);
+        assert_eq!(SortOrder::parse("bogus"), SortOrder::Name);
+
+        Ok(())
+    }
+}
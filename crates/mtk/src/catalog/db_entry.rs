@@ -138,6 +138,20 @@ impl DbEntry {
     pub fn linked_urls(&self) -> Vec<String> {
         self.get("linked_urls").ok().flatten().unwrap_or(vec![])
     }
+
+    /// Uploader or channel of downloaded media, usually from an .info.json file
+    pub fn uploader(&self) -> Option<String> {
+        self.get("uploader").ok().flatten()
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        self.get("tags").ok().flatten().unwrap_or(vec![])
+    }
+
+    /// Timestamp (in seconds) of the frame to use as a video's preview, if pinned
+    pub fn preview_timestamp(&self) -> Option<f64> {
+        self.get("preview_timestamp").ok().flatten()
+    }
 }
 
 #[cfg(test)]
@@ -13,8 +13,12 @@ use super::{Catalog, DbEntry};
 /// Key | Value
 /// ----|------
 /// group_name::__last_update | timestamp
+/// group_name::__version | version (optional, 1 if missing)
 /// group_name::key1 | value1
 /// group_name::key2 | value2
+///
+/// Groups whose contents change (new fields, values computed differently) bump their
+/// version, so that notes written by older code are regenerated.
 
 fn get_last_update_key(group_name: &str) -> String {
     if group_name.is_empty() {
@@ -23,6 +27,13 @@ fn get_last_update_key(group_name: &str) -> String {
     format!("{}::__last_update", group_name)
 }
 
+fn get_version_key(group_name: &str) -> String {
+    if group_name.is_empty() {
+        panic!("group_name cannot be empty");
+    }
+    format!("{}::__version", group_name)
+}
+
 /// Check if the generated group needs to be updated (if the file's modtime is newer than the group's last update time).
 pub fn needs_update(entry: &Entry, group_name: &str) -> bool {
     let last_update_key = get_last_update_key(group_name);
@@ -35,9 +46,33 @@ pub fn needs_update(entry: &Entry, group_name: &str) -> bool {
     }
 }
 
+/// The version of the stored group. Groups written before versions existed are version 1.
+pub fn version(entry: &DbEntry, group_name: &str) -> u64 {
+    entry.notes_generated.get(get_version_key(group_name)).and_then(|v| v.as_u64()).unwrap_or(1)
+}
+
+/// Like needs_update, but also if the group was written with an older version.
+pub fn needs_update_versioned(entry: &Entry, group_name: &str, version: u64) -> bool {
+    needs_update(entry, group_name) || self::version(&entry.db, group_name) < version
+}
+
+/// When the generated group was last updated, in seconds since the epoch.
+pub fn last_update(entry: &DbEntry, group_name: &str) -> Option<u64> {
+    entry.notes_generated.get(get_last_update_key(group_name))?.as_u64()
+}
+
 /// Update the generated group with the current time and the new values. Each sub-key in the container
 /// will be stored as a separate key in the group.
 pub fn update<T: Serialize>(catalog: &mut Catalog, entry_id: i64, group_name: &str, container: &T) {
+    update_with_version(catalog, entry_id, group_name, None, container);
+}
+
+/// Like update, but also records the group's version.
+pub fn update_versioned<T: Serialize>(catalog: &mut Catalog, entry_id: i64, group_name: &str, version: u64, container: &T) {
+    update_with_version(catalog, entry_id, group_name, Some(version), container);
+}
+
+fn update_with_version<T: Serialize>(catalog: &mut Catalog, entry_id: i64, group_name: &str, version: Option<u64>, container: &T) {
     let current_time = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .expect("duration_since")
@@ -57,6 +92,9 @@ pub fn update<T: Serialize>(catalog: &mut Catalog, entry_id: i64, group_name: &s
         super::sqlite_catalog::WhichNotes::Generated,
         |notes| {
             notes[&last_update_key] = json!(current_time);
+            if let Some(version) = version {
+                notes[&get_version_key(group_name)] = json!(version);
+            }
             for (key, value) in container_map {
                 let full_key = format!("{}::{}", group_name, key);
 
@@ -75,11 +113,12 @@ pub fn update<T: Serialize>(catalog: &mut Catalog, entry_id: i64, group_name: &s
 /// Read the generated group from the entry's notes_generated field.
 pub fn read<T: DeserializeOwned>(entry: &DbEntry, group_name: &str) -> Option<T> {
     let last_update_key = get_last_update_key(group_name);
+    let version_key = get_version_key(group_name);
 
     if let Some(generated_map) = entry.notes_generated.as_object() {
         let mut out_map = serde_json::Map::new();
         for (key, value) in generated_map {
-            if key == &last_update_key {
+            if key == &last_update_key || key == &version_key {
                 continue;
             }
 
@@ -169,6 +208,44 @@ mod tests {
         assert!(needs_update(&entry, "group1"));
     }
 
+    #[test]
+    fn test_needs_update_versioned() {
+        let mut fs_entry = testing::test_fs_entry("test.mp4");
+        fs_entry.mod_time = chrono::DateTime::<chrono::Utc>::from_timestamp(50, 0).unwrap();
+
+        let mut db_entry = DbEntry::default(123, RepoPathBuf::from("test.mp4"));
+        db_entry.notes_generated = json!({
+            "group1::__last_update": 100,
+            "group2::__last_update": 100,
+            "group2::__version": 2
+        });
+
+        let entry = Entry {
+            fs: fs_entry,
+            db: db_entry,
+        };
+
+        // No version is version 1
+        assert_eq!(version(&entry.db, "group1"), 1);
+        assert_eq!(false, needs_update_versioned(&entry, "group1", 1));
+        assert!(needs_update_versioned(&entry, "group1", 2));
+
+        assert_eq!(version(&entry.db, "group2"), 2);
+        assert_eq!(false, needs_update_versioned(&entry, "group2", 2));
+        assert!(needs_update_versioned(&entry, "group2", 3));
+    }
+
+    #[test]
+    fn test_last_update() {
+        let mut db_entry = DbEntry::default(123, RepoPathBuf::from("test.mp4"));
+        db_entry.notes_generated = json!({
+            "group1::__last_update": 100
+        });
+
+        assert_eq!(last_update(&db_entry, "group1"), Some(100));
+        assert_eq!(last_update(&db_entry, "group2"), None);
+    }
+
     #[test]
     fn test_read_existing_group() {
         let mut db_entry = DbEntry::default(123, RepoPathBuf::from("test.mp4"));
@@ -234,4 +311,21 @@ mod tests {
         assert_eq!(&generated_map["group1::float_key"], &json!(3.14));
         assert_eq!(false, generated_map.contains_key("group1::null_key"));
     }
+
+    #[test]
+    fn test_update_versioned() {
+        let conn = testing::in_memory_conn("");
+        let mut catalog = Catalog::from_conn(conn);
+
+        let fs_entry = testing::test_fs_entry("test.mp4");
+        let entry_id = catalog.get_or_create(&fs_entry).id;
+
+        update_versioned(&mut catalog, entry_id, "group1", 3, &json!({"key1": "value1"}));
+
+        let entry = catalog.get_by_id(entry_id).expect("get entry");
+        assert_eq!(version(&entry, "group1"), 3);
+        // The version isn't part of the group's values
+        let result: Option<serde_json::Value> = read(&entry, "group1");
+        assert_eq!(result, Some(json!({"key1": "value1"})));
+    }
 }
@@ -37,8 +37,8 @@ fn row_to_entry(row: &rusqlite::Row) -> Result<DbEntry, CatalogError> {
     })
 }
 
-fn get_by_path(tx: &rusqlite::Transaction, repo_path: &RepoPathBuf) -> Option<DbEntry> {
-    let result = tx.query_row_and_then(
+fn get_by_path(conn: &rusqlite::Connection, repo_path: &RepoPathBuf) -> Option<DbEntry> {
+    let result = conn.query_row_and_then(
         &format!(
             "SELECT {} FROM entries WHERE repo_path = ?",
             ALL_COLUMN_NAMES.join(",")
@@ -55,6 +55,12 @@ fn get_by_path(tx: &rusqlite::Transaction, repo_path: &RepoPathBuf) -> Option<Db
     }
 }
 
+/// Modification time to record for the entry, in seconds. Only regular files have one.
+fn file_mod_time(fs_entry: &FsEntry) -> Option<i64> {
+    let is_regular_file = fs_entry.file_type.is_file && !fs_entry.is_metadata_file && fs_entry.archive_member.is_none();
+    is_regular_file.then(|| fs_entry.mod_time.timestamp())
+}
+
 pub enum WhichNotes {
     User,
     External,
@@ -115,11 +121,42 @@ impl Catalog {
             )
             .unwrap();
             conn.pragma_update(None, "user_version", &1).unwrap();
-        } else if user_version == 1 {
-            // Latest schema version
-        } else {
+        } else if user_version > 3 {
             panic!("Unknown user_version {}", user_version);
         }
+
+        if user_version < 2 {
+            // Modification time of regular files (NULL for directories and archive pages),
+            // as of when they were last listed. For the recently added feed.
+            conn.execute_batch(
+                "
+                ALTER TABLE entries ADD COLUMN mod_time INTEGER;
+                CREATE INDEX entries_mod_time ON entries(mod_time);
+            ",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 2).unwrap();
+        }
+
+        if user_version < 3 {
+            // Perceptual hashes (also in the "phash" generated notes), for building the
+            // visual similarity index without reading every entry. `seq` changes on every
+            // write, so readers can tell when the index needs rebuilding.
+            conn.execute_batch(
+                "
+                CREATE TABLE phashes (
+                    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                    entry_id INTEGER NOT NULL UNIQUE,
+                    dhash TEXT NOT NULL
+                );
+                INSERT INTO phashes (entry_id, dhash)
+                    SELECT entry_id, json_extract(notes_generated, '$.\"phash::dhash\"') FROM entries
+                    WHERE json_extract(notes_generated, '$.\"phash::dhash\"') IS NOT NULL;
+            ",
+            )
+            .unwrap();
+            conn.pragma_update(None, "user_version", 3).unwrap();
+        }
     }
 
     pub fn get_by_id(&self, entry_id: i64) -> Option<DbEntry> {
@@ -148,6 +185,11 @@ impl Catalog {
             .expect("path_to_id")
     }
 
+    /// Like get_or_create, but doesn't add entries that aren't in the catalog yet
+    pub fn get_by_path(&self, repo_path: &RepoPathBuf) -> Option<DbEntry> {
+        get_by_path(&self.conn, repo_path)
+    }
+
     pub fn contains_path(&self, repo_path: &RepoPathBuf) -> bool {
         self.path_to_id(repo_path).is_some()
     }
@@ -166,15 +208,21 @@ impl Catalog {
                 )
                 .unwrap();
             }
+            tx.execute(
+                "UPDATE entries SET mod_time = ?1 WHERE entry_id = ?2 AND mod_time IS NOT ?1",
+                (file_mod_time(fs_entry), row.id),
+            )
+            .unwrap();
             tx.commit().unwrap();
 
             return get_by_path(&self.conn.transaction().unwrap(), &fs_entry.repo_path).unwrap();
         } else {
             // Create and return the new entry
             tx.execute(
-                "INSERT OR IGNORE INTO entries (repo_path) VALUES (?1)",
+                "INSERT OR IGNORE INTO entries (repo_path, mod_time) VALUES (?1, ?2)",
                 (
                     fs_entry.repo_path.as_str(),
+                    file_mod_time(fs_entry),
                 ),
             )
             .unwrap();
@@ -184,6 +232,26 @@ impl Catalog {
         }
     }
 
+    /// Files under `base_path` (everything, if empty), most recently modified first, as of
+    /// when they were last listed. Deleted entries are left out.
+    pub fn list_recently_modified(&self, base_path: &RepoPathBuf, limit: usize, offset: usize) -> Vec<DbEntry> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM entries
+                WHERE mod_time IS NOT NULL AND deleted = FALSE
+                    AND (?1 = '' OR substr(repo_path, 1, length(?1) + 1) = ?1 || '/')
+                ORDER BY mod_time DESC, entry_id DESC
+                LIMIT ?2 OFFSET ?3",
+                ALL_COLUMN_NAMES.join(",")
+            ))
+            .expect("prepare");
+        stmt.query_and_then((base_path.as_str(), limit as i64, offset as i64), row_to_entry)
+            .expect("list_recently_modified")
+            .collect::<Result<Vec<DbEntry>, CatalogError>>()
+            .expect("list_recently_modified")
+    }
+
     pub fn update_notes_with(
         &mut self,
         id: i64,
@@ -249,6 +317,38 @@ impl Catalog {
             .expect("update should succeed");
     }
 
+    pub fn set_special_type(&mut self, id: i64, special_type: Option<SpecialEntryType>) {
+        self.conn
+            .execute(
+                "UPDATE entries SET special_type = ?1 WHERE entry_id = ?2",
+                (special_type.map(|t| t.to_string()), id),
+            )
+            .expect("update should succeed");
+    }
+
+    /// Add or replace the entry's perceptual hash (as hex) in the phash index
+    pub fn set_phash(&mut self, id: i64, dhash: &str) {
+        self.conn
+            .execute("INSERT OR REPLACE INTO phashes (entry_id, dhash) VALUES (?1, ?2)", (id, dhash))
+            .expect("set_phash");
+    }
+
+    /// Every (entry ID, hex perceptual hash) in the phash index
+    pub fn list_phashes(&self) -> Vec<(i64, String)> {
+        let mut stmt = self.conn.prepare("SELECT entry_id, dhash FROM phashes").expect("prepare");
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("list_phashes")
+            .collect::<Result<Vec<_>, _>>()
+            .expect("list_phashes")
+    }
+
+    /// Changes whenever a hash is added to the phash index, or replaced
+    pub fn phashes_version(&self) -> i64 {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(seq), 0) FROM phashes", [], |row| row.get(0))
+            .expect("phashes_version")
+    }
+
     // Testing/debugging functions
     #[allow(dead_code)]
     pub(crate) fn print_all_entries(&mut self) {
@@ -345,6 +445,16 @@ mod tests {
         assert_eq!(catalog.path_to_id(&RepoPathBuf::from("doesnt/exist")), None);
     }
 
+    #[test]
+    fn test_get_by_path() {
+        let conn = in_memory_conn("");
+        make_fixtures(&conn);
+
+        let catalog = Catalog::from_conn(conn);
+        assert_eq!(catalog.get_by_path(&RepoPathBuf::from("dir1/file2")).expect("is not None").id, 2);
+        assert!(catalog.get_by_path(&RepoPathBuf::from("doesnt/exist")).is_none());
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_get_or_create__exists() {
@@ -400,6 +510,79 @@ mod tests {
         assert_eq!(row.deleted, false);
     }
 
+    #[test]
+    fn test_list_recently_modified() {
+        let conn = testing::in_memory_conn("");
+        let mut catalog = Catalog::from_conn(conn);
+
+        for (path, secs) in [("dir1/old.mp4", 100), ("dir1/new.mp4", 300), ("dir2/middle.mp4", 200), ("dir10/x.mp4", 400)] {
+            let mut fs_entry = testing::test_fs_entry(path);
+            fs_entry.mod_time = chrono::DateTime::from_timestamp(secs, 0).unwrap();
+            catalog.get_or_create(&fs_entry);
+        }
+        let mut dir = testing::test_fs_entry("dir1/subdir");
+        dir.file_type.is_file = false;
+        dir.file_type.is_dir = true;
+        catalog.get_or_create(&dir);
+
+        let paths = |entries: Vec<DbEntry>| entries.into_iter().map(|e| e.repo_path.0).collect::<Vec<_>>();
+        assert_eq!(
+            paths(catalog.list_recently_modified(&RepoPathBuf::from(""), 10, 0)),
+            vec!["dir10/x.mp4", "dir1/new.mp4", "dir2/middle.mp4", "dir1/old.mp4"]
+        );
+        assert_eq!(paths(catalog.list_recently_modified(&RepoPathBuf::from(""), 2, 1)), vec!["dir1/new.mp4", "dir2/middle.mp4"]);
+        assert_eq!(
+            paths(catalog.list_recently_modified(&RepoPathBuf::from("dir1"), 10, 0)),
+            vec!["dir1/new.mp4", "dir1/old.mp4"]
+        );
+
+        // Modification times are kept up to date
+        let mut fs_entry = testing::test_fs_entry("dir1/old.mp4");
+        fs_entry.mod_time = chrono::DateTime::from_timestamp(500, 0).unwrap();
+        catalog.get_or_create(&fs_entry);
+        assert_eq!(paths(catalog.list_recently_modified(&RepoPathBuf::from(""), 1, 0)), vec!["dir1/old.mp4"]);
+    }
+
+    #[test]
+    fn test_phashes() {
+        let conn = testing::in_memory_conn("");
+        make_fixtures(&conn);
+
+        let mut catalog = Catalog::from_conn(conn);
+        assert!(catalog.list_phashes().is_empty());
+        let empty_version = catalog.phashes_version();
+
+        catalog.set_phash(1, "00000000000000ff");
+        catalog.set_phash(2, "ff00000000000000");
+        let version = catalog.phashes_version();
+        assert_ne!(version, empty_version);
+
+        catalog.set_phash(1, "000000000000ffff");
+        assert_ne!(catalog.phashes_version(), version);
+        let mut phashes = catalog.list_phashes();
+        phashes.sort();
+        assert_eq!(phashes, vec![(1, "000000000000ffff".to_string()), (2, "ff00000000000000".to_string())]);
+    }
+
+    #[test]
+    fn test_phashes_from_generated_notes() {
+        let conn = testing::in_memory_conn("");
+        Catalog::init_conn(&conn);
+        // As if hashed before the phash index existed
+        conn.execute_batch(
+            "
+            INSERT INTO entries (entry_id, repo_path, notes_generated)
+            VALUES (1, 'a.jpg', json('{\"phash::dhash\": \"00000000000000ff\"}')), (2, 'b.jpg', json('{}'));
+            DROP TABLE phashes;
+            PRAGMA user_version = 2;
+        ",
+        )
+        .unwrap();
+
+        let catalog = Catalog::from_conn(conn);
+        assert_eq!(catalog.list_phashes(), vec![(1, "00000000000000ff".to_string())]);
+    }
+
     #[test]
     fn test_set_single_note() {
         let conn = testing::in_memory_conn("set_single_note");
@@ -461,4 +644,19 @@ mod tests {
         let row = catalog.get_by_id(1).unwrap();
         assert_eq!(row.description(), "test");
     }
+
+    #[test]
+    fn test_set_special_type() {
+        let conn = testing::in_memory_conn("set_special_type");
+        make_fixtures(&conn);
+
+        let mut catalog = Catalog::from_conn(conn);
+        assert_eq!(catalog.get_by_id(1).unwrap().special_type, None);
+
+        catalog.set_special_type(1, Some(SpecialEntryType::SeriesDir));
+        assert_eq!(catalog.get_by_id(1).unwrap().special_type, Some(SpecialEntryType::SeriesDir));
+
+        catalog.set_special_type(1, None);
+        assert_eq!(catalog.get_by_id(1).unwrap().special_type, None);
+    }
 }
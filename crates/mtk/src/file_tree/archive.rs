@@ -0,0 +1,143 @@
+//! Zip archives of images (CBZ comics, or plain zips), which are browsed as virtual
+//! directories. Each image in the archive is an entry, at e.g. "Comics/vol1.cbz/003.jpg".
+
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::filetype;
+
+/// An image inside an archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveMember {
+    pub archive_path: PathBuf,
+    /// Path within the archive
+    pub name: String,
+    pub size_bytes: u64,
+}
+
+fn open_archive(archive_path: &Path) -> std::io::Result<zip::ZipArchive<std::fs::File>> {
+    zip::ZipArchive::new(std::fs::File::open(archive_path)?).map_err(std::io::Error::other)
+}
+
+fn is_listed_member(name: &str) -> bool {
+    // Skip resource forks and other hidden files that some zip tools add
+    let is_hidden = name.split('/').any(|part| part.starts_with('.') || part == "__MACOSX");
+    !is_hidden && filetype::is_image(Path::new(name))
+}
+
+/// The images in an archive, in page order. Folders within the archive are
+/// flattened, since they're usually just chapters.
+pub fn list_members(archive_path: &Path) -> std::io::Result<Vec<ArchiveMember>> {
+    let mut archive = open_archive(archive_path)?;
+    let mut members = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(std::io::Error::other)?;
+        if file.is_file() && is_listed_member(file.name()) {
+            members.push(ArchiveMember {
+                archive_path: archive_path.to_path_buf(),
+                name: file.name().to_string(),
+                size_bytes: file.size(),
+            });
+        }
+    }
+    members.sort_by(|a, b| crate::browse::sort::natural_cmp(&a.name, &b.name));
+    Ok(members)
+}
+
+/// If `full_path` is inside an archive, the archive member it refers to.
+pub fn find_member(full_path: &Path) -> Option<ArchiveMember> {
+    let archive_path = full_path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| filetype::is_archive(ancestor) && ancestor.is_file())?;
+    let name = full_path.strip_prefix(archive_path).ok()?.to_str()?.replace('\\', "/");
+
+    let mut archive = open_archive(archive_path).ok()?;
+    let file = archive.by_name(&name).ok()?;
+    if !file.is_file() || !is_listed_member(&name) {
+        return None;
+    }
+    Some(ArchiveMember {
+        archive_path: archive_path.to_path_buf(),
+        name,
+        size_bytes: file.size(),
+    })
+}
+
+impl ArchiveMember {
+    /// Path of the member as if the archive were a directory. Nothing exists there on disk.
+    pub fn full_path(&self) -> PathBuf {
+        self.archive_path.join(&self.name)
+    }
+
+    pub fn read(&self) -> std::io::Result<Vec<u8>> {
+        let mut archive = open_archive(&self.archive_path)?;
+        let mut file = archive.by_name(&self.name).map_err(std::io::Error::other)?;
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Extract to an anonymous temporary file, for code that needs a `File`.
+    pub fn extract_to_tempfile(&self) -> std::io::Result<std::fs::File> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&self.read()?)?;
+        file.rewind()?;
+        Ok(file)
+    }
+
+    /// Extract to a named temporary file with the member's extension, for external
+    /// tools (e.g. ffmpeg) that need a path.
+    pub fn extract_to_named_tempfile(&self) -> std::io::Result<tempfile::NamedTempFile> {
+        let extension = Path::new(&self.name).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let mut file = tempfile::Builder::new().suffix(&format!(".{}", extension)).tempfile()?;
+        file.write_all(&self.read()?)?;
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Write a zip with the given files
+    pub(crate) fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).expect("create"));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in files {
+            writer.start_file(*name, options).expect("start_file");
+            writer.write_all(contents).expect("write_all");
+        }
+        writer.finish().expect("finish");
+    }
+
+    #[test]
+    fn test_list_members() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let archive_path = tempdir.path().join("vol1.cbz");
+        write_zip(&archive_path, &[
+            ("ch1/10.jpg", b"ten"),
+            ("ch1/2.jpg", b"two"),
+            ("ch1/1.jpg", b"one"),
+            ("ComicInfo.xml", b"<ComicInfo/>"),
+            ("__MACOSX/ch1/._1.jpg", b""),
+        ]);
+
+        let members = list_members(&archive_path).expect("list_members");
+        let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["ch1/1.jpg", "ch1/2.jpg", "ch1/10.jpg"]);
+        assert_eq!(members[2].size_bytes, 3);
+        assert_eq!(members[2].read().expect("read"), b"ten");
+
+        let mut file = members[1].extract_to_tempfile().expect("extract_to_tempfile");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "two");
+
+        assert!(!archive_path.join("ch1").exists());
+        assert_eq!(find_member(&archive_path.join("ch1/10.jpg")), Some(members[2].clone()));
+        assert_eq!(find_member(&archive_path.join("ch1/3.jpg")), None);
+        assert_eq!(find_member(&archive_path.join("ComicInfo.xml")), None);
+        assert_eq!(find_member(&tempdir.path().join("other/1.jpg")), None);
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 
 use crate::{filetype, RepoPathBuf};
 
-use super::{FsEntry, FileType};
+use super::{archive, ArchiveMember, FsEntry, FileType};
 use super::metadata_file::info_json;
 
 const EADIR_NAME: &str = "@eaDir";
@@ -23,16 +23,42 @@ impl FsEntry {
         let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
         let metadata = std::fs::metadata(path)?;
 
+        let mut file_type = FileType::from_metadata(&metadata);
+        if file_type.is_file && filetype::is_archive(path) {
+            // Archives are browsed as directories of their images
+            file_type = FileType { is_file: false, is_dir: true, is_symlink: file_type.is_symlink };
+        }
+
         Ok(FsEntry {
             repo_path: RepoPathBuf::from_full_path(base_path, path).unwrap(),
             file_path: path.to_path_buf(),
             file_name: file_name,
 
-            file_type: FileType::from_metadata(&metadata),
+            file_type,
             size_bytes: metadata.len(),
             mod_time: mod_time_from_metadata(&metadata),
 
             is_metadata_file: is_metadata_file(path),
+            archive_member: None,
+        })
+    }
+
+    pub fn from_archive_member(member: ArchiveMember, base_path: &Path) -> std::io::Result<FsEntry> {
+        let file_path = member.full_path();
+        // Members share the archive's modification time
+        let archive_metadata = std::fs::metadata(&member.archive_path)?;
+
+        Ok(FsEntry {
+            repo_path: RepoPathBuf::from_full_path(base_path, &file_path).unwrap(),
+            file_name: file_path.file_name().unwrap().to_str().unwrap().to_string(),
+            file_path,
+
+            file_type: FileType { is_file: true, is_dir: false, is_symlink: false },
+            size_bytes: member.size_bytes,
+            mod_time: mod_time_from_metadata(&archive_metadata),
+
+            is_metadata_file: false,
+            archive_member: Some(member),
         })
     }
 
@@ -48,8 +74,12 @@ pub struct ListDirRecurIterator {
 
     read_dir: Option<std::fs::ReadDir>,
     paths: VecDeque<PathBuf>,
+    // Archive members, which are listed before continuing with read_dir
+    pending: VecDeque<FsEntry>,
 
     recurse: bool,
+    // List the members of archives found while recursing
+    expand_archives: bool,
 }
 
 impl ListDirRecurIterator {
@@ -90,8 +120,19 @@ impl Iterator for ListDirRecurIterator {
     type Item = FsEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fs_entry) = self.pending.pop_front() {
+            return Some(fs_entry);
+        }
+
         let dir_entry = self.next_entry().unwrap()?;
-        Some(FsEntry::from_dir_entry(&dir_entry, &self.file_tree.base_path).unwrap())
+        let fs_entry = FsEntry::from_dir_entry(&dir_entry, &self.file_tree.base_path).unwrap();
+        if self.expand_archives && fs_entry.is_archive() {
+            // Archives that can't be read are left empty, rather than failing the listing
+            if let Ok(members) = self.file_tree.list_archive(&fs_entry.file_path) {
+                self.pending.extend(members);
+            }
+        }
+        Some(fs_entry)
     }
 }
 
@@ -120,9 +161,7 @@ impl FileTree {
         }
 
         if !self.include_non_media {
-            let is_media = filetype::is_video(file_path) ||
-                filetype::is_image(file_path) ||
-                filetype::is_document(file_path);
+            let is_media = filetype::is_media(file_path) || filetype::is_archive(file_path);
 
             if !is_dir && !is_media {
                 // Only exception is for metadata files
@@ -160,14 +199,27 @@ impl FileTree {
 
     pub fn open_read(&self, repo_path: &RepoPathBuf) -> std::io::Result<std::fs::File> {
         let full_path = self.repo_to_full_path(repo_path);
+        if !full_path.exists() && let Some(member) = archive::find_member(&full_path) {
+            return member.extract_to_tempfile();
+        }
         std::fs::File::open(&full_path)
     }
 
     pub fn get_fs_entry(&self, repo_path: &RepoPathBuf) -> std::io::Result<FsEntry> {
         let full_path = self.repo_to_full_path(repo_path);
+        if !full_path.exists() && let Some(member) = archive::find_member(&full_path) {
+            return FsEntry::from_archive_member(member, &self.base_path);
+        }
         FsEntry::from_path(&full_path, &self.base_path)
     }
 
+    fn list_archive(&self, archive_path: &Path) -> std::io::Result<Vec<FsEntry>> {
+        archive::list_members(archive_path)?
+            .into_iter()
+            .map(|member| FsEntry::from_archive_member(member, &self.base_path))
+            .collect()
+    }
+
     // If this is a metadata file, get associated files and parsed metadata.
     pub fn read_metadata_file(&self, repo_path: &RepoPathBuf) -> std::io::Result<AssociatedInfo> {
         let full_path = self.repo_to_full_path(repo_path);
@@ -183,17 +235,43 @@ impl FileTree {
 
     pub fn listdir(&self, repo_path: &RepoPathBuf) -> std::io::Result<ListDirRecurIterator> {
         let full_path = self.repo_to_full_path(repo_path);
+        if full_path.is_file() && filetype::is_archive(&full_path) {
+            return Ok(ListDirRecurIterator {
+                file_tree: self.clone(),
+                read_dir: None,
+                paths: VecDeque::new(),
+                pending: self.list_archive(&full_path)?.into(),
+                recurse: false,
+                expand_archives: false,
+            });
+        }
+
         // Do the first read_dir outside the Iterator to catch errors early
         let read_dir = std::fs::read_dir(full_path)?;
         Ok(ListDirRecurIterator {
             file_tree: self.clone(),
             read_dir: Some(read_dir),
             paths: VecDeque::new(),
+            pending: VecDeque::new(),
             recurse: false,
+            expand_archives: false,
         })
     }
 
+    /// Every file and directory under `repo_path`. Archives are listed, but not what's
+    /// in them.
     pub fn list_recursive(&self, repo_path: &RepoPathBuf) -> std::io::Result<ListDirRecurIterator> {
+        self.list_recursive_impl(repo_path, false)
+    }
+
+    /// Like list_recursive, but also lists the members of each archive, after the
+    /// archive itself. Reading every archive is slow, so only use this when the
+    /// members are needed (e.g. to run jobs on them).
+    pub fn list_recursive_with_archives(&self, repo_path: &RepoPathBuf) -> std::io::Result<ListDirRecurIterator> {
+        self.list_recursive_impl(repo_path, true)
+    }
+
+    fn list_recursive_impl(&self, repo_path: &RepoPathBuf, expand_archives: bool) -> std::io::Result<ListDirRecurIterator> {
         let full_path = self.repo_to_full_path(repo_path);
         // Do the first read_dir outside the Iterator to catch errors early
         let read_dir = std::fs::read_dir(full_path)?;
@@ -201,7 +279,9 @@ impl FileTree {
             file_tree: self.clone(),
             read_dir: Some(read_dir),
             paths: VecDeque::new(),
+            pending: VecDeque::new(),
             recurse: true,
+            expand_archives,
         })
     }
 }
@@ -427,6 +507,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_archive_as_dir() -> std::io::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let file_root = tempdir.path();
+
+        std::fs::create_dir(file_root.join("Comics"))?;
+        archive::tests::write_zip(&file_root.join("Comics/vol1.cbz"), &[
+            ("2.png", b"page two"),
+            ("1.png", b"page one"),
+            ("notes.txt", b"not an image"),
+        ]);
+
+        let file_tree = FileTree {
+            base_path: file_root.to_path_buf(),
+            skip_paths: Vec::new(),
+            include_non_media: false,
+        };
+
+        let archive_entry = file_tree.get_fs_entry(&RepoPathBuf::from("Comics/vol1.cbz"))?;
+        assert!(archive_entry.file_type.is_dir);
+        assert!(archive_entry.is_archive());
+
+        let pages: Vec<String> = file_tree.listdir(&RepoPathBuf::from("Comics/vol1.cbz"))?
+            .map(|fl| fl.repo_path.0)
+            .collect();
+        assert_eq!(pages, vec!["Comics/vol1.cbz/1.png", "Comics/vol1.cbz/2.png"]);
+
+        // Recursive listings only include the pages when asked to, after the archive
+        let recurse_filenames: Vec<String> = file_tree.list_recursive(&RepoPathBuf::from(""))?
+            .map(|fl| fl.repo_path.0)
+            .collect();
+        assert_eq!(recurse_filenames, vec!["Comics", "Comics/vol1.cbz"]);
+        let recurse_filenames: Vec<String> = file_tree.list_recursive_with_archives(&RepoPathBuf::from(""))?
+            .map(|fl| fl.repo_path.0)
+            .collect();
+        assert_eq!(recurse_filenames, vec!["Comics", "Comics/vol1.cbz", "Comics/vol1.cbz/1.png", "Comics/vol1.cbz/2.png"]);
+
+        let page = file_tree.get_fs_entry(&RepoPathBuf::from("Comics/vol1.cbz/2.png"))?;
+        assert_eq!(page.file_name, "2.png");
+        assert_eq!(page.size_bytes, 8);
+        assert!(page.file_type.is_file);
+        assert!(page.archive_member.is_some());
+
+        let mut page_str = String::new();
+        file_tree.open_read(&RepoPathBuf::from("Comics/vol1.cbz/2.png"))?.read_to_string(&mut page_str)?;
+        assert_eq!(page_str, "page two");
+
+        assert!(file_tree.get_fs_entry(&RepoPathBuf::from("Comics/vol1.cbz/3.png")).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_include_non_media() -> std::io::Result<()> {
         let root = testdata_path("mixed");
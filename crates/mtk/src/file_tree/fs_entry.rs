@@ -4,6 +4,8 @@ use serde::Serialize;
 
 use crate::RepoPathBuf;
 
+use super::ArchiveMember;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FileType {
     pub is_file: bool,
@@ -34,6 +36,16 @@ pub struct FsEntry {
 
     // If true, this file is a "metadata file" that contains external info for other files.
     pub is_metadata_file: bool,
+
+    // Set for images inside an archive, which don't exist at `file_path`
+    pub archive_member: Option<ArchiveMember>,
+}
+
+impl FsEntry {
+    /// Whether this is an archive, which is browsed as a directory of its images
+    pub fn is_archive(&self) -> bool {
+        self.file_type.is_dir && crate::filetype::is_archive(&self.file_path) && self.file_path.is_file()
+    }
 }
 
 #[cfg(test)]
@@ -4,6 +4,8 @@ pub enum GeneratedFileType {
     AltFormat,
     Minivid,
     Subtitle,
+    Thumbnails,
+    HlsSegment,
 }
 
 #[derive(Debug)]
@@ -21,6 +23,8 @@ impl GeneratedFileType {
             GeneratedFileType::AltFormat => "AF",
             GeneratedFileType::Minivid => "MV",
             GeneratedFileType::Subtitle => "ST",
+            GeneratedFileType::Thumbnails => "TH",
+            GeneratedFileType::HlsSegment => "HS",
         }
     }
     
@@ -30,6 +34,8 @@ impl GeneratedFileType {
             "AF" => Ok(GeneratedFileType::AltFormat),
             "MV" => Ok(GeneratedFileType::Minivid),
             "ST" => Ok(GeneratedFileType::Subtitle),
+            "TH" => Ok(GeneratedFileType::Thumbnails),
+            "HS" => Ok(GeneratedFileType::HlsSegment),
             _ => Err(format!("Unknown two-letter code: {}", code)),
         }
     }
@@ -32,7 +32,18 @@ impl GeneratedTree {
         if !parent_dir.exists() {
             std::fs::create_dir_all(&parent_dir).expect("create_dir_all");
         }
+        self.generated_file_path(gfile)
+    }
+
+    /// Path to the generated file if it exists. Unlike path_to_generated_file, this
+    /// doesn't create any directories, so it's fine for read-only lookups.
+    pub fn existing_generated_file(&self, gfile: &GeneratedFile) -> Option<PathBuf> {
+        let path = self.generated_file_path(gfile);
+        path.exists().then_some(path)
+    }
 
+    fn generated_file_path(&self, gfile: &GeneratedFile) -> PathBuf {
+        let parent_dir = self.parent_dir(gfile.entry_id);
         let filename = format!(
             "{}__{}.{}.{}",
             gfile.entry_id,
@@ -119,6 +130,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_existing_generated_file() -> std::io::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let gen_tree = GeneratedTree::new(tempdir.path());
+
+        let gfile = GeneratedFile {
+            entry_id: 123,
+            file_type: GeneratedFileType::Subtitle,
+            metadata: "en_US".to_string(),
+            extension: "vtt".to_string(),
+        };
+        assert_eq!(gen_tree.existing_generated_file(&gfile), None);
+        // Nothing is created by the lookup
+        assert!(!tempdir.path().join("generated").exists());
+
+        let path = gen_tree.path_to_generated_file(&gfile);
+        std::fs::write(&path, "WEBVTT")?;
+        assert_eq!(gen_tree.existing_generated_file(&gfile), Some(path));
+
+        Ok(())
+    }
+
     #[test]
     fn test_path_to_generated_file_large_entry_id() -> std::io::Result<()> {
         let tempdir = tempfile::tempdir()?;
@@ -41,6 +41,8 @@ pub fn is_info_json(path: &Path) -> bool {
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedInfoJson {
     pub linked_urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
 }
 
 impl ParsedInfoJson {
@@ -53,7 +55,13 @@ impl ParsedInfoJson {
             _ => Vec::new(),
         };
 
-        ParsedInfoJson { linked_urls }
+        // yt-dlp uses "channel" and/or "uploader"; gallery-dl sites often use "author"
+        let uploader = ["channel", "uploader", "author"]
+            .iter()
+            .find_map(|key| raw_info[key].as_str())
+            .map(|s| s.to_owned());
+
+        ParsedInfoJson { linked_urls, uploader }
     }
 
     #[cfg(test)]
@@ -112,6 +120,21 @@ mod tests {
         }"#,
         );
         assert_eq!(parsed.linked_urls.len(), 0);
+        assert_eq!(parsed.uploader, None);
+    }
+
+    #[test]
+    fn test_uploader() {
+        let parsed = ParsedInfoJson::from_str(
+            r#"{
+            "uploader": "someone",
+            "channel": "Some Channel"
+        }"#,
+        );
+        assert_eq!(parsed.uploader.as_deref(), Some("Some Channel"));
+
+        let parsed = ParsedInfoJson::from_str(r#"{ "author": { "name": "x" }, "uploader": "someone" }"#);
+        assert_eq!(parsed.uploader.as_deref(), Some("someone"));
     }
 
     #[test]
@@ -1,5 +1,8 @@
 mod abs_path;
 
+pub mod archive;
+pub use archive::ArchiveMember;
+
 mod fs_entry;
 pub use fs_entry::FileType;
 pub use fs_entry::FsEntry;
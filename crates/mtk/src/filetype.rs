@@ -2,7 +2,10 @@ use std::path::Path;
 
 pub const VIDEO_EXTENSIONS: [&'static str; 5] = ["mp4", "m4v", "mkv", "wmv", "webm"];
 pub const IMAGE_EXTENSIONS: [&'static str; 6] = ["jpg", "jpeg", "png", "webp", "avif", "gif"];
+pub const AUDIO_EXTENSIONS: [&str; 5] = ["mp3", "m4a", "flac", "opus", "ogg"];
 pub const DOCUMENT_EXTENSIONS: [&'static str; 4] = ["txt", "md", "pdf", "epub"];
+/// Zips of images (comics), browsed as directories
+pub const ARCHIVE_EXTENSIONS: [&str; 2] = ["cbz", "zip"];
 
 pub fn is_video(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
@@ -20,6 +23,14 @@ pub fn is_image(path: &Path) -> bool {
     }
 }
 
+pub fn is_audio(path: &Path) -> bool {
+    if let Some(ext) = path.extension() {
+        AUDIO_EXTENSIONS.iter().any(|&e| e == ext)
+    } else {
+        false
+    }
+}
+
 pub fn is_document(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
         DOCUMENT_EXTENSIONS.iter().any(|&e| e == ext)
@@ -28,6 +39,14 @@ pub fn is_document(path: &Path) -> bool {
     }
 }
 
+pub fn is_archive(path: &Path) -> bool {
+    if let Some(ext) = path.extension() {
+        ARCHIVE_EXTENSIONS.iter().any(|&e| e == ext)
+    } else {
+        false
+    }
+}
+
 pub fn is_media(path: &Path) -> bool {
-    is_video(path) || is_image(path) || is_document(path)
+    is_video(path) || is_image(path) || is_audio(path) || is_document(path)
 }
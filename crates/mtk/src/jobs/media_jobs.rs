@@ -1,7 +1,12 @@
 use std::path::{Path, PathBuf};
 
 use crate::{
-    file_tree::{GeneratedFile, GeneratedTree},
+    file_tree::{archive, GeneratedFile, GeneratedTree},
+    media::audio,
+    media::dir_preview,
+    media::document::{self, DocumentFormat},
+    media::image,
+    media::phash,
     media::video::subtitle,
     media::video::{self, transcode::TranscodeProfile},
     preview,
@@ -14,6 +19,36 @@ pub fn generate_video_info_job_spec() -> impl crate::jobs::JobSpec {
         |entry: &crate::Entry| crate::filetype::is_video(&entry.fs.file_path),
         |fs_entry: &crate::FsEntry| video::get_video_info(&fs_entry.file_path),
     )
+    .with_version(video::VIDEO_INFO_VERSION)
+}
+
+pub fn generate_audio_info_job_spec() -> impl crate::jobs::JobSpec {
+    super::misc_jobs::UpdateGeneratedNotesJobSpec::new(
+        "audio_info",
+        audio::AUDIO_INFO_GROUP_NAME,
+        |entry: &crate::Entry| crate::filetype::is_audio(&entry.fs.file_path),
+        |fs_entry: &crate::FsEntry| audio::get_audio_info(&fs_entry.file_path),
+    )
+}
+
+pub fn generate_document_info_job_spec() -> impl crate::jobs::JobSpec {
+    super::misc_jobs::UpdateGeneratedNotesJobSpec::new(
+        "document_info",
+        document::DOCUMENT_GROUP_NAME,
+        |entry: &crate::Entry| DocumentFormat::from_path(&entry.fs.file_path).is_some(),
+        |fs_entry: &crate::FsEntry| document::get_document_info(&fs_entry.file_path),
+    )
+}
+
+pub fn generate_image_info_job_spec() -> impl crate::jobs::JobSpec {
+    super::misc_jobs::UpdateGeneratedNotesJobSpec::new(
+        "image_info",
+        image::IMAGE_INFO_GROUP_NAME,
+        // Pages in archives aren't on disk, and don't need EXIF anyway
+        |entry: &crate::Entry| crate::filetype::is_image(&entry.fs.file_path) && entry.fs.archive_member.is_none(),
+        |fs_entry: &crate::FsEntry| image::get_image_info(&fs_entry.file_path),
+    )
+    .with_version(image::IMAGE_INFO_VERSION)
 }
 
 ////////////////////////////////
@@ -26,13 +61,33 @@ fn run_preview_job(
     media_path: &Path,
     preview_out_path: &Path,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    // Archives are previewed by their first page. Pages need extracting first, since
+    // ffmpeg can't read them from the archive.
+    let archive_member = if crate::filetype::is_archive(media_path) && media_path.is_file() {
+        Some(archive::list_members(media_path)?.into_iter().next().ok_or("Archive has no images")?)
+    } else if !media_path.exists() {
+        archive::find_member(media_path)
+    } else {
+        None
+    };
+    if let Some(member) = archive_member {
+        if preview_out_path.exists() {
+            return Ok(()); // Preview already exists, nothing to do
+        }
+
+        println!("PreviewJob: making preview for {:?}", member.full_path());
+        let extracted = member.extract_to_named_tempfile()?;
+        crate::media::image::try_make_preview_image(extracted.path(), preview_out_path)?;
+        return Ok(());
+    }
+
     if crate::filetype::is_video(media_path) {
         if preview_out_path.exists() {
             return Ok(()); // Preview already exists, nothing to do
         }
 
         println!("PreviewJob: making preview for {:?}", media_path);
-        crate::media::video::video_preview::make_preview_image(media_path, preview_out_path);
+        video::video_preview::make_preview_image(media_path, preview_out_path, None)?;
     } else if crate::filetype::is_image(media_path) {
         if preview_out_path.exists() {
             return Ok(()); // Preview already exists, nothing to do
@@ -40,6 +95,20 @@ fn run_preview_job(
 
         println!("PreviewJob: making preview for {:?}", media_path);
         crate::media::image::make_preview_image(media_path, preview_out_path);
+    } else if crate::filetype::is_audio(media_path) {
+        if preview_out_path.exists() {
+            return Ok(()); // Preview already exists, nothing to do
+        }
+
+        println!("PreviewJob: extracting cover art for {:?}", media_path);
+        audio::make_preview_image(media_path, preview_out_path)?;
+    } else if DocumentFormat::from_path(media_path).is_some() {
+        if preview_out_path.exists() {
+            return Ok(()); // Preview already exists, nothing to do
+        }
+
+        println!("PreviewJob: making preview for {:?}", media_path);
+        document::make_preview_image(media_path, preview_out_path)?;
     } else {
         // Other filetypes not supported yet
         return Err(format!("Unsupported file type for preview: {:?}", media_path).into());
@@ -48,6 +117,15 @@ fn run_preview_job(
     Ok(())
 }
 
+/// Standalone function to check if a preview can be made from the entry's file type
+fn is_previewable(entry: &crate::Entry) -> bool {
+    let path = &entry.fs.file_path;
+    crate::filetype::is_video(path)
+        || crate::filetype::is_image(path)
+        || DocumentFormat::from_path(path).is_some()
+        || entry.fs.is_archive()
+}
+
 impl super::JobSpec for PreviewJobSpec {
     fn job_type(&self) -> &str {
         "preview"
@@ -58,25 +136,51 @@ impl super::JobSpec for PreviewJobSpec {
         stash: &crate::Vault,
         entry: &crate::Entry,
     ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
-        if crate::filetype::is_video(&entry.fs.file_path) {
-        } else if crate::filetype::is_image(&entry.fs.file_path) {
-        } else {
+        if crate::filetype::is_audio(&entry.fs.file_path) {
+            // Only files with embedded cover art get previews
+            if !audio::read_audio_info(&entry.db).is_some_and(|info| info.has_cover_art) {
+                return Ok(None);
+            }
+        } else if !is_previewable(entry) {
             return Ok(None);
         }
 
         if entry.fs.file_path.is_dir() {
-            // TODO: skip directories for now (later, generate dir previews from the contents)
+            // Directory previews are made from the contents by DirPreviewJobSpec
             return Ok(None);
         }
 
         let gen_tree = stash.new_generated_tree();
         let preview_path = preview::get_preview(entry.db.id, &gen_tree);
+        let media_path = entry.fs.file_path.clone();
+
+        if crate::filetype::is_video(&entry.fs.file_path) {
+            if preview_path.exists() && !video::video_preview::is_preview_stale(&entry.db) {
+                return Ok(None);
+            }
+
+            // Record which frame was used, so that pinning a new one can be detected
+            let entry_id = entry.db.id;
+            let pinned_timestamp = entry.db.preview_timestamp();
+            let mut catalog = stash.open_catalog()?;
+            return Ok(Some(Box::new(move || {
+                println!("PreviewJob: making preview for {:?}", media_path);
+                let frame = video::video_preview::make_preview_image(&media_path, &preview_path, pinned_timestamp)?;
+                crate::catalog::generated_notes::update(
+                    &mut catalog,
+                    entry_id,
+                    video::video_preview::PREVIEW_GROUP_NAME,
+                    &frame,
+                );
+                Ok(())
+            })));
+        }
+
         if preview_path.exists() {
             // Preview already exists, don't create a new one
             return Ok(None);
         }
 
-        let media_path = entry.fs.file_path.clone();
         Ok(Some(Box::new(move || {
             run_preview_job(&media_path, &preview_path)
         })))
@@ -84,15 +188,249 @@ impl super::JobSpec for PreviewJobSpec {
 }
 
 ////////////////////////////////
-// TranscodeChromeJobSpec
+// DirPreviewJobSpec
+////////////////////////////////
+
+/// Make a preview for directories by tiling the previews of a few children. Remade
+/// whenever a different set of children is picked, or their previews change.
+pub struct DirPreviewJobSpec;
+
+impl super::JobSpec for DirPreviewJobSpec {
+    fn job_type(&self) -> &str {
+        "dir_preview"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        // Archives use their first page instead, from PreviewJobSpec
+        if !entry.fs.file_type.is_dir || entry.fs.is_archive() {
+            return Ok(None);
+        }
+
+        let gen_tree = stash.new_generated_tree();
+        let mut catalog = stash.open_catalog()?;
+        let children = dir_preview::pick_children(&entry.fs.repo_path, &stash.new_file_tree(), &mut catalog, &gen_tree)?;
+        if children.is_empty() {
+            // Wait for the children's previews
+            return Ok(None);
+        }
+
+        let preview_path = preview::get_preview(entry.db.id, &gen_tree);
+        if !dir_preview::is_stale(&entry.db, &children, &preview_path) {
+            return Ok(None);
+        }
+
+        let entry_id = entry.db.id;
+        let dir_path = entry.fs.file_path.clone();
+        Ok(Some(Box::new(move || {
+            println!("DirPreviewJob: making preview for {:?}", dir_path);
+            let child_previews: Vec<PathBuf> = children.iter().map(|child| child.preview_path.clone()).collect();
+            dir_preview::make_dir_preview(&child_previews, &preview_path)?;
+            let record = dir_preview::DirPreview {
+                children: children.iter().map(|child| child.entry_id).collect(),
+            };
+            crate::catalog::generated_notes::update(&mut catalog, entry_id, dir_preview::DIR_PREVIEW_GROUP_NAME, &record);
+            Ok(())
+        })))
+    }
+}
+
+////////////////////////////////
+// MinividJobSpec
+////////////////////////////////
+
+/// Make a short montage of the video, which is played when hovering over it in listings.
+/// Animated images get one too, made from their first few seconds.
+pub struct MinividJobSpec;
+
+/// Whether the image_info job found the image to be animated
+fn is_animated_image(entry: &crate::Entry) -> bool {
+    crate::filetype::is_image(&entry.fs.file_path)
+        && image::image_info::read_image_info(&entry.db).is_some_and(|info| info.animated)
+}
+
+impl super::JobSpec for MinividJobSpec {
+    fn job_type(&self) -> &str {
+        "minivid"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        let is_video = crate::filetype::is_video(&entry.fs.file_path);
+        if !is_video && !is_animated_image(entry) {
+            return Ok(None);
+        }
+
+        let gfile = video::video_preview::minivid_gfile(entry.db.id);
+        let minivid_path = stash.new_generated_tree().path_to_generated_file(&gfile);
+        if minivid_path.exists() {
+            return Ok(None);
+        }
+
+        let file_path = entry.fs.file_path.clone();
+        Ok(Some(Box::new(move || {
+            println!("MinividJob: making minivid for {:?}", file_path);
+            if is_video {
+                video::video_preview::make_minivid(&file_path, &minivid_path)
+            } else {
+                image::animated::make_minivid(&file_path, &minivid_path)
+            }
+        })))
+    }
+}
+
+////////////////////////////////
+// GifTranscodeJobSpec
 ////////////////////////////////
 
-/// Transcode video to something that Chrome can play.
-pub struct TranscodeChromeJobSpec {
-    // TODO: specify the target profile
+/// Convert large animated GIFs to a looping webm, which the entry page shows instead.
+pub struct GifTranscodeJobSpec;
+
+impl super::JobSpec for GifTranscodeJobSpec {
+    fn job_type(&self) -> &str {
+        "gif_transcode"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        let is_gif = entry
+            .fs
+            .file_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        if !is_gif || entry.fs.size_bytes < image::animated::GIF_TRANSCODE_MIN_BYTES || !is_animated_image(entry) {
+            return Ok(None);
+        }
+
+        let gfile = image::animated::gif_webm_gfile(entry.db.id);
+        let webm_path = stash.new_generated_tree().path_to_generated_file(&gfile);
+        if webm_path.exists() {
+            return Ok(None);
+        }
+
+        let gif_path = entry.fs.file_path.clone();
+        Ok(Some(Box::new(move || {
+            println!("GifTranscodeJob: transcoding {:?}", gif_path);
+            image::animated::transcode_gif(&gif_path, &webm_path)
+        })))
+    }
 }
 
-impl crate::jobs::JobSpec for TranscodeChromeJobSpec {
+////////////////////////////////
+// ThumbnailsJobSpec
+////////////////////////////////
+
+/// Make a sprite sheet and WebVTT thumbnails track for scrubbing through long videos.
+pub struct ThumbnailsJobSpec;
+
+impl super::JobSpec for ThumbnailsJobSpec {
+    fn job_type(&self) -> &str {
+        "thumbnails"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        if !crate::filetype::is_video(&entry.fs.file_path) {
+            return Ok(None);
+        }
+
+        // Wait for the video_info job
+        let Some(video_info) = crate::catalog::generated_notes::read::<video::VideoInfo>(
+            &entry.db,
+            video::VIDEO_INFO_GROUP_NAME,
+        ) else {
+            return Ok(None);
+        };
+        if video_info.duration_secs < video::thumbnails::THUMBNAILS_MIN_DURATION_SECS {
+            return Ok(None);
+        }
+
+        let gen_tree = stash.new_generated_tree();
+        let vtt_path = gen_tree.path_to_generated_file(&video::thumbnails::thumbnails_vtt_gfile(entry.db.id));
+        if vtt_path.exists() {
+            return Ok(None);
+        }
+        let sprite_path = gen_tree.path_to_generated_file(&video::thumbnails::sprite_gfile(entry.db.id));
+
+        let video_path = entry.fs.file_path.clone();
+        Ok(Some(Box::new(move || {
+            println!("ThumbnailsJob: making thumbnails for {:?}", video_path);
+            video::thumbnails::make_thumbnails(&video_path, &video_info, &sprite_path, &vtt_path)
+        })))
+    }
+}
+
+////////////////////////////////
+// PerceptualHashJobSpec
+////////////////////////////////
+
+/// Compute a perceptual hash for images, and for the preview frame of videos.
+pub struct PerceptualHashJobSpec;
+
+impl super::JobSpec for PerceptualHashJobSpec {
+    fn job_type(&self) -> &str {
+        "phash"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        let (hash_path, preview_mtime) = if crate::filetype::is_image(&entry.fs.file_path) && entry.fs.archive_member.is_none() {
+            (entry.fs.file_path.clone(), None)
+        } else if crate::filetype::is_video(&entry.fs.file_path) || entry.fs.archive_member.is_some() {
+            let preview_path = preview::get_preview(entry.db.id, &stash.new_generated_tree());
+            let Ok(metadata) = std::fs::metadata(&preview_path) else {
+                // Wait for the preview job to pick a frame
+                return Ok(None);
+            };
+            let preview_mtime = chrono::DateTime::<chrono::Utc>::from(metadata.modified()?).timestamp();
+            (preview_path, Some(preview_mtime))
+        } else {
+            return Ok(None);
+        };
+
+        // Previews can be remade (e.g. when a frame is pinned) without the file changing
+        let stored = crate::catalog::generated_notes::read::<phash::PerceptualHash>(&entry.db, phash::PHASH_GROUP_NAME);
+        let is_current = stored.is_some_and(|hash| hash.preview_mtime == preview_mtime)
+            && !crate::catalog::generated_notes::needs_update(entry, phash::PHASH_GROUP_NAME);
+        if is_current {
+            return Ok(None);
+        }
+
+        let entry_id = entry.db.id;
+        let mut catalog = stash.open_catalog()?;
+        Ok(Some(Box::new(move || {
+            println!("PerceptualHashJob: hashing {:?}", hash_path);
+            let mut hash = phash::get_perceptual_hash(&hash_path)?;
+            hash.preview_mtime = preview_mtime;
+            crate::query::visual::record_phash(&mut catalog, entry_id, &hash);
+            Ok(())
+        })))
+    }
+}
+
+////////////////////////////////
+// TranscodeJobSpec
+////////////////////////////////
+
+/// Transcode video to something that all the configured clients can play.
+pub struct TranscodeJobSpec;
+
+impl crate::jobs::JobSpec for TranscodeJobSpec {
     fn job_type(&self) -> &str {
         "transcode"
     }
@@ -102,14 +440,26 @@ impl crate::jobs::JobSpec for TranscodeChromeJobSpec {
         stash: &crate::Vault,
         entry: &crate::Entry,
     ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
-        if !is_transcode_needed_chrome(entry) {
+        let clients = &stash.config.playback_clients;
+        if !is_transcode_needed(entry, clients) {
             return Ok(None);
         }
+        // Most preferred first: the first one that all the clients can play is used
+        let mut profile = None;
+        for name in &stash.config.transcode_job_profiles {
+            let candidate = stash.config.transcode_profile(name)?;
+            if clients.iter().all(|client| client.can_play_profile(&candidate)) {
+                profile = Some(candidate);
+                break;
+            }
+        }
+        let profile = profile.ok_or_else(|| format!("No transcode profile plays in all of {:?}", clients))?;
         let video_path = entry.fs.file_path.clone();
         let entry_id = entry.db.id;
         let gen_tree = stash.new_generated_tree();
+        let mut catalog = stash.open_catalog()?;
         Ok(Some(Box::new(move || {
-            run_transcode_job(&video_path, entry_id, &gen_tree)
+            run_transcode_job(&video_path, entry_id, &profile, &gen_tree, &mut catalog)
         })))
     }
 }
@@ -118,29 +468,26 @@ impl crate::jobs::JobSpec for TranscodeChromeJobSpec {
 pub fn run_transcode_job(
     video_path: &Path,
     entry_id: i64,
+    profile: &TranscodeProfile,
     gen_tree: &GeneratedTree,
+    catalog: &mut crate::catalog::Catalog,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    for profile in [TranscodeProfile::AV1_400K] {
-        let gfile = GeneratedFile {
-            entry_id,
-            file_type: crate::file_tree::GeneratedFileType::AltFormat,
-            metadata: "av1_400k".to_string(),
-            extension: "webm".to_string(),
-        };
-        let gen_path = gen_tree.path_to_generated_file(&gfile);
-        if !gen_path.exists() {
-            println!(
-                "TranscodeJob: transcoding {:?} ({}) to {:?}",
-                video_path, entry_id, profile
-            );
-            video::transcode::transcode_alt_format(video_path, &gen_path, profile);
-        }
+    let gfile = profile.to_gen_file(entry_id);
+    let gen_path = gen_tree.path_to_generated_file(&gfile);
+    if !gen_path.exists() {
+        println!(
+            "TranscodeJob: transcoding {:?} ({}) to {:?}",
+            video_path, entry_id, profile.name
+        );
+        video::transcode::transcode_alt_format(video_path, &gen_path, profile)?;
+        // Cache the info now, so the player doesn't need to probe it
+        video::video_info::record_alt_format_info(catalog, entry_id, &gfile, &gen_path)?;
     }
     Ok(())
 }
 
 /// Standalone function to check if a transcode job is needed
-fn is_transcode_needed_chrome(entry: &crate::Entry) -> bool {
+fn is_transcode_needed(entry: &crate::Entry, clients: &[video::Client]) -> bool {
     if !crate::filetype::is_video(&entry.fs.file_path) {
         return false;
     }
@@ -148,7 +495,7 @@ fn is_transcode_needed_chrome(entry: &crate::Entry) -> bool {
         &entry.db,
         video::VIDEO_INFO_GROUP_NAME,
     ) {
-        Some(video_info) => !video::chrome_can_play(&video_info),
+        Some(video_info) => !video::playability::all_can_play(clients, &video_info),
         None => {
             // Don't do anything if we don't have video info yet. We don't
             // want to over-transcode and waste CPU/disk.
@@ -196,11 +543,11 @@ impl crate::jobs::JobSpec for ConvertSubtitlesJobSpec {
 
         let potential_to_convert = subtitle::find_non_vtt_subtitles(&video_path);
         let mut to_convert = Vec::new();
-        for (lang_country, subtitle) in potential_to_convert {
+        for subtitle in potential_to_convert {
             let gfile = GeneratedFile {
                 entry_id,
                 file_type: crate::file_tree::GeneratedFileType::Subtitle,
-                metadata: lang_country,
+                metadata: subtitle.key.clone(),
                 extension: "vtt".to_string(),
             };
             let path = gen_tree.path_to_generated_file(&gfile);
@@ -221,6 +568,56 @@ impl crate::jobs::JobSpec for ConvertSubtitlesJobSpec {
     }
 }
 
+////////////////////////////////
+// BurnInSubtitlesJobSpec
+////////////////////////////////
+
+/// Burn bitmap subtitles (which can't be converted to WebVTT) into an alt format, if
+/// a profile is configured for it.
+pub struct BurnInSubtitlesJobSpec;
+
+impl crate::jobs::JobSpec for BurnInSubtitlesJobSpec {
+    fn job_type(&self) -> &str {
+        "burn_subtitles"
+    }
+
+    fn create_job(
+        &self,
+        stash: &crate::Vault,
+        entry: &crate::Entry,
+    ) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        let Some(profile_str) = &stash.config.burn_in_subtitles_profile else {
+            return Ok(None);
+        };
+        let profile = stash.config.transcode_profile(profile_str)?;
+        if !crate::filetype::is_video(&entry.fs.file_path) {
+            return Ok(None);
+        }
+
+        let video_path = entry.fs.file_path.clone();
+        let Some(subtitle) = subtitle::find_burn_in_subtitle(&video_path, &stash.config.preferred_subtitle_languages) else {
+            return Ok(None);
+        };
+        let entry_id = entry.db.id;
+        let gfile = profile.to_burned_in_gen_file(entry_id, &subtitle.key);
+        let gen_path = stash.new_generated_tree().path_to_generated_file(&gfile);
+        if gen_path.exists() {
+            return Ok(None);
+        }
+
+        let mut catalog = stash.open_catalog()?;
+        Ok(Some(Box::new(move || {
+            println!(
+                "BurnInSubtitlesJob: burning {:?} into {:?} ({:?})",
+                subtitle, video_path, profile.name
+            );
+            video::transcode::transcode_alt_format_burn_in(&video_path, &gen_path, &profile, Some(&subtitle))?;
+            video::video_info::record_alt_format_info(&mut catalog, entry_id, &gfile, &gen_path)?;
+            Ok(())
+        })))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -306,6 +703,266 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preview_job_spec_audio() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::write(file_root.path().join("song.mp3"), "")?;
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let spec = PreviewJobSpec {};
+
+        // No audio info yet, or no cover art
+        let song = testing::entry_for("song.mp3", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &song)?.is_none());
+        crate::catalog::generated_notes::update(&mut catalog, song.db.id, audio::AUDIO_INFO_GROUP_NAME, &audio::AudioInfo::default());
+        let song = testing::entry_for("song.mp3", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &song)?.is_none());
+
+        let with_cover_art = audio::AudioInfo { has_cover_art: true, ..Default::default() };
+        crate::catalog::generated_notes::update(&mut catalog, song.db.id, audio::AUDIO_INFO_GROUP_NAME, &with_cover_art);
+        let song = testing::entry_for("song.mp3", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &song)?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_job_spec_documents() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::write(file_root.path().join("book.epub"), "")?;
+        std::fs::write(file_root.path().join("notes.txt"), "")?;
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let spec = PreviewJobSpec {};
+
+        // Only paged documents
+        let book = testing::entry_for("book.epub", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &book)?.is_some());
+        let notes = testing::entry_for("notes.txt", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &notes)?.is_none());
+
+        // Not a valid EPUB
+        assert!(run_preview_job(&book.fs.file_path, &file_root.path().join("book.webp")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_job_spec_archives() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        let square = std::fs::read(testing::testdata_path("preview").join("square.png"))?;
+        crate::file_tree::archive::tests::write_zip(&file_root.path().join("vol1.cbz"), &[
+            ("2.png", b"not an image"),
+            ("1.png", &square),
+        ]);
+        crate::file_tree::archive::tests::write_zip(&file_root.path().join("empty.zip"), &[]);
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let spec = PreviewJobSpec {};
+
+        let archive = testing::entry_for("vol1.cbz", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &archive)?.is_some());
+        assert!(DirPreviewJobSpec.create_job(&vault, &archive)?.is_none());
+        let page = testing::entry_for("vol1.cbz/1.png", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &page)?.is_some());
+
+        let empty = testing::entry_for("empty.zip", &file_tree, &mut catalog)?;
+        assert!(run_preview_job(&empty.fs.file_path, &file_root.path().join("empty.webp")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_job_spec_pinned_timestamp() {
+        let file_root = testing::testdata_path("preview");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = PreviewJobSpec {};
+
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        let preview_path = preview::get_preview(video.db.id, &vault.new_generated_tree());
+        std::fs::write(&preview_path, "").expect("write preview");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+
+        // Pinning a timestamp remakes the existing preview
+        catalog.set_single_note(video.db.id, crate::catalog::sqlite_catalog::WhichNotes::User, "preview_timestamp", serde_json::json!(1.5));
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_some());
+
+        let frame = video::video_preview::PreviewFrame { timestamp_secs: 1.5, pinned: true };
+        crate::catalog::generated_notes::update(&mut catalog, video.db.id, video::video_preview::PREVIEW_GROUP_NAME, &frame);
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+    }
+
+    #[test]
+    fn test_dir_preview_job_spec() {
+        let file_root = testing::testdata_path("mixed");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = DirPreviewJobSpec {};
+
+        // Not a directory
+        let photo = testing::entry_for("Photos/cats_tall.jpg", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &photo).expect("create_job").is_none());
+
+        // No child previews yet
+        let dir = testing::entry_for("Photos", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &dir).expect("create_job").is_none());
+
+        std::fs::write(preview::get_preview(photo.db.id, &vault.new_generated_tree()), "").expect("write preview");
+        assert!(spec.create_job(&vault, &dir).expect("create_job").is_some());
+    }
+
+    #[test]
+    fn test_minivid_job_spec() {
+        let file_root = testing::testdata_path("preview");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = MinividJobSpec {};
+
+        // Only videos and animated images
+        let image = testing::entry_for("square.png", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &image).expect("create_job").is_none());
+
+        let image_info = image::ImageInfo { width: 100, height: 100, animated: true, ..Default::default() };
+        crate::catalog::generated_notes::update(&mut catalog, image.db.id, image::IMAGE_INFO_GROUP_NAME, &image_info);
+        let image = testing::entry_for("square.png", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &image).expect("create_job").is_some());
+
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_some());
+
+        // Minivid already exists
+        let gfile = video::video_preview::minivid_gfile(video.db.id);
+        std::fs::write(vault.new_generated_tree().path_to_generated_file(&gfile), "").expect("write minivid");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+    }
+
+    #[test]
+    fn test_gif_transcode_job_spec() {
+        let file_root = tempfile::tempdir().expect("tempdir");
+        let big_size = image::animated::GIF_TRANSCODE_MIN_BYTES as usize;
+        std::fs::write(file_root.path().join("big.gif"), vec![0; big_size]).expect("write gif");
+        std::fs::write(file_root.path().join("small.gif"), vec![0; 1000]).expect("write gif");
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path()).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = GifTranscodeJobSpec {};
+        let image_info = image::ImageInfo { width: 100, height: 100, animated: true, ..Default::default() };
+
+        // No image info yet
+        let gif = testing::entry_for("big.gif", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &gif).expect("create_job").is_none());
+
+        crate::catalog::generated_notes::update(&mut catalog, gif.db.id, image::IMAGE_INFO_GROUP_NAME, &image_info);
+        let gif = testing::entry_for("big.gif", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &gif).expect("create_job").is_some());
+
+        // Too small to bother
+        let small = testing::entry_for("small.gif", &file_tree, &mut catalog).expect("entry_for");
+        crate::catalog::generated_notes::update(&mut catalog, small.db.id, image::IMAGE_INFO_GROUP_NAME, &image_info);
+        let small = testing::entry_for("small.gif", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &small).expect("create_job").is_none());
+
+        // Already transcoded
+        let gfile = image::animated::gif_webm_gfile(gif.db.id);
+        std::fs::write(vault.new_generated_tree().path_to_generated_file(&gfile), "").expect("write webm");
+        assert!(spec.create_job(&vault, &gif).expect("create_job").is_none());
+    }
+
+    #[test]
+    fn test_thumbnails_job_spec() {
+        let file_root = testing::testdata_path("preview");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = ThumbnailsJobSpec {};
+
+        // No video info yet
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+
+        let mut video_info = video::VideoInfo {
+            mime_type: "video/mp4".to_string(),
+            codec: "h264".to_string(),
+            codec_rfc6381: "avc1".to_string(),
+            duration_secs: 10.0,
+            width: 320,
+            height: 240,
+            bitrate: 1000,
+            ..Default::default()
+        };
+        crate::catalog::generated_notes::update(&mut catalog, video.db.id, video::VIDEO_INFO_GROUP_NAME, &video_info);
+
+        // Too short
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+
+        video_info.duration_secs = 600.0;
+        crate::catalog::generated_notes::update(&mut catalog, video.db.id, video::VIDEO_INFO_GROUP_NAME, &video_info);
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_some());
+
+        // Thumbnails already exist
+        let gfile = video::thumbnails::thumbnails_vtt_gfile(video.db.id);
+        std::fs::write(vault.new_generated_tree().path_to_generated_file(&gfile), "").expect("write vtt");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+    }
+
+    #[test]
+    fn test_phash_job_spec_needs_preview() {
+        let file_root = testing::testdata_path("preview");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root).expect("tempdir_stash");
+
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog().expect("open_catalog");
+        let spec = PerceptualHashJobSpec {};
+
+        // Not video or image
+        assert!(
+            spec.create_job(&vault, &testing::fake_entry("make_testdata.sh"))
+                .expect("create_job")
+                .is_none()
+        );
+
+        // Images are hashed directly
+        let image = testing::entry_for("square.png", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &image).expect("create_job").is_some());
+
+        // Videos wait until there's a preview frame
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+
+        let preview_path = preview::get_preview(video.db.id, &vault.new_generated_tree());
+        std::fs::create_dir_all(preview_path.parent().unwrap()).expect("create_dir_all");
+        std::fs::write(&preview_path, "").expect("write preview");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_some());
+
+        // Hashed already, from the current preview
+        let preview_file = std::fs::File::options().write(true).open(&preview_path).expect("open preview");
+        preview_file.set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000)).expect("set_modified");
+        let hash = phash::PerceptualHash { preview_mtime: Some(1000), ..phash::PerceptualHash::from_dhash(1) };
+        crate::query::visual::record_phash(&mut catalog, video.db.id, &hash);
+        let video = testing::entry_for("short_video.mp4", &file_tree, &mut catalog).expect("entry_for");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_none());
+
+        // The preview was remade
+        preview_file.set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(2000)).expect("set_modified");
+        assert!(spec.create_job(&vault, &video).expect("create_job").is_some());
+    }
+
     #[test]
     #[ignore]
     fn test_transcode_job_run() -> Result<(), Box<dyn std::error::Error>> {
@@ -313,19 +970,27 @@ mod tests {
         let (_tempdir, stash) = testing::tempdir_vault(&file_root)?;
 
         let video_path = file_root.join("vidaud_h265_aac.mkv");
+        let mut catalog = stash.open_catalog()?;
+        let entry = testing::entry_for("vidaud_h265_aac.mkv", &stash.new_file_tree(), &mut catalog)?;
 
         let gen_tree = stash.new_generated_tree();
-        run_transcode_job(&video_path, 1, &gen_tree)?;
+        let profile = TranscodeProfile::builtin("av1_400k").expect("builtin");
+        run_transcode_job(&video_path, entry.db.id, &profile, &gen_tree, &mut catalog)?;
 
         let files =
-            gen_tree.query_generated_files(1, crate::file_tree::GeneratedFileType::AltFormat);
+            gen_tree.query_generated_files(entry.db.id, crate::file_tree::GeneratedFileType::AltFormat);
         assert!(files.len() > 0);
 
+        // Alt format info was recorded
+        let entry = testing::entry_for("vidaud_h265_aac.mkv", &stash.new_file_tree(), &mut catalog)?;
+        let group_name = video::video_info::alt_format_group_name(&files[0]);
+        assert!(crate::catalog::generated_notes::read::<video::VideoInfo>(&entry.db, &group_name).is_some());
+
         Ok(())
     }
 
     #[test]
-    fn test_is_transcode_needed_chrome() -> testing::TestResult {
+    fn test_is_transcode_needed() -> testing::TestResult {
         // Use a tempdir stash for all cases
         let file_root = testing::testdata_path("transcode");
         let (_tempdir, stash) = testing::tempdir_vault(&file_root)?;
@@ -335,29 +1000,59 @@ mod tests {
 
         // Images
         let image_entry = testing::fake_entry("an_image.jpg");
-        assert_eq!(false, is_transcode_needed_chrome(&image_entry));
+        assert_eq!(false, is_transcode_needed(&image_entry, &[video::Client::Chrome]));
 
-        // Bog standard H264-in-MP4, which Chrome can play
+        // Bog standard H264-in-MP4, which every browser can play
         write_video_info(&stash, "vidaud_h264_aac.mp4")?;
         assert_eq!(
             false,
-            is_transcode_needed_chrome(&testing::entry_for(
-                "vidaud_h264_aac.mp4",
-                &file_tree,
-                &mut catalog
-            )?)
+            is_transcode_needed(
+                &testing::entry_for("vidaud_h264_aac.mp4", &file_tree, &mut catalog)?,
+                &[video::Client::Chrome, video::Client::Firefox, video::Client::Safari]
+            )
         );
 
         // H265-in-MKV, which Chrome can't play
         write_video_info(&stash, "vidaud_h265_aac.mkv")?;
-        assert_eq!(
-            true,
-            is_transcode_needed_chrome(&testing::entry_for(
-                "vidaud_h265_aac.mkv",
-                &file_tree,
-                &mut catalog
-            )?)
-        );
+        let h265_entry = testing::entry_for("vidaud_h265_aac.mkv", &file_tree, &mut catalog)?;
+        assert_eq!(true, is_transcode_needed(&h265_entry, &[video::Client::Chrome]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transcode_job_spec_profiles() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::write(file_root.path().join("video.mp4"), "")?;
+        let (_tempdir, mut vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        // HEVC, which Chrome can't play
+        let entry = testing::entry_for("video.mp4", &file_tree, &mut catalog)?;
+        let video_info = video::VideoInfo {
+            mime_type: "video/mp4".to_string(),
+            codec: "hevc".to_string(),
+            codec_rfc6381: "hvc1".to_string(),
+            duration_secs: 10.0,
+            width: 320,
+            height: 240,
+            bitrate: 1000,
+            ..Default::default()
+        };
+        crate::catalog::generated_notes::update(&mut catalog, entry.db.id, video::VIDEO_INFO_GROUP_NAME, &video_info);
+        let entry = testing::entry_for("video.mp4", &file_tree, &mut catalog)?;
+
+        assert!(TranscodeJobSpec.create_job(&vault, &entry)?.is_some());
+
+        vault.config.transcode_job_profiles = vec!["h264_800k".to_string()];
+        assert!(TranscodeJobSpec.create_job(&vault, &entry)?.is_some());
+
+        // Unknown profile, or none that Chrome can play
+        vault.config.transcode_job_profiles = vec!["nonexistent".to_string()];
+        assert!(TranscodeJobSpec.create_job(&vault, &entry).is_err());
+        vault.config.transcode_job_profiles = Vec::new();
+        assert!(TranscodeJobSpec.create_job(&vault, &entry).is_err());
 
         Ok(())
     }
@@ -388,6 +1083,25 @@ mod tests {
         assert_eq!(files.len(), 3);
     }
 
+    #[test]
+    fn test_burn_in_subtitles_job_spec_config() -> testing::TestResult {
+        let root = testing::testdata_path("subtitle");
+        let (_tempdir, mut vault) = testing::tempdir_vault(&root)?;
+        let entry = testing::entry_for(
+            "art001m1203451716~small_10s.mp4",
+            &vault.new_file_tree(),
+            &mut vault.open_catalog()?,
+        )?;
+
+        // Off unless a profile is configured
+        assert!(BurnInSubtitlesJobSpec.create_job(&vault, &entry)?.is_none());
+
+        vault.config.burn_in_subtitles_profile = Some("h264_9000k".to_string());
+        assert!(BurnInSubtitlesJobSpec.create_job(&vault, &entry).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_convert_subtitle_job_spec_not_needed() {
         // TODO: implement
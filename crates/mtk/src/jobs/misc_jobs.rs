@@ -3,11 +3,14 @@ use serde::{de::DeserializeOwned, Serialize};
 use super::JobSpec;
 
 use crate::FsEntry;
-use crate::catalog::generated_notes;
+use crate::browse::classify;
+use crate::catalog::{generated_notes, SpecialEntryType};
+use crate::query::similar;
 
 pub struct UpdateGeneratedNotesJobSpec<CheckNeededFn, GenerateFn> {
     job_type: String,
     group_name: String,
+    version: u64,
     check_needed_fn: CheckNeededFn,
     generate_fn: GenerateFn,
 }
@@ -17,10 +20,17 @@ impl <CheckNeededFn, GenerateFn> UpdateGeneratedNotesJobSpec<CheckNeededFn, Gene
         Self {
             job_type: job_type.to_string(),
             group_name: group_name.to_string(),
+            version: 1,
             check_needed_fn,
             generate_fn,
         }
     }
+
+    /// Regenerate notes written with an older version of the group
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
 }
 
 impl <CheckNeededFn, GeneratedT, GenerateFn> JobSpec for UpdateGeneratedNotesJobSpec<CheckNeededFn, GenerateFn> where 
@@ -33,10 +43,11 @@ impl <CheckNeededFn, GeneratedT, GenerateFn> JobSpec for UpdateGeneratedNotesJob
     }
 
     fn create_job(&self, stash: &crate::Vault, entry: &crate::Entry) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
-        if !(self.check_needed_fn)(entry) || !generated_notes::needs_update(entry, &self.group_name) {
+        if !(self.check_needed_fn)(entry) || !generated_notes::needs_update_versioned(entry, &self.group_name, self.version) {
             return Ok(None);
         }
         let group_name = self.group_name.clone();
+        let version = self.version;
         let entry_id = entry.db.id;
         let fs_entry = entry.fs.clone();
         let generate_fn = self.generate_fn;
@@ -44,7 +55,74 @@ impl <CheckNeededFn, GeneratedT, GenerateFn> JobSpec for UpdateGeneratedNotesJob
         Ok(Some(Box::new(move || {
             println!("Updating generated \"{}\" notes for {}", &group_name, fs_entry.repo_path);
             let generated = (generate_fn)(&fs_entry)?;
-            generated_notes::update(&mut catalog, entry_id, &group_name, &generated);
+            generated_notes::update_versioned(&mut catalog, entry_id, &group_name, version, &generated);
+            Ok(())
+        })))
+    }
+}
+
+/// Mark directories as series or galleries, from what's in them. Only the types this
+/// job sets are changed, so other special types are left alone.
+pub struct ClassifyDirJobSpec;
+
+impl JobSpec for ClassifyDirJobSpec {
+    fn job_type(&self) -> &str {
+        "classify_dir"
+    }
+
+    fn create_job(&self, stash: &crate::Vault, entry: &crate::Entry) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        if !entry.fs.file_type.is_dir || entry.fs.is_archive() {
+            return Ok(None);
+        }
+        let is_classified_type = |special_type: &Option<SpecialEntryType>| {
+            matches!(special_type, None | Some(SpecialEntryType::SeriesDir) | Some(SpecialEntryType::GalleryDir))
+        };
+        if !is_classified_type(&entry.db.special_type) {
+            return Ok(None);
+        }
+
+        let children: Vec<FsEntry> = stash.new_file_tree().listdir(&entry.fs.repo_path)?.collect();
+        let special_type = classify::classify_dir(&entry.fs, &children);
+        if special_type == entry.db.special_type {
+            return Ok(None);
+        }
+
+        let entry_id = entry.db.id;
+        let repo_path = entry.fs.repo_path.clone();
+        let mut catalog = stash.open_catalog()?;
+        Ok(Some(Box::new(move || {
+            println!("ClassifyDirJob: marking {} as {:?}", repo_path, special_type);
+            catalog.set_special_type(entry_id, special_type);
+            Ok(())
+        })))
+    }
+}
+
+/// Work out which entries are similar to each file, for the "More Like This" shelf.
+/// Each run compares against the whole repo, so it's done here instead of per page view.
+pub struct SimilarJobSpec;
+
+impl JobSpec for SimilarJobSpec {
+    fn job_type(&self) -> &str {
+        "similar"
+    }
+
+    fn create_job(&self, stash: &crate::Vault, entry: &crate::Entry) -> Result<Option<Box<crate::jobs::JobFn>>, Box<dyn std::error::Error>> {
+        if entry.fs.file_type.is_dir || entry.fs.is_metadata_file || !similar::needs_update(entry) {
+            return Ok(None);
+        }
+
+        let entry = entry.clone();
+        let file_tree = stash.new_file_tree();
+        let mut catalog = stash.open_catalog()?;
+        let history_db = stash.open_history_db();
+        Ok(Some(Box::new(move || {
+            println!("SimilarJob: finding entries similar to {}", entry.fs.repo_path);
+            let similar = similar::similar_entries(&entry, &file_tree, &catalog, &history_db, similar::NUM_STORED_SIMILAR);
+            let record = similar::SimilarEntries {
+                entry_ids: similar.iter().map(|(similar_entry, _)| similar_entry.db.id).collect(),
+            };
+            generated_notes::update(&mut catalog, entry.db.id, similar::SIMILAR_GROUP_NAME, &record);
             Ok(())
         })))
     }
@@ -106,4 +184,88 @@ mod tests {
         };
         assert_eq!(false, job_spec.create_job(&stash, &updated_entry).unwrap().is_some());
     }
+
+    #[test]
+    fn test_update_generated_notes_job_spec_version() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::write(file_root.path().join("file.txt"), "")?;
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        let generate_fn = |_: &FsEntry| Ok(json!({"key1": "value1"}));
+        let job_spec_v1 = UpdateGeneratedNotesJobSpec::new("job", "group1", |_: &Entry| true, generate_fn);
+        let job_spec_v2 = UpdateGeneratedNotesJobSpec::new("job", "group1", |_: &Entry| true, generate_fn).with_version(2);
+
+        let entry = testing::entry_for("file.txt", &file_tree, &mut catalog)?;
+        job_spec_v1.create_job(&vault, &entry)?.expect("job needed")()?;
+
+        // Up to date for version 1, but not version 2
+        let entry = testing::entry_for("file.txt", &file_tree, &mut catalog)?;
+        assert!(job_spec_v1.create_job(&vault, &entry)?.is_none());
+        job_spec_v2.create_job(&vault, &entry)?.expect("job needed")()?;
+
+        let entry = testing::entry_for("file.txt", &file_tree, &mut catalog)?;
+        assert_eq!(generated_notes::version(&entry.db, "group1"), 2);
+        assert!(job_spec_v1.create_job(&vault, &entry)?.is_none());
+        assert!(job_spec_v2.create_job(&vault, &entry)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_classify_dir_job_spec() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::create_dir(file_root.path().join("Show"))?;
+        for name in ["Show - 01.mp4", "Show - 02.mp4"] {
+            std::fs::write(file_root.path().join("Show").join(name), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let spec = ClassifyDirJobSpec;
+
+        let show = testing::entry_for("Show", &file_tree, &mut catalog)?;
+        let job = spec.create_job(&vault, &show)?.expect("job needed");
+        job()?;
+        let show = testing::entry_for("Show", &file_tree, &mut catalog)?;
+        assert_eq!(show.db.special_type, Some(SpecialEntryType::SeriesDir));
+        assert!(spec.create_job(&vault, &show)?.is_none());
+
+        // Files and other special types aren't touched
+        let episode = testing::entry_for("Show/Show - 01.mp4", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &episode)?.is_none());
+        catalog.set_special_type(show.db.id, Some(SpecialEntryType::PreviewFile));
+        let show = testing::entry_for("Show", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &show)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_similar_job_spec() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::create_dir(file_root.path().join("Show"))?;
+        for name in ["1.mp4", "2.mp4"] {
+            std::fs::write(file_root.path().join("Show").join(name), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let spec = SimilarJobSpec;
+
+        let dir = testing::entry_for("Show", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &dir)?.is_none());
+
+        let sibling = testing::entry_for("Show/2.mp4", &file_tree, &mut catalog)?;
+        let episode = testing::entry_for("Show/1.mp4", &file_tree, &mut catalog)?;
+        spec.create_job(&vault, &episode)?.expect("job needed")()?;
+
+        let episode = testing::entry_for("Show/1.mp4", &file_tree, &mut catalog)?;
+        assert!(spec.create_job(&vault, &episode)?.is_none());
+        let similar = similar::read_similar_entries(&episode.db, &file_tree, &catalog, 10);
+        assert_eq!(similar.iter().map(|e| e.db.id).collect::<Vec<_>>(), vec![sibling.db.id]);
+
+        Ok(())
+    }
 }
@@ -26,7 +26,20 @@ impl JobRegistry {
 pub fn default_registry() -> JobRegistry {
     let mut registry = JobRegistry::new();
     registry.register(Box::new(super::media_jobs::PreviewJobSpec{}));
+    registry.register(Box::new(super::media_jobs::DirPreviewJobSpec{}));
     registry.register(Box::new(super::media_jobs::generate_video_info_job_spec()));
+    registry.register(Box::new(super::media_jobs::generate_audio_info_job_spec()));
+    registry.register(Box::new(super::media_jobs::generate_image_info_job_spec()));
+    registry.register(Box::new(super::media_jobs::generate_document_info_job_spec()));
+    registry.register(Box::new(super::media_jobs::MinividJobSpec{}));
+    registry.register(Box::new(super::media_jobs::GifTranscodeJobSpec{}));
+    registry.register(Box::new(super::media_jobs::ThumbnailsJobSpec{}));
+    registry.register(Box::new(super::media_jobs::PerceptualHashJobSpec{}));
+    registry.register(Box::new(super::media_jobs::TranscodeJobSpec{}));
+    registry.register(Box::new(super::media_jobs::ConvertSubtitlesJobSpec{}));
+    registry.register(Box::new(super::media_jobs::BurnInSubtitlesJobSpec{}));
+    registry.register(Box::new(super::misc_jobs::ClassifyDirJobSpec{}));
+    registry.register(Box::new(super::misc_jobs::SimilarJobSpec{}));
     registry
 }
 
@@ -38,6 +51,19 @@ mod tests {
     fn test_default_registry() {
         let registry = default_registry();
         assert!(registry.get("preview").is_some());
+        assert!(registry.get("dir_preview").is_some());
         assert!(registry.get("video_info").is_some());
+        assert!(registry.get("audio_info").is_some());
+        assert!(registry.get("image_info").is_some());
+        assert!(registry.get("document_info").is_some());
+        assert!(registry.get("minivid").is_some());
+        assert!(registry.get("gif_transcode").is_some());
+        assert!(registry.get("thumbnails").is_some());
+        assert!(registry.get("phash").is_some());
+        assert!(registry.get("transcode").is_some());
+        assert!(registry.get("subtitles").is_some());
+        assert!(registry.get("burn_subtitles").is_some());
+        assert!(registry.get("classify_dir").is_some());
+        assert!(registry.get("similar").is_some());
     }
 }
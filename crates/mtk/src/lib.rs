@@ -26,9 +26,18 @@ pub struct Entry {
 impl Entry {
     pub fn display_title(&self) -> String {
         let catalog_title = self.db.title();
-        catalog_title.unwrap_or_else(|| {
-            self.fs.file_name.clone()
-        })
+        catalog_title
+            // Music and podcasts usually have better titles in their tags
+            .or_else(|| media::audio::read_audio_info(&self.db).and_then(|info| info.display_title()))
+            // EPUB titles are reliable, unlike PDF ones (often e.g. "Microsoft Word - draft.docx")
+            .or_else(|| {
+                media::document::read_document_info(&self.db)
+                    .filter(|info| info.format == "epub")
+                    .and_then(|info| info.title)
+            })
+            .unwrap_or_else(|| {
+                self.fs.file_name.clone()
+            })
     }
 }
 
@@ -0,0 +1,226 @@
+//! Audio files (music, podcasts): stream info and tags from ffprobe, and embedded
+//! cover art for previews.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::generated_notes;
+
+/// Generated notes group (on the main entry)
+pub const AUDIO_INFO_GROUP_NAME: &str = "audio_info";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioInfo {
+    pub mime_type: String,
+    pub codec: String,
+    pub duration_secs: f64,
+    pub bitrate: u64,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+
+    // From ID3 or Vorbis comment tags
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+
+    /// Whether the file has an embedded cover image, which is used as its preview
+    pub has_cover_art: bool,
+}
+
+impl AudioInfo {
+    /// "Artist - Title" if the tags have them, for display instead of the filename
+    pub fn display_title(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title.clone()),
+            _ => None,
+        }
+    }
+}
+
+fn mime_type_for_extension(extension: &str) -> &'static str {
+    match extension.to_lowercase().as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "flac" => "audio/flac",
+        "opus" => "audio/ogg",
+        "ogg" => "audio/ogg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: FfprobeFormat,
+}
+
+/// Build an AudioInfo from ffprobe's JSON output.
+fn parse_ffprobe_output(json: &str, extension: &str) -> Result<AudioInfo, Box<dyn std::error::Error>> {
+    let ffprobe_output: FfprobeOutput = serde_json::from_str(json)?;
+
+    let audio_stream = ffprobe_output
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("audio"))
+        .ok_or("No audio stream")?;
+    let has_cover_art = ffprobe_output
+        .streams
+        .iter()
+        .any(|stream| stream.codec_type.as_deref() == Some("video") && stream.disposition.get("attached_pic") == Some(&1));
+
+    // ID3 tags end up on the format, Vorbis comments (ogg, opus) on the stream, and
+    // the case of the keys varies
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for (key, value) in audio_stream.tags.iter().chain(ffprobe_output.format.tags.iter()) {
+        tags.entry(key.to_lowercase()).or_insert_with(|| value.clone());
+    }
+    let tag = |key: &str| tags.get(key).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+
+    Ok(AudioInfo {
+        mime_type: mime_type_for_extension(extension).to_string(),
+        codec: audio_stream.codec_name.clone().unwrap_or_else(|| "unknown".to_string()),
+        duration_secs: ffprobe_output.format.duration.as_deref().and_then(|d| d.parse().ok()).unwrap_or(0.0),
+        bitrate: ffprobe_output.format.bit_rate.as_deref().and_then(|b| b.parse().ok()).unwrap_or(0),
+        sample_rate: audio_stream.sample_rate.as_deref().and_then(|r| r.parse().ok()),
+        channels: audio_stream.channels,
+        title: tag("title"),
+        artist: tag("artist").or_else(|| tag("album_artist")),
+        album: tag("album"),
+        track: tag("track"),
+        date: tag("date"),
+        genre: tag("genre"),
+        has_cover_art,
+    })
+}
+
+/// Extract an AudioInfo from an audio file using ffprobe.
+pub fn get_audio_info(audio_path: &Path) -> Result<AudioInfo, Box<dyn std::error::Error>> {
+    let output = std::process::Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_streams")
+        .arg("-show_format")
+        .arg("-of")
+        .arg("json")
+        .arg(audio_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed for {:?}: {}",
+            audio_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+
+    let extension = audio_path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    parse_ffprobe_output(&String::from_utf8_lossy(&output.stdout), extension)
+}
+
+/// The cached AudioInfo from the "audio_info" job, if it has run.
+pub fn read_audio_info(db_entry: &crate::catalog::DbEntry) -> Option<AudioInfo> {
+    generated_notes::read::<AudioInfo>(db_entry, AUDIO_INFO_GROUP_NAME)
+}
+
+/// Save the embedded cover art as a preview image.
+pub fn make_preview_image(audio_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = std::process::Command::new("ffmpeg");
+    cmd.arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(audio_path)
+        .arg("-an")
+        .arg("-map")
+        .arg("0:v:0");
+    crate::preview::ffmpeg_preview_args(&mut cmd);
+    let output = cmd.arg(out_path).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_preview_image failed for {:?}: {}",
+            audio_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ffprobe_output_id3() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "audio", "codec_name": "mp3", "sample_rate": "44100", "channels": 2},
+                {"codec_type": "video", "codec_name": "mjpeg", "disposition": {"attached_pic": 1}}
+            ],
+            "format": {
+                "duration": "215.040000", "bit_rate": "320000",
+                "tags": {"title": "Song", "artist": "Band", "album": "Album", "track": "3/12", "genre": ""}
+            }
+        }"#;
+        let info = parse_ffprobe_output(json, "MP3").expect("parse_ffprobe_output");
+        assert_eq!(info, AudioInfo {
+            mime_type: "audio/mpeg".to_string(),
+            codec: "mp3".to_string(),
+            duration_secs: 215.04,
+            bitrate: 320000,
+            sample_rate: Some(44100),
+            channels: Some(2),
+            title: Some("Song".to_string()),
+            artist: Some("Band".to_string()),
+            album: Some("Album".to_string()),
+            track: Some("3/12".to_string()),
+            date: None,
+            genre: None,
+            has_cover_art: true,
+        });
+        assert_eq!(info.display_title().as_deref(), Some("Band - Song"));
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output_vorbis() {
+        // Vorbis comments are on the stream, in upper case
+        let json = r#"{
+            "streams": [{"codec_type": "audio", "codec_name": "opus", "channels": 1, "tags": {"TITLE": "Episode 12", "ALBUM_ARTIST": "Podcast"}}],
+            "format": {"duration": "3600.5"}
+        }"#;
+        let info = parse_ffprobe_output(json, "opus").expect("parse_ffprobe_output");
+        assert_eq!(info.mime_type, "audio/ogg");
+        assert_eq!(info.title.as_deref(), Some("Episode 12"));
+        assert_eq!(info.artist.as_deref(), Some("Podcast"));
+        assert_eq!(info.bitrate, 0);
+        assert!(!info.has_cover_art);
+
+        let json = r#"{"streams": [{"codec_type": "video", "codec_name": "h264"}], "format": {}}"#;
+        assert!(parse_ffprobe_output(json, "m4a").is_err());
+    }
+}
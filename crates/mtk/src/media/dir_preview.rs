@@ -0,0 +1,225 @@
+//! Previews for directories, made by tiling the previews of a few of their children.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::{generated_notes, Catalog, DbEntry};
+use crate::file_tree::GeneratedTree;
+use crate::{preview, FileTree, RepoPathBuf};
+
+pub const DIR_PREVIEW_GROUP_NAME: &str = "dir_preview";
+
+const DIR_PREVIEW_MAX_CHILDREN: usize = 4;
+/// Each child's preview is cropped to a square of this size
+const TILE_DIMENSION: i32 = 240;
+
+/// Which children a directory's preview was made from.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DirPreview {
+    pub children: Vec<i64>,
+}
+
+/// A child whose preview goes into the directory's preview.
+#[derive(Debug)]
+pub struct PreviewChild {
+    pub entry_id: i64,
+    pub preview_path: PathBuf,
+}
+
+/// Pick the children to show in a directory's preview: highest rated first, then by
+/// name. Only children that already have previews (including subdirectories) count.
+pub fn pick_children(
+    dir_path: &RepoPathBuf,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    gen_tree: &GeneratedTree,
+) -> std::io::Result<Vec<PreviewChild>> {
+    let mut candidates = Vec::new();
+    for child in file_tree.listdir(dir_path)? {
+        if child.is_metadata_file {
+            continue;
+        }
+
+        let db_entry = catalog.get_or_create(&child);
+        if crate::browse::should_hide_entry(&db_entry) {
+            continue;
+        }
+
+        let preview_path = preview::get_preview(db_entry.id, gen_tree);
+        if preview_path.exists() {
+            candidates.push((db_entry.rating(), child.repo_path, PreviewChild { entry_id: db_entry.id, preview_path }));
+        }
+    }
+
+    // Unrated children sort after rated ones
+    candidates.sort_by(|(a_rating, a_path, _), (b_rating, b_path, _)| {
+        b_rating.cmp(a_rating).then_with(|| a_path.cmp(b_path))
+    });
+    Ok(candidates
+        .into_iter()
+        .take(DIR_PREVIEW_MAX_CHILDREN)
+        .map(|(_, _, child)| child)
+        .collect())
+}
+
+fn mod_time(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Whether the directory's preview needs to be (re)made: it doesn't exist yet, a
+/// different set of children was picked, or one of the children's previews changed.
+pub fn is_stale(db_entry: &DbEntry, children: &[PreviewChild], preview_path: &Path) -> bool {
+    let Some(preview_mod_time) = mod_time(preview_path) else {
+        return true;
+    };
+
+    let child_ids: Vec<i64> = children.iter().map(|child| child.entry_id).collect();
+    match generated_notes::read::<DirPreview>(db_entry, DIR_PREVIEW_GROUP_NAME) {
+        Some(current) if current.children == child_ids => {}
+        _ => return true,
+    }
+
+    children
+        .iter()
+        .any(|child| mod_time(&child.preview_path).is_some_and(|t| t > preview_mod_time))
+}
+
+/// ffmpeg filtergraph that crops each input to a square tile and lays them out:
+/// side by side for up to 3 tiles, or a 2x2 grid for 4.
+fn collage_filter(num_tiles: usize) -> String {
+    let mut filter = String::new();
+    for i in 0..num_tiles {
+        filter += &format!(
+            "[{i}:v]scale={dim}:{dim}:force_original_aspect_ratio=increase,crop={dim}:{dim},setsar=1[t{i}];",
+            i = i,
+            dim = TILE_DIMENSION,
+        );
+    }
+    for i in 0..num_tiles {
+        filter += &format!("[t{}]", i);
+    }
+
+    filter += &match num_tiles {
+        1 => "null[out]".to_string(),
+        4 => "xstack=inputs=4:layout=0_0|w0_0|0_h0|w0_h0[out]".to_string(),
+        n => format!("hstack=inputs={}[out]", n),
+    };
+    filter
+}
+
+/// Tile the children's previews into a preview for the directory.
+pub fn make_dir_preview(child_previews: &[PathBuf], out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if child_previews.is_empty() || child_previews.len() > DIR_PREVIEW_MAX_CHILDREN {
+        return Err(format!("can't make a dir preview from {} children", child_previews.len()).into());
+    }
+
+    let mut cmd = std::process::Command::new("ffmpeg");
+    cmd.arg("-y").arg("-v").arg("error");
+    for child_preview in child_previews {
+        cmd.arg("-i").arg(child_preview);
+    }
+    let output = cmd
+        .arg("-filter_complex")
+        .arg(collage_filter(child_previews.len()))
+        .arg("-map")
+        .arg("[out]")
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-compression_level")
+        .arg("5")
+        .arg("-quality")
+        .arg("50")
+        .arg(out_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_dir_preview failed for {:?}: {}",
+            out_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::catalog::sqlite_catalog::WhichNotes;
+    use crate::testing;
+
+    #[test]
+    fn test_collage_filter() {
+        assert!(collage_filter(1).ends_with("[t0]null[out]"));
+        assert!(collage_filter(3).ends_with("[t0][t1][t2]hstack=inputs=3[out]"));
+        assert!(collage_filter(4).contains("[t3]xstack=inputs=4:"));
+        assert_eq!(collage_filter(4).matches("crop=240:240").count(), 4);
+    }
+
+    #[test]
+    fn test_pick_children_and_is_stale() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for path in ["dir/a.jpg", "dir/b.jpg", "dir/c.jpg", "dir/d.jpg", "dir/e.jpg", "dir/no_preview.jpg", "dir/sub/f.jpg"] {
+            let fs_path = file_root.path().join(path);
+            std::fs::create_dir_all(fs_path.parent().unwrap())?;
+            std::fs::write(&fs_path, "")?;
+        }
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let gen_tree = vault.new_generated_tree();
+
+        let mut ids = std::collections::HashMap::new();
+        for path in ["dir/a.jpg", "dir/b.jpg", "dir/c.jpg", "dir/d.jpg", "dir/e.jpg", "dir/sub", "dir/no_preview.jpg"] {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            if path != "dir/no_preview.jpg" {
+                std::fs::write(preview::get_preview(entry.db.id, &gen_tree), "")?;
+            }
+            ids.insert(path, entry.db.id);
+        }
+        catalog.set_notes_json(ids["dir/e.jpg"], WhichNotes::User, r#"{"rating": 5}"#);
+        catalog.set_notes_json(ids["dir/no_preview.jpg"], WhichNotes::User, r#"{"rating": 5}"#);
+
+        let children = pick_children(&RepoPathBuf::from("dir"), &file_tree, &mut catalog, &gen_tree)?;
+        let child_ids: Vec<i64> = children.iter().map(|c| c.entry_id).collect();
+        // Highest rated first, then by name; subdirectories with previews count too
+        assert_eq!(child_ids, vec![ids["dir/e.jpg"], ids["dir/a.jpg"], ids["dir/b.jpg"], ids["dir/c.jpg"]]);
+
+        let dir = testing::entry_for("dir", &file_tree, &mut catalog)?;
+        let dir_preview_path = preview::get_preview(dir.db.id, &gen_tree);
+        assert!(is_stale(&dir.db, &children, &dir_preview_path));
+
+        std::fs::write(&dir_preview_path, "")?;
+        generated_notes::update(&mut catalog, dir.db.id, DIR_PREVIEW_GROUP_NAME, &DirPreview { children: child_ids.clone() });
+        let dir = testing::entry_for("dir", &file_tree, &mut catalog)?;
+        assert!(!is_stale(&dir.db, &children, &dir_preview_path));
+
+        // Different children picked
+        assert!(is_stale(&dir.db, &children[..3], &dir_preview_path));
+
+        // A child's preview was remade
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::options().write(true).open(&children[1].preview_path)?.set_modified(later)?;
+        assert!(is_stale(&dir.db, &children, &dir_preview_path));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_dir_preview() {
+        let root = testing::testdata_path("preview");
+        let tempdir = tempfile::tempdir().expect("tempdir");
+        let out_path = tempdir.path().join("dir.webp");
+
+        let children: Vec<PathBuf> = ["square.png", "wide.png", "xtall.png", "xwide.png"]
+            .iter()
+            .map(|name| root.join(name))
+            .collect();
+        make_dir_preview(&children, &out_path).expect("make_dir_preview");
+        assert!(out_path.exists());
+
+        assert!(make_dir_preview(&[], &out_path).is_err());
+    }
+}
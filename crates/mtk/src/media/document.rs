@@ -0,0 +1,384 @@
+//! Paged documents (PDF, EPUB): page counts, titles and authors, and previews of
+//! the first page or cover.
+
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::{generated_notes, DbEntry};
+
+/// Generated notes group (on the main entry)
+pub const DOCUMENT_GROUP_NAME: &str = "document";
+
+/// Size of the rendered first page of PDFs, before it's scaled down like other previews
+const PDF_RENDER_SIZE: u32 = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DocumentFormat {
+    Pdf,
+    Epub,
+}
+
+impl DocumentFormat {
+    /// Only formats with pages (so not txt or md)
+    pub fn from_path(path: &Path) -> Option<DocumentFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "pdf" => Some(DocumentFormat::Pdf),
+            "epub" => Some(DocumentFormat::Epub),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentInfo {
+    /// "pdf" or "epub"
+    pub format: String,
+    /// Not known for EPUBs, which reflow
+    pub page_count: Option<u32>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+/// The poppler commands used for PDFs, so that tests (and machines without poppler)
+/// can substitute their own.
+pub trait PdfTools {
+    /// Output of `pdfinfo`, as "Key: value" lines
+    fn pdfinfo(&self, pdf_path: &Path) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Render the first page as a PNG
+    fn render_first_page(&self, pdf_path: &Path, png_out_path: &Path) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Runs pdfinfo and pdftoppm from poppler-utils
+pub struct Poppler;
+
+impl PdfTools for Poppler {
+    fn pdfinfo(&self, pdf_path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let output = std::process::Command::new("pdfinfo").arg(pdf_path).output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "pdfinfo failed for {:?}: {}",
+                pdf_path,
+                String::from_utf8_lossy(&output.stderr)
+            ).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn render_first_page(&self, pdf_path: &Path, png_out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        // pdftoppm takes an output prefix, and adds the extension itself
+        let out_prefix = png_out_path.with_extension("");
+        let output = std::process::Command::new("pdftoppm")
+            .arg("-png")
+            .arg("-singlefile")
+            .arg("-f")
+            .arg("1")
+            .arg("-l")
+            .arg("1")
+            .arg("-scale-to")
+            .arg(PDF_RENDER_SIZE.to_string())
+            .arg(pdf_path)
+            .arg(&out_prefix)
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "pdftoppm failed for {:?}: {}",
+                pdf_path,
+                String::from_utf8_lossy(&output.stderr)
+            ).into());
+        }
+        Ok(())
+    }
+}
+
+fn parse_pdfinfo(pdfinfo: &str) -> DocumentInfo {
+    let field = |key: &str| {
+        pdfinfo.lines().find_map(|line| {
+            let (line_key, value) = line.split_once(':')?;
+            let value = value.trim();
+            (line_key.trim() == key && !value.is_empty()).then(|| value.to_string())
+        })
+    };
+    DocumentInfo {
+        format: "pdf".to_string(),
+        page_count: field("Pages").and_then(|pages| pages.parse().ok()),
+        title: field("Title"),
+        author: field("Author"),
+    }
+}
+
+////////////////////////////////
+// EPUB
+////////////////////////////////
+
+fn decode_xml_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Attributes of each `<tag ...>` in the document
+fn xml_tags(xml: &str, tag: &str) -> Vec<Vec<(String, String)>> {
+    let tag_re = regex::Regex::new(&format!(r"<{}\s([^>]*)>", regex::escape(tag))).unwrap();
+    let attr_re = regex::Regex::new(r#"([\w:-]+)\s*=\s*"([^"]*)""#).unwrap();
+    tag_re
+        .captures_iter(xml)
+        .map(|tag_captures| {
+            attr_re
+                .captures_iter(&tag_captures[1])
+                .map(|attr| (attr[1].to_string(), decode_xml_entities(&attr[2])))
+                .collect()
+        })
+        .collect()
+}
+
+fn xml_element_text(xml: &str, tag: &str) -> Option<String> {
+    let re = regex::Regex::new(&format!(r"<{tag}(?:\s[^>]*)?>([^<]*)</{tag}>", tag = regex::escape(tag))).unwrap();
+    let text = decode_xml_entities(re.captures(xml)?[1].trim());
+    (!text.is_empty()).then_some(text)
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+fn read_zip_text<R: Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut text = String::new();
+    archive.by_name(name)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Resolve an href in the package document, which is relative to the package document
+fn resolve_href(opf_path: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = opf_path.split('/').collect();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            ".." => {
+                parts.pop();
+            }
+            "." => {}
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// The package (.opf) document, which has the metadata and manifest
+fn read_epub_package<R: Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let container = read_zip_text(archive, "META-INF/container.xml")?;
+    let opf_path = xml_tags(&container, "rootfile")
+        .iter()
+        .find_map(|attrs| attr(attrs, "full-path").map(|path| path.to_string()))
+        .ok_or("No rootfile in container.xml")?;
+    let opf = read_zip_text(archive, &opf_path)?;
+    Ok((opf_path, opf))
+}
+
+fn read_epub_info<R: Read + std::io::Seek>(reader: R) -> Result<DocumentInfo, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let (_, opf) = read_epub_package(&mut archive)?;
+    Ok(DocumentInfo {
+        format: "epub".to_string(),
+        page_count: None,
+        title: xml_element_text(&opf, "dc:title"),
+        author: xml_element_text(&opf, "dc:creator"),
+    })
+}
+
+/// The cover image's path within the EPUB. EPUB 3 marks it in the manifest, while
+/// EPUB 2 points to the manifest item from a `<meta name="cover">`.
+fn find_epub_cover(opf_path: &str, opf: &str) -> Option<String> {
+    let items = xml_tags(opf, "item");
+    let cover_id = xml_tags(opf, "meta")
+        .iter()
+        .find(|attrs| attr(attrs, "name") == Some("cover"))
+        .and_then(|attrs| attr(attrs, "content").map(|id| id.to_string()));
+
+    let cover_item = items
+        .iter()
+        .find(|attrs| attr(attrs, "properties").is_some_and(|props| props.split_whitespace().any(|p| p == "cover-image")))
+        .or_else(|| items.iter().find(|attrs| cover_id.is_some() && attr(attrs, "id") == cover_id.as_deref()))?;
+    if !attr(cover_item, "media-type").is_some_and(|media_type| media_type.starts_with("image/")) {
+        return None;
+    }
+    Some(resolve_href(opf_path, attr(cover_item, "href")?))
+}
+
+struct EpubCover {
+    data: Vec<u8>,
+    /// Path within the EPUB
+    path: String,
+}
+
+fn read_epub_cover<R: Read + std::io::Seek>(reader: R) -> Result<Option<EpubCover>, Box<dyn std::error::Error>> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let (opf_path, opf) = read_epub_package(&mut archive)?;
+    let Some(cover_path) = find_epub_cover(&opf_path, &opf) else {
+        return Ok(None);
+    };
+    let mut data = Vec::new();
+    archive.by_name(&cover_path)?.read_to_end(&mut data)?;
+    Ok(Some(EpubCover { data, path: cover_path }))
+}
+
+////////////////////////////////
+
+pub fn get_document_info_with(path: &Path, pdf_tools: &dyn PdfTools) -> Result<DocumentInfo, Box<dyn std::error::Error>> {
+    match DocumentFormat::from_path(path) {
+        Some(DocumentFormat::Pdf) => Ok(parse_pdfinfo(&pdf_tools.pdfinfo(path)?)),
+        Some(DocumentFormat::Epub) => read_epub_info(std::fs::File::open(path)?),
+        None => Err(format!("Not a paged document: {:?}", path).into()),
+    }
+}
+
+/// Extract a DocumentInfo from a PDF (using pdfinfo) or EPUB.
+pub fn get_document_info(path: &Path) -> Result<DocumentInfo, Box<dyn std::error::Error>> {
+    get_document_info_with(path, &Poppler)
+}
+
+/// The cached DocumentInfo from the "document_info" job, if it has run.
+pub fn read_document_info(db_entry: &DbEntry) -> Option<DocumentInfo> {
+    generated_notes::read::<DocumentInfo>(db_entry, DOCUMENT_GROUP_NAME)
+}
+
+pub fn make_preview_image_with(
+    path: &Path,
+    out_path: &Path,
+    pdf_tools: &dyn PdfTools,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tempdir = tempfile::tempdir()?;
+    let page_path = match DocumentFormat::from_path(path) {
+        Some(DocumentFormat::Pdf) => {
+            let page_path = tempdir.path().join("page.png");
+            pdf_tools.render_first_page(path, &page_path)?;
+            page_path
+        }
+        Some(DocumentFormat::Epub) => {
+            let cover = read_epub_cover(std::fs::File::open(path)?)?.ok_or("EPUB has no cover")?;
+            let extension = Path::new(&cover.path).extension().and_then(|ext| ext.to_str()).unwrap_or("jpg");
+            let page_path = tempdir.path().join(format!("cover.{}", extension));
+            std::fs::write(&page_path, cover.data)?;
+            page_path
+        }
+        None => return Err(format!("Not a paged document: {:?}", path).into()),
+    };
+    super::image::try_make_preview_image(&page_path, out_path)
+}
+
+/// Make a preview from the first page of a PDF, or the cover of an EPUB.
+pub fn make_preview_image(path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    make_preview_image_with(path, out_path, &Poppler)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    use crate::testing;
+
+    struct FakePdfTools;
+
+    impl PdfTools for FakePdfTools {
+        fn pdfinfo(&self, _pdf_path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+            Ok("Title:          Annual Report\nAuthor:         \nPages:          42\nPage size:      612 x 792 pts (letter)\n".to_string())
+        }
+
+        fn render_first_page(&self, _pdf_path: &Path, _png_out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+            Err("not rendering in tests".into())
+        }
+    }
+
+    /// A minimal EPUB with a cover image, with the OPF in a subdirectory
+    fn make_epub(epub3_cover: bool) -> Vec<u8> {
+        let cover_item = if epub3_cover {
+            r#"<item id="img" href="../images/cover.jpg" media-type="image/jpeg" properties="cover-image"/>"#
+        } else {
+            r#"<item href="../images/cover.jpg" id="img" media-type="image/jpeg"/>"#
+        };
+        let opf = format!(
+            r#"<?xml version="1.0"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+              <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                <dc:title>Pride &amp; Prejudice</dc:title>
+                <dc:creator id="author">Jane Austen</dc:creator>
+                <meta name="cover" content="img"/>
+              </metadata>
+              <manifest>
+                <item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/>
+                {}
+              </manifest>
+            </package>"#,
+            cover_item
+        );
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in [
+            ("mimetype", b"application/epub+zip".as_slice()),
+            (
+                "META-INF/container.xml",
+                br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#,
+            ),
+            ("OEBPS/content.opf", opf.as_bytes()),
+            ("images/cover.jpg", b"\xff\xd8\xff\xe0fake"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_get_document_info_pdf() {
+        let info = get_document_info_with(Path::new("report.PDF"), &FakePdfTools).expect("get_document_info");
+        assert_eq!(info, DocumentInfo {
+            format: "pdf".to_string(),
+            page_count: Some(42),
+            title: Some("Annual Report".to_string()),
+            author: None,
+        });
+
+        assert!(get_document_info_with(Path::new("notes.md"), &FakePdfTools).is_err());
+        assert!(make_preview_image_with(Path::new("report.pdf"), Path::new("out.webp"), &FakePdfTools).is_err());
+    }
+
+    #[test]
+    fn test_read_epub() {
+        for epub3_cover in [true, false] {
+            let epub = make_epub(epub3_cover);
+            let info = read_epub_info(std::io::Cursor::new(&epub)).expect("read_epub_info");
+            assert_eq!(info.title.as_deref(), Some("Pride & Prejudice"));
+            assert_eq!(info.author.as_deref(), Some("Jane Austen"));
+            assert_eq!(info.page_count, None);
+
+            let cover = read_epub_cover(std::io::Cursor::new(&epub)).expect("read_epub_cover").expect("cover");
+            assert_eq!(cover.path, "images/cover.jpg");
+            assert!(cover.data.starts_with(b"\xff\xd8"));
+        }
+
+        assert!(read_epub_info(std::io::Cursor::new(b"not a zip")).is_err());
+    }
+
+    #[test]
+    fn test_make_preview_image_bad_cover() -> testing::TestResult {
+        // The cover isn't a real JPEG, so making the preview fails without panicking
+        let tempdir = tempfile::tempdir()?;
+        let epub_path = tempdir.path().join("book.epub");
+        std::fs::write(&epub_path, make_epub(true))?;
+        let out_path = tempdir.path().join("preview.webp");
+        assert!(make_preview_image_with(&epub_path, &out_path, &FakePdfTools).is_err());
+        Ok(())
+    }
+}
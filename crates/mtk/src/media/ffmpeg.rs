@@ -1,4 +1,5 @@
 use std::io::BufRead;
+use std::path::Path;
 
 /// Convert ffprobe's "codec_name" to "fancy" format
 /// Test with:
@@ -34,6 +35,22 @@ pub fn check_command_output(output: std::process::Output, msg: &str) {
     }
 }
 
+/// A temporary file next to `out_path`, with the same extension so that ffmpeg picks the
+/// same format. Write output there and then `persist` it to `out_path`, so that an
+/// interrupted (or concurrent) run never leaves a partial file at `out_path`.
+pub fn temp_output_path(out_path: &Path) -> std::io::Result<tempfile::TempPath> {
+    let dir = out_path.parent().unwrap_or(Path::new("."));
+    let suffix = out_path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    Ok(tempfile::Builder::new()
+        .prefix(".tmp")
+        .suffix(&suffix)
+        .tempfile_in(dir)?
+        .into_temp_path())
+}
+
 struct ProgressParser {
     time_secs: f32,
     done: bool,
@@ -78,11 +95,11 @@ impl ProgressParser {
     }
 }
 
-/// Run ffmpeg command and get progress updates
+/// Run ffmpeg command and get progress updates, returning its exit status
 pub fn ffmpeg_progress_updates(
     cmd: &mut std::process::Command,
     mut progress_cb: impl FnMut(f32),
-) {
+) -> std::process::ExitStatus {
     let mut child = cmd.arg("-progress").arg("pipe:1")
         .stdout(std::process::Stdio::piped())
         .spawn()
@@ -98,4 +115,33 @@ pub fn ffmpeg_progress_updates(
             progress_cb(progress_parser.get());
         }
     }
+    child.wait().expect("wait for ffmpeg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_output_path() -> std::io::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let out_path = tempdir.path().join("123__MV.minivid.webm");
+
+        let first = temp_output_path(&out_path)?;
+        let second = temp_output_path(&out_path)?;
+        assert_ne!(first.to_path_buf(), second.to_path_buf());
+        assert_eq!(first.parent(), Some(tempdir.path()));
+        assert_eq!(first.extension().unwrap(), "webm");
+
+        std::fs::write(&first, "done")?;
+        first.persist(&out_path)?;
+        assert_eq!(std::fs::read_to_string(&out_path)?, "done");
+
+        // Dropped without persisting: nothing is left behind
+        let second_path = second.to_path_buf();
+        drop(second);
+        assert!(!second_path.exists());
+
+        Ok(())
+    }
 }
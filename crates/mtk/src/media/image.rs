@@ -2,17 +2,59 @@ use std::path::Path;
 
 use crate::preview;
 
-use super::ffmpeg;
+pub mod animated;
+pub mod exif;
+pub mod image_info;
+pub use image_info::{IMAGE_INFO_GROUP_NAME, IMAGE_INFO_VERSION};
+pub use image_info::ImageInfo;
+pub use image_info::get_image_info;
+
+/// ffmpeg filter that turns an image with the given EXIF orientation upright
+fn orientation_filter(orientation: u16) -> Option<&'static str> {
+    match orientation {
+        2 => Some("hflip"),
+        3 => Some("hflip,vflip"),
+        4 => Some("vflip"),
+        5 => Some("transpose=cclock_flip"),
+        6 => Some("transpose=clock"),
+        7 => Some("transpose=clock_flip"),
+        8 => Some("transpose=cclock"),
+        // 1 is already upright
+        _ => None,
+    }
+}
+
+/// The image's EXIF orientation, if it has one
+fn read_orientation(image_path: &Path) -> Option<u16> {
+    let data = std::fs::read(image_path).ok()?;
+    exif::find_exif_block(&data)
+        .and_then(exif::parse_exif)
+        .and_then(|fields| fields.orientation)
+}
 
 pub fn make_preview_image(image_path: &Path, out_path: &Path) {
+    try_make_preview_image(image_path, out_path).expect("make_preview_image");
+}
+
+/// Like make_preview_image, but returns an error instead of panicking. For images
+/// that didn't come straight from the file tree (document pages, archive members),
+/// which may well not decode.
+pub fn try_make_preview_image(image_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let rotate_filter = read_orientation(image_path).and_then(orientation_filter);
+
     let mut command = std::process::Command::new("ffmpeg");
-    command.arg("-i").arg(image_path);
-    preview::ffmpeg_preview_args(&mut command);
-    let output = command
-        .arg(out_path)
-        .output()
-        .expect("failed to spawn ffmpeg");
-    ffmpeg::check_command_output(output, "make_preview_image");
+    // Rotate explicitly, rather than relying on the ffmpeg version's handling of EXIF
+    command.arg("-noautorotate").arg("-i").arg(image_path);
+    preview::ffmpeg_preview_args_with_filter(&mut command, rotate_filter);
+    let output = command.arg(out_path).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_preview_image failed for {:?}: {}",
+            image_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -21,6 +63,14 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_orientation_filter() {
+        assert_eq!(orientation_filter(1), None);
+        assert_eq!(orientation_filter(6), Some("transpose=clock"));
+        assert_eq!(orientation_filter(8), Some("transpose=cclock"));
+        assert_eq!(orientation_filter(42), None);
+    }
+
     fn get_image_dimensions(image_path: &Path) -> (u32, u32) {
         // Use ffprobe to get the dimensions of the image
         let output = std::process::Command::new("ffprobe")
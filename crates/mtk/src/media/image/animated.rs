@@ -0,0 +1,230 @@
+//! Animated images (GIF, WebP, APNG): detection, hover previews, and converting big
+//! GIFs to a much smaller looping video.
+
+use std::path::Path;
+
+use crate::file_tree::{GeneratedFile, GeneratedFileType};
+use crate::media::ffmpeg;
+
+/// GIFs at least this big get a webm alt format
+pub const GIF_TRANSCODE_MIN_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Hover previews of animated images are cut off after this long
+const MINIVID_MAX_SECS: f64 = 10.0;
+const MINIVID_DIMENSION: u32 = 240;
+
+pub fn gif_webm_gfile(entry_id: i64) -> GeneratedFile {
+    GeneratedFile {
+        entry_id,
+        file_type: GeneratedFileType::AltFormat,
+        metadata: "vp9_loop".to_string(),
+        extension: "webm".to_string(),
+    }
+}
+
+/// Count the frames in a GIF, stopping once there's more than one.
+fn gif_has_multiple_frames(data: &[u8]) -> bool {
+    // Header and logical screen descriptor
+    let Some(&packed) = data.get(10) else {
+        return false;
+    };
+    let mut pos = 13;
+    if packed & 0x80 != 0 {
+        pos += 3 << ((packed & 0x07) + 1);
+    }
+
+    // Skip a series of data sub-blocks, ending with an empty one
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let size = *data.get(pos)? as usize;
+            pos += 1 + size;
+            if size == 0 {
+                return Some(pos);
+            }
+        }
+    };
+
+    let mut num_frames = 0;
+    while let Some(&block_type) = data.get(pos) {
+        match block_type {
+            // Extension: label, then sub-blocks
+            0x21 => match skip_sub_blocks(pos + 2) {
+                Some(next) => pos = next,
+                None => break,
+            },
+            // Image descriptor, optional local color table, LZW code size, then sub-blocks
+            0x2c => {
+                num_frames += 1;
+                if num_frames > 1 {
+                    return true;
+                }
+                let Some(&packed) = data.get(pos + 9) else {
+                    break;
+                };
+                pos += 10;
+                if packed & 0x80 != 0 {
+                    pos += 3 << ((packed & 0x07) + 1);
+                }
+                match skip_sub_blocks(pos + 1) {
+                    Some(next) => pos = next,
+                    None => break,
+                }
+            }
+            // Trailer, or garbage
+            _ => break,
+        }
+    }
+    false
+}
+
+/// Whether an image file's contents are animated. Only the container is checked, so
+/// this is cheap even for large files.
+pub fn is_animated(data: &[u8]) -> bool {
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        gif_has_multiple_frames(data)
+    } else if data.len() >= 21 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" && &data[12..16] == b"VP8X" {
+        // Extended WebP: the animation flag is in the VP8X header
+        data[20] & 0x02 != 0
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // APNG: an acTL chunk before the image data
+        let mut pos = 8;
+        while let Some(header) = data.get(pos..pos + 8) {
+            let length = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+            match &header[4..8] {
+                b"acTL" => return true,
+                b"IDAT" => return false,
+                _ => pos += 12 + length,
+            }
+        }
+        false
+    } else {
+        false
+    }
+}
+
+/// Make a short looping video of an animated image, for hover previews.
+pub fn make_minivid(image_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // The minivid job skips images that already have one, so don't leave a partial one
+    let temp_path = ffmpeg::temp_output_path(out_path)?;
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(image_path)
+        .arg("-t")
+        .arg(format!("{}s", MINIVID_MAX_SECS))
+        .arg("-vf")
+        .arg(format!(
+            "scale=w='if(gt(iw,ih),-2,min(iw,{dim}))':h='if(gt(iw,ih),min(ih,{dim}),-2)',setsar=1",
+            dim = MINIVID_DIMENSION,
+        ))
+        .arg("-an")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-c:v")
+        .arg("libvpx-vp9")
+        .arg("-b:v")
+        .arg("150k")
+        .arg("-deadline")
+        .arg("good")
+        .arg("-cpu-used")
+        .arg("4")
+        .arg(&temp_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_minivid failed for {:?}: {}",
+            image_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    temp_path.persist(out_path)?;
+    Ok(())
+}
+
+/// Convert a GIF to a webm that's played in a loop instead. These are usually a
+/// fraction of the size, and decode much more cheaply.
+pub fn transcode_gif(gif_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // The entry page shows the webm as soon as it exists, so don't leave a partial one
+    let temp_path = ffmpeg::temp_output_path(out_path)?;
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(gif_path)
+        // yuv420p needs even dimensions
+        .arg("-vf")
+        .arg("scale=trunc(iw/2)*2:trunc(ih/2)*2")
+        .arg("-an")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-c:v")
+        .arg("libvpx-vp9")
+        .arg("-crf")
+        .arg("35")
+        .arg("-b:v")
+        .arg("0")
+        .arg(&temp_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "transcode_gif failed for {:?}: {}",
+            gif_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    temp_path.persist(out_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GIF with a 2-color global color table and `num_frames` 1x1 frames
+    fn make_gif(num_frames: usize) -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        gif.extend([0, 0, 0, 255, 255, 255]);
+        // Netscape looping extension
+        gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+        for _ in 0..num_frames {
+            // Graphic control extension
+            gif.extend(b"\x21\xf9\x04\x04\x0a\x00\x00\x00");
+            // Image descriptor, LZW code size, one data sub-block, terminator
+            gif.extend(b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x00");
+            gif.extend(b"\x02\x02\x44\x01\x00");
+        }
+        gif.push(0x3b);
+        gif
+    }
+
+    #[test]
+    fn test_is_animated_gif() {
+        assert!(!is_animated(&make_gif(1)));
+        assert!(is_animated(&make_gif(3)));
+        // Truncated
+        assert!(!is_animated(&make_gif(3)[..40]));
+    }
+
+    #[test]
+    fn test_is_animated_webp_png() {
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+        webp.extend([0x02, 0, 0, 0]);
+        assert!(is_animated(&webp));
+        webp[20] = 0x10; // Just the alpha flag
+        assert!(!is_animated(&webp));
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(b"\0\0\0\x0dIHDR");
+        png.extend([0; 13 + 4]);
+        let mut apng = png.clone();
+        apng.extend(b"\0\0\0\x08acTL");
+        assert!(is_animated(&apng));
+        png.extend(b"\0\0\0\0IDAT");
+        assert!(!is_animated(&png));
+
+        assert!(!is_animated(b"\xff\xd8\xff"));
+    }
+}
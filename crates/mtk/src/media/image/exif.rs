@@ -0,0 +1,446 @@
+//! Minimal EXIF reader: finds the TIFF-structured EXIF block in JPEG, PNG and WebP
+//! files and reads the handful of tags we care about. Anything malformed is skipped
+//! rather than treated as an error, since camera and editor output varies a lot.
+
+/// The EXIF fields we extract. All are optional.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExifFields {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens_model: Option<String>,
+    /// As recorded, "YYYY:MM:DD HH:MM:SS" in the camera's local time
+    pub date_time_original: Option<String>,
+    pub orientation: Option<u16>,
+    /// Signed decimal degrees
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+    /// Meters, negative below sea level
+    pub gps_altitude: Option<f64>,
+}
+
+// IFD0 tags
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+// Exif IFD tags
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_LENS_MODEL: u16 = 0xa434;
+// GPS IFD tags
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+const TAG_GPS_ALTITUDE_REF: u16 = 0x0005;
+const TAG_GPS_ALTITUDE: u16 = 0x0006;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Find the EXIF block (starting at the TIFF header) in an image file's contents.
+pub fn find_exif_block(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        find_exif_in_jpeg(data)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        find_exif_in_png(data)
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        find_exif_in_webp(data)
+    } else {
+        None
+    }
+}
+
+fn find_exif_in_jpeg(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Start of scan: no more metadata segments after this
+        if marker == 0xda {
+            return None;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xe1 && segment.starts_with(EXIF_HEADER) {
+            return Some(&segment[EXIF_HEADER.len()..]);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+fn find_exif_in_png(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        let chunk_type = &data[pos + 4..pos + 8];
+        let chunk = data.get(pos + 8..pos + 8 + length)?;
+        if chunk_type == b"eXIf" {
+            return Some(chunk);
+        }
+        if chunk_type == b"IDAT" {
+            return None;
+        }
+        // Length, type, data and CRC
+        pos += 12 + length;
+    }
+    None
+}
+
+fn find_exif_in_webp(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let chunk_type = &data[pos..pos + 4];
+        let length = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let chunk = data.get(pos + 8..pos + 8 + length)?;
+        if chunk_type == b"EXIF" {
+            // Some writers include the JPEG-style header, some don't
+            return Some(chunk.strip_prefix(EXIF_HEADER).unwrap_or(chunk));
+        }
+        // Chunks are padded to an even length
+        pos += 8 + length + (length & 1);
+    }
+    None
+}
+
+/// A TIFF-structured block, with its byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// One IFD entry, with its value still encoded.
+struct IfdEntry {
+    field_type: u16,
+    count: u32,
+    // Offset of the value in the TIFF data (inline values point into the entry itself)
+    value_offset: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Tiff<'a>> {
+        let little_endian = match data.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let tiff = Tiff { data, little_endian };
+        (tiff.u16_at(2)? == 42).then_some(tiff)
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn ifd0_offset(&self) -> Option<usize> {
+        self.u32_at(4).map(|offset| offset as usize)
+    }
+
+    /// Read the entries of the IFD at `offset`, keyed by tag.
+    fn read_ifd(&self, offset: usize) -> Vec<(u16, IfdEntry)> {
+        let Some(count) = self.u16_at(offset) else {
+            return Vec::new();
+        };
+
+        let mut entries = Vec::new();
+        for i in 0..count as usize {
+            let entry_offset = offset + 2 + i * 12;
+            let (Some(tag), Some(field_type), Some(value_count)) = (
+                self.u16_at(entry_offset),
+                self.u16_at(entry_offset + 2),
+                self.u32_at(entry_offset + 4),
+            ) else {
+                break;
+            };
+
+            let Some(type_size) = type_size(field_type) else {
+                continue;
+            };
+            let value_offset = if type_size * value_count as usize <= 4 {
+                entry_offset + 8
+            } else {
+                match self.u32_at(entry_offset + 8) {
+                    Some(offset) => offset as usize,
+                    None => continue,
+                }
+            };
+            entries.push((tag, IfdEntry { field_type, count: value_count, value_offset }));
+        }
+        entries
+    }
+
+    fn read_string(&self, entry: &IfdEntry) -> Option<String> {
+        if entry.field_type != 2 {
+            return None;
+        }
+        let bytes = self.data.get(entry.value_offset..entry.value_offset + entry.count as usize)?;
+        let string = String::from_utf8_lossy(bytes);
+        let string = string.trim_end_matches('\0').trim();
+        (!string.is_empty()).then(|| string.to_string())
+    }
+
+    fn read_uint(&self, entry: &IfdEntry) -> Option<u32> {
+        match entry.field_type {
+            1 | 7 => self.data.get(entry.value_offset).map(|b| *b as u32),
+            3 => self.u16_at(entry.value_offset).map(|v| v as u32),
+            4 => self.u32_at(entry.value_offset),
+            _ => None,
+        }
+    }
+
+    fn read_rationals(&self, entry: &IfdEntry) -> Option<Vec<f64>> {
+        if entry.field_type != 5 {
+            return None;
+        }
+        (0..entry.count as usize)
+            .map(|i| {
+                let numerator = self.u32_at(entry.value_offset + i * 8)?;
+                let denominator = self.u32_at(entry.value_offset + i * 8 + 4)?;
+                (denominator != 0).then(|| numerator as f64 / denominator as f64)
+            })
+            .collect()
+    }
+}
+
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+fn find(entries: &[(u16, IfdEntry)], tag: u16) -> Option<&IfdEntry> {
+    entries.iter().find(|(t, _)| *t == tag).map(|(_, entry)| entry)
+}
+
+/// Degrees, minutes and seconds to signed decimal degrees.
+fn gps_coordinate(tiff: &Tiff, gps_ifd: &[(u16, IfdEntry)], value_tag: u16, ref_tag: u16, negative_ref: &str) -> Option<f64> {
+    let dms = tiff.read_rationals(find(gps_ifd, value_tag)?)?;
+    let degrees = dms.first()? + dms.get(1).unwrap_or(&0.0) / 60.0 + dms.get(2).unwrap_or(&0.0) / 3600.0;
+    let reference = find(gps_ifd, ref_tag).and_then(|entry| tiff.read_string(entry));
+    if reference.as_deref() == Some(negative_ref) {
+        Some(-degrees)
+    } else {
+        Some(degrees)
+    }
+}
+
+/// Read the fields we care about from an EXIF block (starting at the TIFF header).
+pub fn parse_exif(tiff_data: &[u8]) -> Option<ExifFields> {
+    let tiff = Tiff::new(tiff_data)?;
+    let ifd0 = tiff.read_ifd(tiff.ifd0_offset()?);
+
+    let string_tag = |entries: &[(u16, IfdEntry)], tag| find(entries, tag).and_then(|entry| tiff.read_string(entry));
+    let sub_ifd = |tag| {
+        find(&ifd0, tag)
+            .and_then(|entry| tiff.read_uint(entry))
+            .map(|offset| tiff.read_ifd(offset as usize))
+            .unwrap_or_default()
+    };
+    let exif_ifd = sub_ifd(TAG_EXIF_IFD);
+    let gps_ifd = sub_ifd(TAG_GPS_IFD);
+
+    let gps_altitude = find(&gps_ifd, TAG_GPS_ALTITUDE)
+        .and_then(|entry| tiff.read_rationals(entry))
+        .and_then(|values| values.first().copied())
+        .map(|altitude| {
+            let below_sea_level = find(&gps_ifd, TAG_GPS_ALTITUDE_REF).and_then(|entry| tiff.read_uint(entry)) == Some(1);
+            if below_sea_level { -altitude } else { altitude }
+        });
+
+    Some(ExifFields {
+        make: string_tag(&ifd0, TAG_MAKE),
+        model: string_tag(&ifd0, TAG_MODEL),
+        lens_model: string_tag(&exif_ifd, TAG_LENS_MODEL),
+        // Fall back to the modification date when the original date is missing
+        date_time_original: string_tag(&exif_ifd, TAG_DATE_TIME_ORIGINAL).or_else(|| string_tag(&ifd0, TAG_DATE_TIME)),
+        orientation: find(&ifd0, TAG_ORIENTATION).and_then(|entry| tiff.read_uint(entry)).map(|o| o as u16),
+        gps_latitude: gps_coordinate(&tiff, &gps_ifd, TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, "S"),
+        gps_longitude: gps_coordinate(&tiff, &gps_ifd, TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, "W"),
+        gps_altitude,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds big-endian TIFF blocks for tests. Values that don't fit in an entry
+    /// are placed after all the IFDs.
+    pub(crate) struct TiffBuilder {
+        ifds: Vec<Vec<(u16, u16, u32, Vec<u8>)>>,
+    }
+
+    pub(crate) enum Value<'a> {
+        Ascii(&'a str),
+        Short(u16),
+        Long(u32),
+        Byte(u8),
+        Rationals(&'a [(u32, u32)]),
+        /// Offset of another IFD, by index
+        Ifd(usize),
+    }
+
+    impl TiffBuilder {
+        pub(crate) fn new(num_ifds: usize) -> TiffBuilder {
+            TiffBuilder { ifds: vec![Vec::new(); num_ifds] }
+        }
+
+        pub(crate) fn add(&mut self, ifd: usize, tag: u16, value: Value) -> &mut Self {
+            let (field_type, count, bytes) = match value {
+                Value::Ascii(s) => (2, s.len() as u32 + 1, [s.as_bytes(), b"\0"].concat()),
+                Value::Short(v) => (3, 1, v.to_be_bytes().to_vec()),
+                Value::Long(v) => (4, 1, v.to_be_bytes().to_vec()),
+                Value::Byte(v) => (1, 1, vec![v]),
+                Value::Rationals(values) => (
+                    5,
+                    values.len() as u32,
+                    values.iter().flat_map(|(n, d)| [n.to_be_bytes(), d.to_be_bytes()].concat()).collect(),
+                ),
+                // Patched with the real offset in build()
+                Value::Ifd(index) => (4, 1, (index as u32).to_be_bytes().to_vec()),
+            };
+            self.ifds[ifd].push((tag, field_type, count, bytes));
+            self
+        }
+
+        pub(crate) fn build(&self) -> Vec<u8> {
+            let ifd_offsets: Vec<usize> = self
+                .ifds
+                .iter()
+                .scan(8, |offset, ifd| {
+                    let this = *offset;
+                    *offset += 2 + ifd.len() * 12 + 4;
+                    Some(this)
+                })
+                .collect();
+            let mut extra_offset = ifd_offsets.last().map_or(8, |last| last + 2 + self.ifds.last().unwrap().len() * 12 + 4);
+
+            let mut data = b"MM\0\x2a\0\0\0\x08".to_vec();
+            let mut extra = Vec::new();
+            for ifd in &self.ifds {
+                data.extend((ifd.len() as u16).to_be_bytes());
+                for (tag, field_type, count, bytes) in ifd {
+                    data.extend(tag.to_be_bytes());
+                    data.extend(field_type.to_be_bytes());
+                    data.extend(count.to_be_bytes());
+                    let bytes = if [TAG_EXIF_IFD, TAG_GPS_IFD].contains(tag) {
+                        let index = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+                        (ifd_offsets[index] as u32).to_be_bytes().to_vec()
+                    } else {
+                        bytes.clone()
+                    };
+                    if bytes.len() <= 4 {
+                        let mut inline = bytes.clone();
+                        inline.resize(4, 0);
+                        data.extend(inline);
+                    } else {
+                        data.extend((extra_offset as u32).to_be_bytes());
+                        extra_offset += bytes.len();
+                        extra.extend(bytes);
+                    }
+                }
+                // No next IFD
+                data.extend(0u32.to_be_bytes());
+            }
+            data.extend(extra);
+            data
+        }
+    }
+
+    pub(crate) fn sample_exif() -> Vec<u8> {
+        let mut builder = TiffBuilder::new(3);
+        builder
+            .add(0, TAG_MAKE, Value::Ascii("Canon"))
+            .add(0, TAG_MODEL, Value::Ascii("Canon EOS R6"))
+            .add(0, TAG_ORIENTATION, Value::Short(6))
+            .add(0, TAG_DATE_TIME, Value::Ascii("2023:09:01 10:00:00"))
+            .add(0, TAG_EXIF_IFD, Value::Ifd(1))
+            .add(0, TAG_GPS_IFD, Value::Ifd(2))
+            .add(1, TAG_DATE_TIME_ORIGINAL, Value::Ascii("2023:08:14 16:02:11"))
+            .add(1, TAG_LENS_MODEL, Value::Ascii("RF24-105mm F4 L IS USM"))
+            .add(2, TAG_GPS_LATITUDE_REF, Value::Ascii("N"))
+            .add(2, TAG_GPS_LATITUDE, Value::Rationals(&[(35, 1), (39, 1), (2952, 100)]))
+            .add(2, TAG_GPS_LONGITUDE_REF, Value::Ascii("W"))
+            .add(2, TAG_GPS_LONGITUDE, Value::Rationals(&[(139, 1), (42, 1), (0, 1)]))
+            .add(2, TAG_GPS_ALTITUDE_REF, Value::Byte(1))
+            .add(2, TAG_GPS_ALTITUDE, Value::Rationals(&[(125, 10)]));
+        builder.build()
+    }
+
+    #[test]
+    fn test_parse_exif() {
+        let fields = parse_exif(&sample_exif()).expect("parse_exif");
+        assert_eq!(fields.make.as_deref(), Some("Canon"));
+        assert_eq!(fields.model.as_deref(), Some("Canon EOS R6"));
+        assert_eq!(fields.lens_model.as_deref(), Some("RF24-105mm F4 L IS USM"));
+        assert_eq!(fields.date_time_original.as_deref(), Some("2023:08:14 16:02:11"));
+        assert_eq!(fields.orientation, Some(6));
+        assert!((fields.gps_latitude.unwrap() - 35.6582).abs() < 0.0001);
+        assert_eq!(fields.gps_longitude, Some(-139.7));
+        assert_eq!(fields.gps_altitude, Some(-12.5));
+    }
+
+    #[test]
+    fn test_parse_exif_minimal() {
+        let mut builder = TiffBuilder::new(1);
+        builder
+            .add(0, TAG_DATE_TIME, Value::Ascii("2020:01:02 03:04:05"))
+            // Some writers store the orientation as a LONG rather than a SHORT
+            .add(0, TAG_ORIENTATION, Value::Long(3));
+        let fields = parse_exif(&builder.build()).expect("parse_exif");
+        assert_eq!(fields, ExifFields {
+            date_time_original: Some("2020:01:02 03:04:05".to_string()),
+            orientation: Some(3),
+            ..Default::default()
+        });
+
+        assert!(parse_exif(b"not exif").is_none());
+        // Truncated
+        assert!(parse_exif(&sample_exif()[..20]).is_some_and(|fields| fields.make.is_none()));
+    }
+
+    #[test]
+    fn test_find_exif_block() {
+        let exif = sample_exif();
+
+        // JPEG: SOI, an APP0 segment, then APP1 with the EXIF header
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xe1];
+        jpeg.extend(((exif.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(EXIF_HEADER);
+        jpeg.extend(&exif);
+        jpeg.extend([0xff, 0xda]);
+        assert_eq!(find_exif_block(&jpeg), Some(&exif[..]));
+
+        // PNG eXIf chunk
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend((exif.len() as u32).to_be_bytes());
+        png.extend(b"eXIf");
+        png.extend(&exif);
+        png.extend([0, 0, 0, 0]);
+        assert_eq!(find_exif_block(&png), Some(&exif[..]));
+
+        // WebP EXIF chunk, with the optional header
+        let mut webp = b"RIFF\0\0\0\0WEBP".to_vec();
+        webp.extend(b"EXIF");
+        webp.extend(((exif.len() + EXIF_HEADER.len()) as u32).to_le_bytes());
+        webp.extend(EXIF_HEADER);
+        webp.extend(&exif);
+        assert_eq!(find_exif_block(&webp), Some(&exif[..]));
+
+        assert_eq!(find_exif_block(b"GIF89a"), None);
+        assert_eq!(find_exif_block(&[0xff, 0xd8, 0xff, 0xda]), None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::{generated_notes, DbEntry};
+
+use super::{animated, exif};
+
+/// Generated notes group (on the main entry)
+pub const IMAGE_INFO_GROUP_NAME: &str = "image_info";
+
+/// Version of the "image_info" group, bumped when ImageInfo changes so the job
+/// re-reads existing images.
+///
+/// 2: animated
+pub const IMAGE_INFO_VERSION: u64 = 2;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+
+    /// When the photo was taken, as "YYYY-MM-DDTHH:MM:SS" in the camera's local time
+    pub capture_date: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    /// EXIF orientation (1-8)
+    pub orientation: Option<u16>,
+    pub gps: Option<GpsPosition>,
+    /// Animated GIF, WebP or PNG
+    #[serde(default)]
+    pub animated: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+impl ImageInfo {
+    pub fn capture_time(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(self.capture_date.as_deref()?, "%Y-%m-%dT%H:%M:%S").ok()
+    }
+
+    /// Camera make and model, without repeating the make (models often include it)
+    pub fn camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+}
+
+/// Normalize EXIF ("2023:08:14 16:02:11") and XMP ("2023-08-14T16:02:11.50+02:00")
+/// dates to "2023-08-14T16:02:11". Dates without a time, and zeroed-out dates from
+/// cameras whose clock was never set, are dropped.
+fn normalize_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let prefix = date.get(0..19)?;
+    let normalized: String = prefix
+        .char_indices()
+        .map(|(i, c)| match (i, c) {
+            (4 | 7, ':') => '-',
+            (10, ' ') => 'T',
+            _ => c,
+        })
+        .collect();
+    NaiveDateTime::parse_from_str(&normalized, "%Y-%m-%dT%H:%M:%S").ok()?;
+    Some(normalized)
+}
+
+/// Properties from an XMP packet, used where EXIF is missing. Handles both the
+/// attribute (`exif:DateTimeOriginal="..."`) and element forms.
+fn xmp_property(xmp: &str, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        let pattern = format!(
+            r#"{name}="([^"]*)"|<{name}>([^<]*)</{name}>"#,
+            name = regex::escape(name),
+        );
+        let captures = regex::Regex::new(&pattern).ok()?.captures(xmp)?;
+        let value = captures.get(1).or_else(|| captures.get(2))?.as_str().trim();
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+fn find_xmp_packet(data: &[u8]) -> Option<String> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+    let start = data.windows(START.len()).position(|w| w == START)?;
+    let end = start + data[start..].windows(END.len()).position(|w| w == END)? + END.len();
+    Some(String::from_utf8_lossy(&data[start..end]).into_owned())
+}
+
+/// Build an ImageInfo from the file's contents (for metadata) and its dimensions.
+fn parse_image_metadata(data: &[u8], width: u32, height: u32) -> ImageInfo {
+    let exif = exif::find_exif_block(data)
+        .and_then(exif::parse_exif)
+        .unwrap_or_default();
+    let xmp = find_xmp_packet(data).unwrap_or_default();
+
+    let gps = match (exif.gps_latitude, exif.gps_longitude) {
+        (Some(latitude), Some(longitude)) => Some(GpsPosition { latitude, longitude, altitude: exif.gps_altitude }),
+        _ => None,
+    };
+
+    ImageInfo {
+        width,
+        height,
+        capture_date: exif
+            .date_time_original
+            .as_deref()
+            .and_then(normalize_date)
+            .or_else(|| {
+                xmp_property(&xmp, &["exif:DateTimeOriginal", "photoshop:DateCreated", "xmp:CreateDate"])
+                    .as_deref()
+                    .and_then(normalize_date)
+            }),
+        camera_make: exif.make.or_else(|| xmp_property(&xmp, &["tiff:Make"])),
+        camera_model: exif.model.or_else(|| xmp_property(&xmp, &["tiff:Model"])),
+        lens: exif.lens_model.or_else(|| xmp_property(&xmp, &["exifEX:LensModel", "aux:Lens"])),
+        orientation: exif.orientation,
+        gps,
+        animated: animated::is_animated(data),
+    }
+}
+
+fn get_image_dimensions(image_path: &Path) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    let output = std::process::Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("stream=width,height")
+        .arg("-of")
+        .arg("csv=p=0:s=x")
+        .arg(image_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed for {:?}: {}",
+            image_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (width, height) = stdout.trim().split_once('x').ok_or("unexpected ffprobe output")?;
+    Ok((width.parse()?, height.parse()?))
+}
+
+/// Extract an ImageInfo from an image file: dimensions using ffprobe, and
+/// EXIF/XMP metadata from the file itself.
+pub fn get_image_info(image_path: &Path) -> Result<ImageInfo, Box<dyn std::error::Error>> {
+    let (width, height) = get_image_dimensions(image_path)?;
+    let data = std::fs::read(image_path)?;
+    Ok(parse_image_metadata(&data, width, height))
+}
+
+/// The cached ImageInfo from the "image_info" job, if it has run.
+pub fn read_image_info(db_entry: &DbEntry) -> Option<ImageInfo> {
+    generated_notes::read::<ImageInfo>(db_entry, IMAGE_INFO_GROUP_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    #[test]
+    fn test_normalize_date() {
+        assert_eq!(normalize_date("2023:08:14 16:02:11").as_deref(), Some("2023-08-14T16:02:11"));
+        assert_eq!(normalize_date("2023-08-14T16:02:11.50+02:00").as_deref(), Some("2023-08-14T16:02:11"));
+        assert_eq!(normalize_date("0000:00:00 00:00:00"), None);
+        assert_eq!(normalize_date("2023-08-14"), None);
+    }
+
+    #[test]
+    fn test_parse_image_metadata_exif() {
+        // JPEG with just an APP1 segment
+        let exif = exif::tests::sample_exif();
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+        jpeg.extend(((exif.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(&exif);
+
+        let info = parse_image_metadata(&jpeg, 6000, 4000);
+        assert_eq!((info.width, info.height), (6000, 4000));
+        assert_eq!(info.capture_date.as_deref(), Some("2023-08-14T16:02:11"));
+        assert_eq!(info.capture_time().unwrap().to_string(), "2023-08-14 16:02:11");
+        assert_eq!(info.camera().as_deref(), Some("Canon EOS R6"));
+        assert_eq!(info.lens.as_deref(), Some("RF24-105mm F4 L IS USM"));
+        assert_eq!(info.orientation, Some(6));
+        assert_eq!(info.gps.unwrap().longitude, -139.7);
+    }
+
+    #[test]
+    fn test_parse_image_metadata_xmp() {
+        let png = br#"\x89PNG\r\n\x1a\n...<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description
+            xmp:CreateDate="2021-05-06T07:08:09+01:00" tiff:Make="FUJIFILM">
+            <tiff:Model>X-T4</tiff:Model></rdf:Description></rdf:RDF></x:xmpmeta>..."#;
+        let info = parse_image_metadata(png, 10, 20);
+        assert_eq!(info.capture_date.as_deref(), Some("2021-05-06T07:08:09"));
+        assert_eq!(info.camera().as_deref(), Some("FUJIFILM X-T4"));
+        assert_eq!(info.gps, None);
+
+        // No metadata at all
+        assert_eq!(parse_image_metadata(b"GIF89a", 1, 2), ImageInfo { width: 1, height: 2, ..Default::default() });
+    }
+
+    #[test]
+    fn test_get_image_info() {
+        let info = get_image_info(&testing::testdata_path("preview").join("wide.png")).expect("get_image_info");
+        assert!(info.width > info.height);
+    }
+}
@@ -1,3 +1,7 @@
+pub mod audio;
+pub mod dir_preview;
+pub mod document;
 pub mod ffmpeg;
 pub mod image;
+pub mod phash;
 pub mod video;
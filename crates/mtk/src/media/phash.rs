@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub const PHASH_GROUP_NAME: &str = "phash";
+
+/// dHash compares each pixel of a 9x8 grayscale thumbnail to its right neighbor
+const DHASH_WIDTH: usize = 9;
+const DHASH_HEIGHT: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PerceptualHash {
+    // 64-bit difference hash, as 16 hex digits (JSON numbers can't hold all u64s)
+    pub dhash: String,
+    /// Modification time (in seconds) of the preview that was hashed, for entries that
+    /// are hashed by their preview. Remaking the preview changes it, so it's hashed again.
+    #[serde(default)]
+    pub preview_mtime: Option<i64>,
+}
+
+impl PerceptualHash {
+    pub fn from_dhash(dhash: u64) -> PerceptualHash {
+        PerceptualHash {
+            dhash: format!("{:016x}", dhash),
+            preview_mtime: None,
+        }
+    }
+
+    pub fn dhash(&self) -> Option<u64> {
+        u64::from_str_radix(&self.dhash, 16).ok()
+    }
+}
+
+/// Number of bits that differ between two hashes. Lower is more similar; under ~10
+/// usually means the same picture, resized or re-encoded.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Compute a dHash from 9x8 grayscale pixels, in row-major order.
+pub fn dhash_from_pixels(pixels: &[u8]) -> u64 {
+    assert_eq!(pixels.len(), DHASH_WIDTH * DHASH_HEIGHT);
+
+    let mut hash = 0u64;
+    for row in pixels.chunks(DHASH_WIDTH) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | ((pair[0] < pair[1]) as u64);
+        }
+    }
+    hash
+}
+
+/// Compute the dHash of an image (or the first frame of a video) using ffmpeg.
+pub fn compute_dhash(image_path: &Path) -> Result<u64, Box<dyn std::error::Error>> {
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(image_path)
+        .arg("-vframes")
+        .arg("1")
+        .arg("-vf")
+        .arg(format!("scale={}:{}:flags=area,format=gray", DHASH_WIDTH, DHASH_HEIGHT))
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg failed for {:?}: {}",
+            image_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+
+    let pixels = &output.stdout;
+    if pixels.len() != DHASH_WIDTH * DHASH_HEIGHT {
+        return Err(format!("unexpected dhash thumbnail size {} for {:?}", pixels.len(), image_path).into());
+    }
+    Ok(dhash_from_pixels(pixels))
+}
+
+pub fn get_perceptual_hash(image_path: &Path) -> Result<PerceptualHash, Box<dyn std::error::Error>> {
+    compute_dhash(image_path).map(PerceptualHash::from_dhash)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::testdata_path;
+
+    use super::*;
+
+    #[test]
+    fn test_dhash_from_pixels() {
+        // Every row gets brighter to the right: all bits set
+        let increasing: Vec<u8> = (0..DHASH_HEIGHT)
+            .flat_map(|_| (0..DHASH_WIDTH as u8).map(|x| x * 10))
+            .collect();
+        assert_eq!(dhash_from_pixels(&increasing), u64::MAX);
+
+        let flat = vec![128u8; DHASH_WIDTH * DHASH_HEIGHT];
+        assert_eq!(dhash_from_pixels(&flat), 0);
+
+        // Uniformly brighter image has the same hash
+        let brighter: Vec<u8> = increasing.iter().map(|p| p + 50).collect();
+        assert_eq!(dhash_from_pixels(&brighter), dhash_from_pixels(&increasing));
+    }
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(0b1011, 0b0001), 2);
+        assert_eq!(hamming_distance(0, u64::MAX), 64);
+    }
+
+    #[test]
+    fn test_perceptual_hash_roundtrip() {
+        let hash = PerceptualHash::from_dhash(0x00ff_1234_abcd_0001);
+        assert_eq!(hash.dhash, "00ff1234abcd0001");
+        assert_eq!(hash.dhash(), Some(0x00ff_1234_abcd_0001));
+    }
+
+    #[test]
+    fn test_compute_dhash() {
+        let root = testdata_path("mixed").join("Photos");
+        let cats = compute_dhash(&root.join("cats_tall.jpg")).expect("compute_dhash");
+        let pidgeon = compute_dhash(&root.join("pidgeon_wide.jpg")).expect("compute_dhash");
+
+        assert_eq!(cats, compute_dhash(&root.join("cats_tall.jpg")).expect("compute_dhash"));
+        assert!(hamming_distance(cats, pidgeon) > 10);
+    }
+}
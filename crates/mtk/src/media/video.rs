@@ -1,7 +1,10 @@
 pub mod video_info;
-pub use video_info::VIDEO_INFO_GROUP_NAME;
+pub use video_info::{VIDEO_INFO_GROUP_NAME, VIDEO_INFO_VERSION};
 pub use video_info::VideoInfo;
+pub use video_info::AudioStreamInfo;
+pub use video_info::Chapter;
 pub use video_info::get_video_info;
+pub use video_info::get_video_info_cached;
 
 pub mod subtitle;
 pub use subtitle::find_all_vtt_subtitles;
@@ -10,5 +13,11 @@ pub use subtitle::SubtitleSource;
 
 pub mod video_preview;
 
+pub mod thumbnails;
+
 pub mod transcode;
-pub use transcode::chrome_can_play;
+
+pub mod playability;
+pub use playability::Client;
+
+pub mod hls;
@@ -0,0 +1,238 @@
+//! HLS streaming: playlists for each video, with segments transcoded on demand and
+//! cached in the generated tree.
+
+use std::path::{Path, PathBuf};
+
+use crate::file_tree::{GeneratedFile, GeneratedFileType, GeneratedTree};
+use crate::media::ffmpeg;
+
+use super::transcode::{self, TranscodeProfile};
+use super::VideoInfo;
+
+pub const HLS_SEGMENT_SECS: f64 = 6.0;
+
+/// Built-in profiles offered in the master playlist, lowest quality first. These
+/// aren't looked up in the config, since HLS needs H.264 at a known bitrate.
+pub const HLS_RENDITIONS: [&str; 2] = ["h264_200k", "h264_800k"];
+
+pub fn hls_renditions() -> Vec<TranscodeProfile> {
+    HLS_RENDITIONS
+        .iter()
+        .map(|name| TranscodeProfile::builtin(name).expect("built-in profile"))
+        .collect()
+}
+
+/// CODECS attribute for the renditions: H.264 High profile and AAC-LC
+const HLS_CODECS: &str = "avc1.64001f,mp4a.40.2";
+
+pub fn segment_gfile(entry_id: i64, profile: &TranscodeProfile, index: usize) -> GeneratedFile {
+    GeneratedFile {
+        entry_id,
+        file_type: GeneratedFileType::HlsSegment,
+        metadata: format!("{}_{:05}", profile.name, index),
+        extension: "ts".to_string(),
+    }
+}
+
+/// Renditions worth offering for this video: those that don't scale it up, or just
+/// the lowest one for small videos.
+pub fn renditions_for(video_info: &VideoInfo) -> Vec<TranscodeProfile> {
+    let mut renditions = hls_renditions();
+    let lowest = renditions[0].clone();
+    renditions.retain(|profile| profile.height <= video_info.height);
+    if renditions.is_empty() {
+        vec![lowest]
+    } else {
+        renditions
+    }
+}
+
+pub fn num_segments(duration_secs: f64) -> usize {
+    ((duration_secs / HLS_SEGMENT_SECS).ceil() as usize).max(1)
+}
+
+/// Master playlist listing each rendition. `rendition_url` gives the URL of a
+/// rendition's media playlist.
+pub fn master_playlist(video_info: &VideoInfo, rendition_url: impl Fn(&TranscodeProfile) -> String) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for profile in renditions_for(video_info) {
+        let height = std::cmp::min(profile.height, video_info.height);
+        let width = if video_info.height > 0 {
+            // Same rounding to an even width as the -2 in the scale filter
+            let width = video_info.width as f64 * height as f64 / video_info.height as f64;
+            (width / 2.0).round() as u32 * 2
+        } else {
+            0
+        };
+        playlist += &format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{},CODECS=\"{}\"\n{}\n",
+            profile.bandwidth_bps().unwrap_or_default(),
+            width,
+            height,
+            HLS_CODECS,
+            rendition_url(&profile),
+        );
+    }
+    playlist
+}
+
+/// Media playlist for one rendition. `segment_url` gives the URL of each segment.
+pub fn media_playlist(duration_secs: f64, segment_url: impl Fn(usize) -> String) -> String {
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n",
+        HLS_SEGMENT_SECS.ceil() as u64,
+    );
+    for index in 0..num_segments(duration_secs) {
+        let start = index as f64 * HLS_SEGMENT_SECS;
+        let length = HLS_SEGMENT_SECS.min(duration_secs - start);
+        playlist += &format!("#EXTINF:{:.3},\n{}\n", length, segment_url(index));
+    }
+    playlist += "#EXT-X-ENDLIST\n";
+    playlist
+}
+
+/// Transcode one segment of the video into an MPEG-TS file.
+pub fn transcode_segment(
+    video_path: &Path,
+    profile: &TranscodeProfile,
+    index: usize,
+    out_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start_secs = index as f64 * HLS_SEGMENT_SECS;
+
+    // Write to a temporary file first, so a segment that's still being transcoded
+    // (or was interrupted) is never served. Each request gets its own, since two
+    // players can ask for the same segment at once.
+    let temp_path = ffmpeg::temp_output_path(out_path)?;
+    let mut cmd = std::process::Command::new("ffmpeg");
+    cmd.arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-ss")
+        .arg(format!("{}s", start_secs))
+        .arg("-t")
+        .arg(format!("{}s", HLS_SEGMENT_SECS))
+        .arg("-i")
+        .arg(video_path);
+    transcode::hls_segment_args(profile, &mut cmd)?;
+    let output = cmd
+        // Keep the segment's timestamps relative to the whole video
+        .arg("-output_ts_offset")
+        .arg(format!("{}", start_secs))
+        .arg("-muxdelay")
+        .arg("0")
+        .arg("-f")
+        .arg("mpegts")
+        .arg(&temp_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "transcode_segment failed for {:?} segment {}: {}",
+            video_path,
+            index,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+
+    temp_path.persist(out_path)?;
+    Ok(())
+}
+
+/// Path to a segment in the cache, transcoding it first if needed.
+pub fn get_or_transcode_segment(
+    video_path: &Path,
+    video_info: &VideoInfo,
+    gen_tree: &GeneratedTree,
+    entry_id: i64,
+    profile: &TranscodeProfile,
+    index: usize,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if index >= num_segments(video_info.duration_secs) {
+        return Err(format!("segment {} is past the end of {:?}", index, video_path).into());
+    }
+
+    let segment_path = gen_tree.path_to_generated_file(&segment_gfile(entry_id, profile, index));
+    if !segment_path.exists() {
+        transcode_segment(video_path, profile, index, &segment_path)?;
+    }
+    Ok(segment_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    fn video_info(duration_secs: f64, width: u32, height: u32) -> VideoInfo {
+        VideoInfo {
+            mime_type: "video/webm".to_string(),
+            codec: "hevc".to_string(),
+            codec_rfc6381: "hvc1".to_string(),
+            duration_secs,
+            width,
+            height,
+            bitrate: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_renditions_for() {
+        assert_eq!(renditions_for(&video_info(60.0, 1920, 1080)).len(), 2);
+        assert_eq!(renditions_for(&video_info(60.0, 640, 480)).len(), 1);
+        // Smaller than every rendition
+        let tiny = renditions_for(&video_info(60.0, 320, 240));
+        assert_eq!(tiny.len(), 1);
+        assert_eq!(tiny[0].name, "h264_200k");
+    }
+
+    #[test]
+    fn test_master_playlist() {
+        let playlist = master_playlist(&video_info(60.0, 1920, 1080), |p| format!("{}/index.m3u8", p.name));
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=232000,RESOLUTION=640x360,CODECS=\"avc1.64001f,mp4a.40.2\"\n\
+            h264_200k/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=864000,RESOLUTION=1280x720,CODECS=\"avc1.64001f,mp4a.40.2\"\n\
+            h264_800k/index.m3u8\n"
+        );
+
+        // Not scaled up
+        let playlist = master_playlist(&video_info(60.0, 320, 240), |p| p.name.clone());
+        assert!(playlist.contains("RESOLUTION=320x240"));
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        let playlist = media_playlist(14.5, |i| format!("{}.ts", i));
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n\
+            #EXTINF:6.000,\n0.ts\n\
+            #EXTINF:6.000,\n1.ts\n\
+            #EXTINF:2.500,\n2.ts\n\
+            #EXT-X-ENDLIST\n"
+        );
+        assert_eq!(num_segments(12.0), 2);
+        assert_eq!(num_segments(0.0), 1);
+    }
+
+    #[test]
+    fn test_get_or_transcode_segment() {
+        let video_path = testing::testdata_path("preview").join("short_video.mp4");
+        let tempdir = tempfile::tempdir().expect("tempdir");
+        let gen_tree = GeneratedTree::new(tempdir.path());
+        let info = super::super::get_video_info(&video_path).expect("get_video_info");
+
+        let segment_path = get_or_transcode_segment(&video_path, &info, &gen_tree, 1, &hls_renditions()[0], 0)
+            .expect("get_or_transcode_segment");
+        assert!(segment_path.exists());
+        // Only the finished segment is left behind
+        let num_files = std::fs::read_dir(segment_path.parent().unwrap()).expect("read_dir").count();
+        assert_eq!(num_files, 1);
+
+        assert!(get_or_transcode_segment(&video_path, &info, &gen_tree, 1, &hls_renditions()[0], 1000).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Which browsers can play which videos, going by container, video codec (and
+//! profile) and audio codec. Used to pick the best source for each browser, and to
+//! decide which videos need transcoding.
+
+use serde::Deserialize;
+
+use super::transcode::{self, TranscodeProfile};
+use super::{AudioStreamInfo, VideoInfo};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Client {
+    /// Chrome, and other Chromium-based browsers
+    Chrome,
+    Firefox,
+    /// Safari on macOS, and every browser on iOS (they all use WebKit)
+    Safari,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Container {
+    Mp4,
+    WebM,
+    Matroska,
+    QuickTime,
+    Other,
+}
+
+fn container(video_info: &VideoInfo) -> Container {
+    match video_info.mime_type.as_str() {
+        "video/mp4" => Container::Mp4,
+        "video/webm" => Container::WebM,
+        "video/x-matroska" => Container::Matroska,
+        "video/quicktime" => Container::QuickTime,
+        _ => Container::Other,
+    }
+}
+
+/// Video codec family: "avc1", "hvc1", "vp08", "vp09" or "av01"
+fn video_codec(video_info: &VideoInfo) -> &str {
+    let codec = video_info.codec_rfc6381.as_str();
+    if codec.starts_with("av01") {
+        "av01"
+    } else {
+        codec
+    }
+}
+
+impl Client {
+    /// From the Sec-CH-UA client hint, which only Chromium-based browsers send
+    pub fn from_client_hint(sec_ch_ua: &str) -> Option<Client> {
+        (sec_ch_ua.contains("\"Chromium\"") || sec_ch_ua.contains("\"Google Chrome\"")).then_some(Client::Chrome)
+    }
+
+    pub fn from_user_agent(user_agent: &str) -> Option<Client> {
+        if ["iPhone", "iPad", "iPod"].iter().any(|device| user_agent.contains(device)) {
+            Some(Client::Safari)
+        } else if user_agent.contains("Firefox/") {
+            Some(Client::Firefox)
+        } else if user_agent.contains("Chrome/") || user_agent.contains("Chromium/") {
+            Some(Client::Chrome)
+        } else if user_agent.contains("Safari/") {
+            Some(Client::Safari)
+        } else {
+            None
+        }
+    }
+
+    fn plays_container(&self, container: Container) -> bool {
+        match (self, container) {
+            (_, Container::Mp4 | Container::WebM) => true,
+            // Chromium plays Matroska with codecs it supports (it's close enough to WebM)
+            (Client::Chrome, Container::Matroska) => true,
+            (Client::Chrome | Client::Safari, Container::QuickTime) => true,
+            _ => false,
+        }
+    }
+
+    fn plays_video_codec(&self, container: Container, codec: &str) -> bool {
+        match (self, codec) {
+            (_, "avc1") => true,
+            // Chrome can't consistently play HEVC yet, and Firefox not at all
+            (Client::Safari, "hvc1") => true,
+            (Client::Chrome | Client::Firefox, "vp08" | "vp09" | "av01") => true,
+            // Safari plays VP8/VP9 only in WebM, and AV1 only with hardware decoding
+            (Client::Safari, "vp08" | "vp09") => container == Container::WebM,
+            _ => false,
+        }
+    }
+
+    fn plays_audio_codec(&self, codec: &str) -> bool {
+        matches!(
+            (self, codec),
+            (_, "aac" | "mp3" | "opus" | "flac")
+                | (Client::Chrome | Client::Firefox, "vorbis")
+                | (Client::Safari, "ac3" | "eac3")
+        )
+    }
+
+    pub fn can_play(&self, video_info: &VideoInfo) -> bool {
+        let container = container(video_info);
+        let codec = video_codec(video_info);
+        // Browsers only decode 8-bit 4:2:0 H.264, not High 10 or 4:4:4
+        let profile_ok = codec != "avc1"
+            || video_info.pix_fmt.as_deref().is_none_or(|pix_fmt| matches!(pix_fmt, "yuv420p" | "yuvj420p"));
+        // Browsers only play the first audio stream
+        let audio_ok = video_info
+            .audio_streams
+            .first()
+            .is_none_or(|audio| self.plays_audio_codec(&audio.codec));
+        self.plays_container(container) && self.plays_video_codec(container, codec) && profile_ok && audio_ok
+    }
+
+    /// Whether the client can play what the profile transcodes to
+    pub fn can_play_profile(&self, profile: &TranscodeProfile) -> bool {
+        self.can_play(&profile_output_info(profile))
+    }
+}
+
+/// Whether all of the clients can play the video
+pub fn all_can_play(clients: &[Client], video_info: &VideoInfo) -> bool {
+    clients.iter().all(|client| client.can_play(video_info))
+}
+
+/// What the output of a transcode profile looks like, as far as playability goes
+fn profile_output_info(profile: &TranscodeProfile) -> VideoInfo {
+    let mime_type = match profile.container {
+        transcode::Container::Mp4 => "video/mp4",
+        transcode::Container::Webm => "video/webm",
+    };
+    let codec_rfc6381 = match profile.video_codec {
+        transcode::VideoCodec::H264 => "avc1",
+        transcode::VideoCodec::Vp9 => "vp09",
+        transcode::VideoCodec::Av1 => "av01",
+    };
+    let audio_codec = match profile.audio_codec {
+        transcode::AudioCodec::Aac => "aac",
+        transcode::AudioCodec::Opus => "opus",
+    };
+    VideoInfo {
+        mime_type: mime_type.to_string(),
+        codec_rfc6381: codec_rfc6381.to_string(),
+        height: profile.height,
+        pix_fmt: Some(profile.pixel_format.clone().unwrap_or_else(|| "yuv420p".to_string())),
+        audio_streams: vec![AudioStreamInfo {
+            codec: audio_codec.to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video_info(mime_type: &str, codec_rfc6381: &str, audio_codec: &str) -> VideoInfo {
+        VideoInfo {
+            mime_type: mime_type.to_string(),
+            codec_rfc6381: codec_rfc6381.to_string(),
+            pix_fmt: Some("yuv420p".to_string()),
+            audio_streams: vec![AudioStreamInfo {
+                codec: audio_codec.to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_user_agent() {
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+        let safari = "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_4) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15";
+        let chrome_ios = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) CriOS/124.0.6367.88 Mobile/15E148 Safari/604.1";
+        assert_eq!(Client::from_user_agent(chrome), Some(Client::Chrome));
+        assert_eq!(Client::from_user_agent(firefox), Some(Client::Firefox));
+        assert_eq!(Client::from_user_agent(safari), Some(Client::Safari));
+        assert_eq!(Client::from_user_agent(chrome_ios), Some(Client::Safari));
+        assert_eq!(Client::from_user_agent("curl/8.5.0"), None);
+
+        let hint = r#""Chromium";v="124", "Google Chrome";v="124", "Not-A.Brand";v="99""#;
+        assert_eq!(Client::from_client_hint(hint), Some(Client::Chrome));
+        assert_eq!(Client::from_client_hint(""), None);
+    }
+
+    #[test]
+    fn test_can_play() {
+        let h264_mp4 = video_info("video/mp4", "avc1", "aac");
+        assert!(all_can_play(&[Client::Chrome, Client::Firefox, Client::Safari], &h264_mp4));
+
+        let hevc_mp4 = video_info("video/mp4", "hvc1", "aac");
+        assert!(!Client::Chrome.can_play(&hevc_mp4));
+        assert!(!Client::Firefox.can_play(&hevc_mp4));
+        assert!(Client::Safari.can_play(&hevc_mp4));
+
+        let vp9_mkv = video_info("video/x-matroska", "vp09", "opus");
+        assert!(Client::Chrome.can_play(&vp9_mkv));
+        assert!(!Client::Firefox.can_play(&vp9_mkv));
+        assert!(!Client::Safari.can_play(&vp9_mkv));
+
+        let av1_webm = video_info("video/webm", "av01.0.08M.10", "opus");
+        assert!(Client::Firefox.can_play(&av1_webm));
+        assert!(!Client::Safari.can_play(&av1_webm));
+
+        let h264_10bit = VideoInfo {
+            pix_fmt: Some("yuv420p10le".to_string()),
+            ..video_info("video/mp4", "avc1", "aac")
+        };
+        assert!(!Client::Chrome.can_play(&h264_10bit));
+
+        let vorbis = video_info("video/webm", "vp09", "vorbis");
+        assert!(Client::Chrome.can_play(&vorbis));
+        assert!(!Client::Safari.can_play(&vorbis));
+        let dts = video_info("video/mp4", "avc1", "dts");
+        assert!(!Client::Chrome.can_play(&dts));
+    }
+
+    #[test]
+    fn test_can_play_profile() {
+        let av1 = TranscodeProfile::builtin("av1_400k").expect("builtin");
+        assert!(Client::Chrome.can_play_profile(&av1));
+        assert!(!Client::Safari.can_play_profile(&av1));
+
+        let h264 = TranscodeProfile::builtin("h264_800k").expect("builtin");
+        assert!(Client::Safari.can_play_profile(&h264));
+    }
+}
@@ -8,6 +8,8 @@ use serde::Deserialize;
 
 use crate::file_tree::{GeneratedFileType, GeneratedTree};
 
+pub mod language;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum SubtitleSource {
     WebVTTFile(PathBuf),
@@ -18,10 +20,104 @@ pub enum SubtitleSource {
 #[derive(Debug, Clone)]
 pub struct Subtitle {
     pub source: SubtitleSource,
-    pub lang_country: String,
+    /// Tells the video's subtitles apart, and names their converted WebVTT files,
+    /// e.g. "en-US", "ja_forced" or "und_2"
+    pub key: String,
+    /// BCP-47 tag, None if the language is unknown
+    pub language: Option<String>,
+    pub forced: bool,
+    pub hearing_impaired: bool,
+    /// Track title from the container, e.g. "Commentary"
+    pub title: Option<String>,
+    /// Image-based subtitles (PGS, VobSub, ...) can't be converted to WebVTT, only
+    /// burned into the video
+    pub is_bitmap: bool,
+}
+
+impl Subtitle {
+    /// Make a subtitle from a label like "en_US" (from a file name), "eng" (from
+    /// ffprobe), "en.forced" or "ja_sdh_2" (from a key).
+    fn from_label(source: SubtitleSource, label: &str) -> Subtitle {
+        let mut language_parts = Vec::new();
+        let (mut forced, mut hearing_impaired, mut index) = (false, false, 1);
+        for part in label.split(['.', '_']).filter(|part| !part.is_empty()) {
+            match part.to_lowercase().as_str() {
+                "forced" => forced = true,
+                "sdh" | "cc" | "hi" if !language_parts.is_empty() => hearing_impaired = true,
+                digits if digits.len() <= 2 && digits.chars().all(|c| c.is_ascii_digit()) => {
+                    index = digits.parse().unwrap_or(1);
+                }
+                _ => language_parts.push(part),
+            }
+        }
+
+        let mut subtitle = Subtitle {
+            source,
+            key: String::new(),
+            language: language::normalize(&language_parts.join("-")),
+            forced,
+            hearing_impaired,
+            title: None,
+            is_bitmap: false,
+        };
+        subtitle.key = subtitle.canonical_key(index);
+        subtitle
+    }
+
+    /// Key made from the language and flags. Never contains '.', which separates the
+    /// parts of generated file names.
+    fn canonical_key(&self, index: u32) -> String {
+        let mut key = self.language.clone().unwrap_or_else(|| "und".to_string());
+        if self.forced {
+            key.push_str("_forced");
+        }
+        if self.hearing_impaired {
+            key.push_str("_sdh");
+        }
+        if index > 1 {
+            key.push_str(&format!("_{}", index));
+        }
+        key
+    }
+
+    /// Label to show in the player's track menu, e.g. "English (US) [SDH]"
+    pub fn label(&self) -> String {
+        let mut label = match &self.language {
+            Some(tag) => language::display_name(tag),
+            None => "Unknown".to_string(),
+        };
+        if let Some(title) = &self.title {
+            label.push_str(&format!(" - {}", title));
+        }
+        if self.forced {
+            label.push_str(" [Forced]");
+        }
+        if self.hearing_impaired {
+            label.push_str(" [SDH]");
+        }
+        label
+    }
+}
+
+/// Label for a subtitle key, e.g. "ja_sdh" to "Japanese [SDH]"
+pub fn key_label(key: &str) -> String {
+    Subtitle::from_label(SubtitleSource::OtherFile(PathBuf::new()), key).label()
 }
 
-fn find_subtitle_files_same_dir(video_path: &Path) -> HashMap<String, Subtitle> {
+/// ffprobe codec names of image-based subtitle formats
+const BITMAP_CODECS: [&str; 4] = ["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub"];
+
+/// Give subtitles with the same language and flags distinct keys, in order
+fn number_duplicate_keys(subtitles: &mut [Subtitle]) {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for subtitle in subtitles {
+        let count = counts.entry(subtitle.canonical_key(1)).or_default();
+        *count += 1;
+        subtitle.key = subtitle.canonical_key(*count);
+    }
+}
+
+fn find_subtitle_files_same_dir(video_path: &Path) -> Vec<Subtitle> {
     // Look for subtitle files in the same directory, following the Facebook format.
     // https://www.facebook.com/help/1528795707381162
     let parent_dir = video_path.parent().expect("parent directory");
@@ -36,7 +132,7 @@ fn find_subtitle_files_same_dir(video_path: &Path) -> HashMap<String, Subtitle>
     video_prefix.push('.');
 
     fn find_files_internal<F>(
-        result: &mut HashMap<String, Subtitle>,
+        result: &mut Vec<Subtitle>,
         parent_dir_str: &str,
         video_prefix: &str,
         extension_suffix: &str,
@@ -51,29 +147,26 @@ fn find_subtitle_files_same_dir(video_path: &Path) -> HashMap<String, Subtitle>
             let path = entry.unwrap();
             let file_name = path.file_name().unwrap().to_str().unwrap();
             if let Some(without_video_name) = file_name.strip_prefix(&video_prefix) {
-                let lang_country = without_video_name
+                // e.g. "en_US", or "en.forced" for "video.en.forced.srt"
+                let label = without_video_name
                     .strip_suffix(extension_suffix)
                     .expect("strip_suffix")
                     .to_string();
-                result.insert(
-                    lang_country.clone(),
-                    Subtitle {
-                        source: (constructor)(path),
-                        lang_country,
-                    },
-                );
+                result.push(Subtitle::from_label((constructor)(path), &label));
             }
         }
     }
 
-    let mut result = HashMap::new();
-    find_files_internal(
-        &mut result,
-        parent_dir_str,
-        &video_prefix,
-        ".srt",
-        SubtitleSource::OtherFile,
-    );
+    let mut result = Vec::new();
+    for extension_suffix in [".srt", ".ass", ".ssa", ".sub"] {
+        find_files_internal(
+            &mut result,
+            parent_dir_str,
+            &video_prefix,
+            extension_suffix,
+            SubtitleSource::OtherFile,
+        );
+    }
     find_files_internal(
         &mut result,
         parent_dir_str,
@@ -82,20 +175,39 @@ fn find_subtitle_files_same_dir(video_path: &Path) -> HashMap<String, Subtitle>
         SubtitleSource::WebVTTFile,
     );
 
+    // .sub files are VobSub if they come with an .idx file, otherwise MicroDVD text
+    for subtitle in &mut result {
+        if let SubtitleSource::OtherFile(path) = &subtitle.source {
+            subtitle.is_bitmap = path.extension().is_some_and(|ext| ext == "sub") && path.with_extension("idx").exists();
+        }
+    }
+
+    number_duplicate_keys(&mut result);
     result
 }
 
-fn find_embedded_subtitles(video_path: &Path) -> HashMap<String, Subtitle> {
+fn find_embedded_subtitles(video_path: &Path) -> Vec<Subtitle> {
     #[derive(Deserialize)]
     struct FfprobeStream {
         index: i32,
         codec_type: String,
+        codec_name: Option<String>,
         tags: Option<FfprobeTags>,
+        #[serde(default)]
+        disposition: FfprobeDisposition,
     }
 
     #[derive(Deserialize)]
     struct FfprobeTags {
         language: Option<String>,
+        title: Option<String>,
+    }
+
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct FfprobeDisposition {
+        forced: i32,
+        hearing_impaired: i32,
     }
 
     #[derive(Deserialize)]
@@ -118,58 +230,89 @@ fn find_embedded_subtitles(video_path: &Path) -> HashMap<String, Subtitle> {
     let ffprobe_output: FfprobeOutput =
         serde_json::from_str(&stdout).expect("failed to parse ffprobe output");
 
-    let mut subtitles = HashMap::new();
+    let mut subtitles = Vec::new();
     for stream in ffprobe_output.streams {
         if stream.codec_type == "subtitle" {
-            let lang_country = stream
+            let (language, title) = stream
                 .tags
-                .and_then(|tags| tags.language)
-                .unwrap_or("unknown".to_string());
-            subtitles.insert(
-                lang_country.clone(),
-                Subtitle {
-                    source: SubtitleSource::Embedded {
-                        video_path: video_path.to_path_buf(),
-                        stream: stream.index,
-                    },
-                    lang_country,
+                .map(|tags| (tags.language, tags.title))
+                .unwrap_or_default();
+            let mut subtitle = Subtitle::from_label(
+                SubtitleSource::Embedded {
+                    video_path: video_path.to_path_buf(),
+                    stream: stream.index,
                 },
+                language.as_deref().unwrap_or("und"),
             );
+            subtitle.forced |= stream.disposition.forced != 0;
+            subtitle.hearing_impaired |= stream.disposition.hearing_impaired != 0;
+            subtitle.title = title;
+            subtitle.is_bitmap = stream
+                .codec_name
+                .is_some_and(|codec_name| BITMAP_CODECS.contains(&codec_name.as_str()));
+            subtitles.push(subtitle);
         }
     }
 
+    number_duplicate_keys(&mut subtitles);
     subtitles
 }
 
-fn find_generated_subtitles(gen_tree: &GeneratedTree, entry_id: i64) -> HashMap<String, Subtitle> {
-    let mut result = HashMap::new();
+fn find_generated_subtitles(gen_tree: &GeneratedTree, entry_id: i64) -> Vec<Subtitle> {
+    // Keyed by canonical key, since files converted before keys were normalized
+    // (e.g. "eng") may sit next to their re-converted copies (e.g. "en")
+    let mut result: HashMap<String, Subtitle> = HashMap::new();
     for gfile in gen_tree.query_generated_files(entry_id, GeneratedFileType::Subtitle) {
-        let lang_country = gfile.metadata.clone();
-        result.insert(
-            lang_country.clone(),
-            Subtitle {
-                source: SubtitleSource::WebVTTFile(gen_tree.path_to_generated_file(&gfile)),
-                lang_country,
-            },
+        let mut subtitle = Subtitle::from_label(
+            SubtitleSource::WebVTTFile(gen_tree.path_to_generated_file(&gfile)),
+            &gfile.metadata,
         );
+        let canonical_key = std::mem::replace(&mut subtitle.key, gfile.metadata);
+        let is_canonical = subtitle.key == canonical_key;
+        if is_canonical || !result.contains_key(&canonical_key) {
+            result.insert(canonical_key, subtitle);
+        }
     }
+
+    let mut result: Vec<Subtitle> = result.into_values().collect();
+    result.sort_by(|a, b| a.key.cmp(&b.key));
     result
 }
 
-pub fn find_non_vtt_subtitles(video_path: &Path) -> HashMap<String, Subtitle> {
-    // Find subtitle files (and embedded streams) that have not been converted to WebVTT
+/// Subtitle files (and embedded streams) that aren't in WebVTT format
+fn find_source_subtitles(video_path: &Path) -> Vec<Subtitle> {
     let mut subtitles = find_embedded_subtitles(video_path);
     subtitles.extend(find_subtitle_files_same_dir(video_path));
 
     // Remove subtitles that are already in WebVTT format
-    subtitles.retain(|_, subtitle| match &subtitle.source {
-        SubtitleSource::WebVTTFile(_) => false,
-        _ => true,
-    });
+    subtitles.retain(|subtitle| !matches!(subtitle.source, SubtitleSource::WebVTTFile(_)));
 
+    number_duplicate_keys(&mut subtitles);
     subtitles
 }
 
+pub fn find_non_vtt_subtitles(video_path: &Path) -> Vec<Subtitle> {
+    // Find text subtitles that haven't been converted to WebVTT
+    let mut subtitles = find_source_subtitles(video_path);
+    subtitles.retain(|subtitle| !subtitle.is_bitmap);
+    subtitles
+}
+
+/// The bitmap subtitle to burn into an alt format, for videos that don't have any
+/// text subtitles. Picked by preferred language, otherwise the first one.
+pub fn find_burn_in_subtitle(video_path: &Path, preferred_languages: &[String]) -> Option<Subtitle> {
+    let has_vtt_file = find_subtitle_files_same_dir(video_path)
+        .iter()
+        .any(|subtitle| matches!(subtitle.source, SubtitleSource::WebVTTFile(_)));
+    let subtitles = find_source_subtitles(video_path);
+    if has_vtt_file || subtitles.iter().any(|subtitle| !subtitle.is_bitmap) {
+        return None;
+    }
+
+    let index = default_subtitle(&subtitles, preferred_languages).unwrap_or(0);
+    subtitles.into_iter().nth(index)
+}
+
 pub fn convert_to_vtt(source: &Subtitle, dest_vtt_path: &Path) {
     let mut command = Command::new("ffmpeg");
 
@@ -179,6 +322,9 @@ pub fn convert_to_vtt(source: &Subtitle, dest_vtt_path: &Path) {
             // This is already in WebVTT format, so this function was probably called in error
             panic!("convert_one_subtitle called with WebVTT source: {:?}", path);
         }
+        _ if source.is_bitmap => {
+            panic!("convert_to_vtt called with bitmap subtitles: {:?}", source);
+        }
         SubtitleSource::OtherFile(path) => {
             command.arg("-i").arg(path);
         }
@@ -213,12 +359,28 @@ pub fn find_all_vtt_subtitles(
     entry_id: i64,
 ) -> Vec<Subtitle> {
     let mut subtitles = find_subtitle_files_same_dir(video_path);
+    subtitles.retain(|subtitle| matches!(subtitle.source, SubtitleSource::WebVTTFile(_)));
     subtitles.extend(find_generated_subtitles(gen_tree, entry_id));
-    subtitles.retain(|_, subtitle| match &subtitle.source {
-        SubtitleSource::WebVTTFile(_) => true,
-        _ => false,
-    });
-    subtitles.values().cloned().collect()
+    subtitles
+}
+
+/// Index of the subtitle to show by default: the best match for the first preferred
+/// language that has one, preferring regular over SDH over forced subtitles.
+pub fn default_subtitle(subtitles: &[Subtitle], preferred_languages: &[String]) -> Option<usize> {
+    preferred_languages.iter().find_map(|preferred| {
+        let preferred = language::normalize(preferred)?;
+        subtitles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, subtitle)| {
+                let quality = language::match_quality(subtitle.language.as_deref()?, &preferred);
+                (quality > 0).then_some((i, quality, subtitle))
+            })
+            .max_by_key(|(i, quality, subtitle)| {
+                (*quality, !subtitle.forced, !subtitle.hearing_impaired, std::cmp::Reverse(*i))
+            })
+            .map(|(i, _, _)| i)
+    })
 }
 
 #[cfg(test)]
@@ -226,6 +388,85 @@ mod tests {
     use super::*;
     use crate::testing::testdata_path;
 
+    fn find_key<'a>(subtitles: &'a [Subtitle], key: &str) -> Option<&'a Subtitle> {
+        subtitles.iter().find(|subtitle| subtitle.key == key)
+    }
+
+    #[test]
+    fn test_from_label() {
+        let source = SubtitleSource::OtherFile(PathBuf::from("video.srt"));
+        let subtitle = Subtitle::from_label(source.clone(), "zh_CN");
+        assert_eq!(subtitle.language.as_deref(), Some("zh-CN"));
+        assert_eq!(subtitle.key, "zh-CN");
+
+        let subtitle = Subtitle::from_label(source.clone(), "eng.forced");
+        assert_eq!(subtitle.language.as_deref(), Some("en"));
+        assert!(subtitle.forced);
+        assert_eq!(subtitle.key, "en_forced");
+        assert_eq!(subtitle.label(), "English [Forced]");
+
+        // Keys parse back to themselves
+        let subtitle = Subtitle::from_label(source.clone(), "ja_sdh_2");
+        assert!(subtitle.hearing_impaired);
+        assert_eq!(subtitle.key, "ja_sdh_2");
+
+        let subtitle = Subtitle::from_label(source, "unknown");
+        assert_eq!(subtitle.language, None);
+        assert_eq!(subtitle.key, "und");
+        assert_eq!(subtitle.label(), "Unknown");
+    }
+
+    #[test]
+    fn test_number_duplicate_keys() {
+        let source = SubtitleSource::OtherFile(PathBuf::from("video.srt"));
+        let mut subtitles: Vec<Subtitle> = ["eng", "en", "eng.sdh", "jpn"]
+            .iter()
+            .map(|label| Subtitle::from_label(source.clone(), label))
+            .collect();
+        number_duplicate_keys(&mut subtitles);
+
+        let keys: Vec<&str> = subtitles.iter().map(|subtitle| subtitle.key.as_str()).collect();
+        assert_eq!(keys, vec!["en", "en_2", "en_sdh", "ja"]);
+    }
+
+    #[test]
+    fn test_default_subtitle() {
+        let source = SubtitleSource::OtherFile(PathBuf::from("video.srt"));
+        let subtitles: Vec<Subtitle> = ["ja", "en_forced", "en-GB_sdh", "en-GB", "fr"]
+            .iter()
+            .map(|label| Subtitle::from_label(source.clone(), label))
+            .collect();
+        let preferred = |languages: &[&str]| -> Vec<String> {
+            languages.iter().map(|language| language.to_string()).collect()
+        };
+
+        assert_eq!(default_subtitle(&subtitles, &preferred(&[])), None);
+        assert_eq!(default_subtitle(&subtitles, &preferred(&["de"])), None);
+        assert_eq!(default_subtitle(&subtitles, &preferred(&["de", "fra"])), Some(4));
+        // Regular subtitles win over SDH and forced ones
+        assert_eq!(default_subtitle(&subtitles, &preferred(&["en-GB"])), Some(3));
+        assert_eq!(default_subtitle(&subtitles, &preferred(&["en_US"])), Some(3));
+    }
+
+    #[test]
+    fn test_find_generated_subtitles_prefers_canonical_keys() {
+        let tempdir = tempfile::tempdir().expect("tempdir");
+        let gen_tree = GeneratedTree::new(tempdir.path());
+        for metadata in ["eng", "en", "jpn"] {
+            let gfile = crate::file_tree::GeneratedFile {
+                entry_id: 1,
+                file_type: GeneratedFileType::Subtitle,
+                metadata: metadata.to_string(),
+                extension: "vtt".to_string(),
+            };
+            std::fs::write(gen_tree.path_to_generated_file(&gfile), "WEBVTT").expect("write");
+        }
+
+        let subtitles = find_generated_subtitles(&gen_tree, 1);
+        let keys: Vec<&str> = subtitles.iter().map(|subtitle| subtitle.key.as_str()).collect();
+        assert_eq!(keys, vec!["en", "jpn"]);
+    }
+
     #[test]
     fn test_find_subtitle_files() {
         let root = testdata_path("subtitle");
@@ -233,19 +474,38 @@ mod tests {
         let files = find_subtitle_files_same_dir(&video_path);
         assert_eq!(files.len(), 2);
 
-        let zh_cn_subtitle = files.get("zh_CN").expect("zh_CN subtitle");
+        let zh_cn_subtitle = find_key(&files, "zh-CN").expect("zh-CN subtitle");
         assert_eq!(
             zh_cn_subtitle.source,
             SubtitleSource::OtherFile(root.join("art001m1203451716~small_10s.zh_CN.srt"))
         );
 
-        let fr_fr_subtitle = files.get("fr_FR").expect("fr_FR subtitle");
+        let fr_fr_subtitle = find_key(&files, "fr-FR").expect("fr-FR subtitle");
         assert_eq!(
             fr_fr_subtitle.source,
             SubtitleSource::WebVTTFile(root.join("art001m1203451716~small_10s.fr_FR.vtt"))
         );
     }
 
+    #[test]
+    fn test_find_subtitle_files_formats() -> crate::testing::TestResult {
+        let tempdir = tempfile::tempdir()?;
+        for name in ["video.mkv", "video.en.ass", "video.ja.ssa", "video.de.sub", "video.fr.sub", "video.fr.idx"] {
+            std::fs::write(tempdir.path().join(name), "")?;
+        }
+
+        let files = find_subtitle_files_same_dir(&tempdir.path().join("video.mkv"));
+        assert_eq!(files.len(), 4);
+        for key in ["en", "ja", "de"] {
+            let subtitle = find_key(&files, key).expect(key);
+            assert!(!subtitle.is_bitmap, "{} is text", key);
+        }
+        // VobSub
+        assert!(find_key(&files, "fr").expect("fr").is_bitmap);
+
+        Ok(())
+    }
+
     #[test]
     fn test_find_embedded_subtitles() {
         let root = testdata_path("subtitle");
@@ -253,7 +513,7 @@ mod tests {
         let subtitles = find_embedded_subtitles(&video_path);
         assert_eq!(subtitles.len(), 2);
 
-        let eng_subtitle = subtitles.get("eng").expect("eng subtitle");
+        let eng_subtitle = find_key(&subtitles, "en").expect("en subtitle");
         assert_eq!(
             eng_subtitle.source,
             SubtitleSource::Embedded {
@@ -262,7 +522,7 @@ mod tests {
             }
         );
 
-        let jpn_subtitle = subtitles.get("jpn").expect("jpn subtitle");
+        let jpn_subtitle = find_key(&subtitles, "ja").expect("ja subtitle");
         assert_eq!(
             jpn_subtitle.source,
             SubtitleSource::Embedded {
@@ -284,16 +544,45 @@ mod tests {
         std::fs::remove_file(dest_file.path()).expect("remove temp file");
         let dest_vtt_path = dest_file.path();
 
-        let subtitle = Subtitle {
-            source: SubtitleSource::OtherFile(source_path),
-            lang_country: "zh_CN".to_string(),
-        };
+        let subtitle = Subtitle::from_label(SubtitleSource::OtherFile(source_path), "zh_CN");
 
         convert_to_vtt(&subtitle, &dest_vtt_path);
 
         assert!(dest_vtt_path.exists());
     }
 
+    #[test]
+    fn test_convert_to_vtt_ass() -> crate::testing::TestResult {
+        let tempdir = tempfile::tempdir()?;
+        let source_path = tempdir.path().join("video.en.ass");
+        std::fs::write(
+            &source_path,
+            "[Script Info]\n\
+            ScriptType: v4.00+\n\
+            \n\
+            [V4+ Styles]\n\
+            Format: Name, Fontname, Fontsize, PrimaryColour, Bold, Italic, Alignment\n\
+            Style: Default,Arial,20,&H00FFFFFF,0,0,2\n\
+            \n\
+            [Events]\n\
+            Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,Hello from ASS\n",
+        )?;
+
+        let subtitles = find_subtitle_files_same_dir(&tempdir.path().join("video.mkv"));
+        assert_eq!(subtitles.len(), 1);
+        assert!(!subtitles[0].is_bitmap);
+
+        let dest_vtt_path = tempdir.path().join("out.vtt");
+        convert_to_vtt(&subtitles[0], &dest_vtt_path);
+
+        let vtt = std::fs::read_to_string(&dest_vtt_path)?;
+        assert!(vtt.starts_with("WEBVTT"));
+        assert!(vtt.contains("-->"));
+        assert!(vtt.contains("Hello from ASS"));
+        Ok(())
+    }
+
     #[test]
     fn test_convert_to_vtt_embedded() {
         let root = testdata_path("subtitle");
@@ -306,13 +595,13 @@ mod tests {
         std::fs::remove_file(dest_file.path()).expect("remove temp file");
         let dest_vtt_path = dest_file.path();
 
-        let subtitle = Subtitle {
-            source: SubtitleSource::Embedded {
+        let subtitle = Subtitle::from_label(
+            SubtitleSource::Embedded {
                 video_path: video_path.clone(),
                 stream: 2,
             },
-            lang_country: "eng".to_string(),
-        };
+            "eng",
+        );
 
         convert_to_vtt(&subtitle, &dest_vtt_path);
 
@@ -0,0 +1,138 @@
+//! BCP-47 language tags for subtitles, which come labelled in all sorts of ways:
+//! "en_US" from file names, "eng" from ffprobe, or just "English".
+
+/// (BCP-47 primary tag, other names for the language, display name)
+const LANGUAGES: [(&str, &[&str], &str); 34] = [
+    ("ar", &["ara", "arabic"], "Arabic"),
+    ("cs", &["cze", "ces", "czech"], "Czech"),
+    ("da", &["dan", "danish"], "Danish"),
+    ("de", &["ger", "deu", "german"], "German"),
+    ("el", &["gre", "ell", "greek"], "Greek"),
+    ("en", &["eng", "english"], "English"),
+    ("es", &["spa", "spanish"], "Spanish"),
+    ("fa", &["per", "fas", "persian"], "Persian"),
+    ("fi", &["fin", "finnish"], "Finnish"),
+    ("fr", &["fre", "fra", "french"], "French"),
+    ("he", &["heb", "hebrew", "iw"], "Hebrew"),
+    ("hi", &["hin", "hindi"], "Hindi"),
+    ("hu", &["hun", "hungarian"], "Hungarian"),
+    ("id", &["ind", "indonesian", "in"], "Indonesian"),
+    ("it", &["ita", "italian"], "Italian"),
+    ("ja", &["jpn", "japanese", "jp"], "Japanese"),
+    ("ko", &["kor", "korean"], "Korean"),
+    ("ms", &["may", "msa", "malay"], "Malay"),
+    ("nb", &["nob", "bokmal"], "Norwegian Bokmål"),
+    ("nl", &["dut", "nld", "dutch"], "Dutch"),
+    ("no", &["nor", "norwegian"], "Norwegian"),
+    ("pl", &["pol", "polish"], "Polish"),
+    ("pt", &["por", "portuguese"], "Portuguese"),
+    ("ro", &["rum", "ron", "romanian"], "Romanian"),
+    ("ru", &["rus", "russian"], "Russian"),
+    ("sv", &["swe", "swedish"], "Swedish"),
+    ("ta", &["tam", "tamil"], "Tamil"),
+    ("th", &["tha", "thai"], "Thai"),
+    ("tl", &["tgl", "tagalog", "fil", "filipino"], "Filipino"),
+    ("tr", &["tur", "turkish"], "Turkish"),
+    ("uk", &["ukr", "ukrainian"], "Ukrainian"),
+    ("vi", &["vie", "vietnamese"], "Vietnamese"),
+    ("yue", &["cantonese"], "Cantonese"),
+    ("zh", &["chi", "zho", "chinese"], "Chinese"),
+];
+
+/// Normalize a language label to a BCP-47 tag, e.g. "en_us" to "en-US", "eng" to
+/// "en" and "zh-hans" to "zh-Hans". None for unknown languages ("und", "unknown",
+/// or anything that doesn't look like a language).
+pub fn normalize(label: &str) -> Option<String> {
+    let label = label.trim().to_lowercase();
+    let mut parts = label.split(['_', '-']).filter(|part| !part.is_empty());
+    let primary = parts.next()?;
+    let primary = LANGUAGES
+        .iter()
+        .find(|(tag, aliases, _)| *tag == primary || aliases.contains(&primary))
+        .map(|(tag, _, _)| tag.to_string())
+        .or_else(|| {
+            // Other ISO 639 codes are passed through as-is
+            let is_code = (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_lowercase());
+            (is_code && primary != "und").then(|| primary.to_string())
+        })?;
+
+    let mut tag = primary;
+    for subtag in parts {
+        tag.push('-');
+        match subtag.len() {
+            // Region, e.g. "US"
+            2 => tag.push_str(&subtag.to_uppercase()),
+            // Script, e.g. "Hans"
+            4 => {
+                let mut chars = subtag.chars();
+                tag.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                tag.push_str(chars.as_str());
+            }
+            _ => tag.push_str(subtag),
+        }
+    }
+    Some(tag)
+}
+
+/// Human-readable name for a BCP-47 tag, e.g. "English (US)"
+pub fn display_name(tag: &str) -> String {
+    let (primary, rest) = tag.split_once('-').unwrap_or((tag, ""));
+    let name = LANGUAGES
+        .iter()
+        .find(|(language, _, _)| *language == primary)
+        .map_or(primary, |(_, _, name)| name);
+    if rest.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} ({})", name, rest)
+    }
+}
+
+/// How well a tag matches a preferred language: 2 for the same tag, 1 for the same
+/// language in another region or script (or no region given), 0 otherwise.
+pub fn match_quality(tag: &str, preferred: &str) -> u8 {
+    let primary = |tag: &str| tag.split('-').next().unwrap_or("").to_lowercase();
+    if tag.eq_ignore_ascii_case(preferred) {
+        2
+    } else if primary(tag) == primary(preferred) {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("en_US").as_deref(), Some("en-US"));
+        assert_eq!(normalize("eng").as_deref(), Some("en"));
+        assert_eq!(normalize("English").as_deref(), Some("en"));
+        assert_eq!(normalize("jpn").as_deref(), Some("ja"));
+        assert_eq!(normalize("zh-hans").as_deref(), Some("zh-Hans"));
+        assert_eq!(normalize("pt_br").as_deref(), Some("pt-BR"));
+        assert_eq!(normalize("es-419").as_deref(), Some("es-419"));
+        assert_eq!(normalize("gsw").as_deref(), Some("gsw"));
+
+        assert_eq!(normalize("unknown"), None);
+        assert_eq!(normalize("und"), None);
+        assert_eq!(normalize(""), None);
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("en-US"), "English (US)");
+        assert_eq!(display_name("ja"), "Japanese");
+        assert_eq!(display_name("gsw"), "gsw");
+    }
+
+    #[test]
+    fn test_match_quality() {
+        assert_eq!(match_quality("en-US", "en-US"), 2);
+        assert_eq!(match_quality("en-GB", "en-US"), 1);
+        assert_eq!(match_quality("en", "en-US"), 1);
+        assert_eq!(match_quality("fr", "en"), 0);
+    }
+}
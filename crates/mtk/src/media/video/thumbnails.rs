@@ -0,0 +1,241 @@
+//! Seek-bar thumbnails: frames every few seconds tiled into one sprite image, plus a
+//! WebVTT "thumbnails" track that maps each time range to a region of the sprite.
+
+use std::path::Path;
+
+use crate::file_tree::{GeneratedFile, GeneratedFileType};
+
+use super::VideoInfo;
+
+/// Only videos at least this long get thumbnails (shorter ones loop and autoplay instead)
+pub const THUMBNAILS_MIN_DURATION_SECS: f64 = 180.0;
+
+/// Take a frame at least this often...
+const DEFAULT_INTERVAL_SECS: f64 = 10.0;
+/// ...but no more than this many frames in total, so long videos don't get giant sprites
+const MAX_TILES: usize = 200;
+const TILE_COLUMNS: usize = 10;
+const TILE_WIDTH: u32 = 160;
+
+pub fn sprite_gfile(entry_id: i64) -> GeneratedFile {
+    GeneratedFile {
+        entry_id,
+        file_type: GeneratedFileType::Thumbnails,
+        metadata: "sprite".to_string(),
+        extension: "jpg".to_string(),
+    }
+}
+
+pub fn thumbnails_vtt_gfile(entry_id: i64) -> GeneratedFile {
+    GeneratedFile {
+        entry_id,
+        file_type: GeneratedFileType::Thumbnails,
+        metadata: "sprite".to_string(),
+        extension: "vtt".to_string(),
+    }
+}
+
+/// How frames are laid out in a sprite sheet.
+#[derive(Debug, PartialEq)]
+pub struct SpriteLayout {
+    pub interval_secs: f64,
+    pub num_tiles: usize,
+    pub columns: usize,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+impl SpriteLayout {
+    pub fn for_video(video_info: &VideoInfo) -> SpriteLayout {
+        let interval_secs = DEFAULT_INTERVAL_SECS.max((video_info.duration_secs / MAX_TILES as f64).ceil());
+        let num_tiles = ((video_info.duration_secs / interval_secs).ceil() as usize).max(1);
+
+        // Keep the displayed aspect ratio, rounding to an even height for the encoder
+        let (width, height) = video_info.display_dimensions();
+        let tile_height = if width > 0 && height > 0 {
+            let height = TILE_WIDTH as f64 * height as f64 / width as f64;
+            ((height / 2.0).round() as u32 * 2).max(2)
+        } else {
+            TILE_WIDTH * 9 / 16
+        };
+
+        SpriteLayout {
+            interval_secs,
+            num_tiles,
+            columns: std::cmp::min(num_tiles, TILE_COLUMNS),
+            tile_width: TILE_WIDTH,
+            tile_height,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.num_tiles.div_ceil(self.columns)
+    }
+
+    /// Make the WebVTT track. Each cue's text is `<sprite_url>#xywh=x,y,w,h`.
+    pub fn to_webvtt(&self, duration_secs: f64, sprite_url: &str) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for i in 0..self.num_tiles {
+            let start = i as f64 * self.interval_secs;
+            let end = ((i + 1) as f64 * self.interval_secs).min(duration_secs);
+            let x = (i % self.columns) as u32 * self.tile_width;
+            let y = (i / self.columns) as u32 * self.tile_height;
+            vtt += &format!(
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                format_vtt_timestamp(start),
+                format_vtt_timestamp(end),
+                sprite_url,
+                x,
+                y,
+                self.tile_width,
+                self.tile_height,
+            );
+        }
+        vtt
+    }
+}
+
+fn format_vtt_timestamp(secs: f64) -> String {
+    let millis = (secs * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000,
+    )
+}
+
+/// Tile frames from the video into a sprite sheet using ffmpeg.
+pub fn make_sprite_sheet(
+    video_path: &Path,
+    layout: &SpriteLayout,
+    out_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(video_path)
+        .arg("-vf")
+        .arg(format!(
+            "fps=1/{},scale={}:{},tile={}x{}",
+            layout.interval_secs,
+            layout.tile_width,
+            layout.tile_height,
+            layout.columns,
+            layout.rows(),
+        ))
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-q:v")
+        .arg("5")
+        .arg(out_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_sprite_sheet failed for {:?}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    Ok(())
+}
+
+/// Make the sprite sheet and its WebVTT track. The track is written last, so
+/// if it exists the sprite sheet is complete.
+pub fn make_thumbnails(
+    video_path: &Path,
+    video_info: &VideoInfo,
+    sprite_path: &Path,
+    vtt_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let layout = SpriteLayout::for_video(video_info);
+    make_sprite_sheet(video_path, &layout, sprite_path)?;
+
+    // Both files are served from the same directory (/generated/<id>/TH/sprite/<ext>),
+    // so the sprite can be referenced relative to the track
+    std::fs::write(vtt_path, layout.to_webvtt(video_info.duration_secs, "jpg"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    fn video_info(duration_secs: f64, width: u32, height: u32) -> VideoInfo {
+        VideoInfo {
+            mime_type: "video/mp4".to_string(),
+            codec: "h264".to_string(),
+            codec_rfc6381: "avc1".to_string(),
+            duration_secs,
+            width,
+            height,
+            bitrate: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sprite_layout() {
+        let layout = SpriteLayout::for_video(&video_info(600.0, 1920, 1080));
+        assert_eq!(layout, SpriteLayout {
+            interval_secs: 10.0,
+            num_tiles: 60,
+            columns: 10,
+            tile_width: 160,
+            tile_height: 90,
+        });
+        assert_eq!(layout.rows(), 6);
+
+        // Long videos take frames less often
+        let long = SpriteLayout::for_video(&video_info(3.0 * 3600.0, 1280, 720));
+        assert_eq!(long.interval_secs, 54.0);
+        assert_eq!(long.num_tiles, MAX_TILES);
+
+        // Portrait, and unknown dimensions
+        assert_eq!(SpriteLayout::for_video(&video_info(200.0, 1080, 1920)).tile_height, 284);
+        assert_eq!(SpriteLayout::for_video(&video_info(200.0, 0, 0)).tile_height, 90);
+
+        // Phone videos stored landscape with a rotation are portrait once displayed
+        for rotation in [90, 270] {
+            let rotated = VideoInfo { rotation, ..video_info(200.0, 1920, 1080) };
+            assert_eq!(SpriteLayout::for_video(&rotated).tile_height, 284);
+        }
+        let upside_down = VideoInfo { rotation: 180, ..video_info(200.0, 1920, 1080) };
+        assert_eq!(SpriteLayout::for_video(&upside_down).tile_height, 90);
+
+        let short = SpriteLayout::for_video(&video_info(25.0, 640, 480));
+        assert_eq!((short.num_tiles, short.columns, short.rows()), (3, 3, 1));
+    }
+
+    #[test]
+    fn test_to_webvtt() {
+        let layout = SpriteLayout::for_video(&video_info(25.0, 640, 480));
+        assert_eq!(
+            layout.to_webvtt(25.0, "jpg"),
+            "WEBVTT\n\
+            \n00:00:00.000 --> 00:00:10.000\njpg#xywh=0,0,160,120\n\
+            \n00:00:10.000 --> 00:00:20.000\njpg#xywh=160,0,160,120\n\
+            \n00:00:20.000 --> 00:00:25.000\njpg#xywh=320,0,160,120\n"
+        );
+
+        assert_eq!(format_vtt_timestamp(3723.5), "01:02:03.500");
+    }
+
+    #[test]
+    fn test_make_thumbnails() {
+        let video_path = testing::testdata_path("preview").join("short_video.mp4");
+        let tempdir = tempfile::tempdir().expect("tempdir");
+        let sprite_path = tempdir.path().join("sprite.jpg");
+        let vtt_path = tempdir.path().join("sprite.vtt");
+
+        let info = super::super::get_video_info(&video_path).expect("get_video_info");
+        make_thumbnails(&video_path, &info, &sprite_path, &vtt_path).expect("make_thumbnails");
+        assert!(std::fs::metadata(&sprite_path).expect("metadata").len() > 0);
+        assert!(std::fs::read_to_string(&vtt_path).expect("read vtt").starts_with("WEBVTT\n"));
+    }
+}
@@ -1,119 +1,326 @@
 use std::path::Path;
 
+use serde::Deserialize;
+
 use crate::file_tree::GeneratedFile;
 
-use super::VideoInfo;
+use super::{Subtitle, SubtitleSource};
 use crate::media::ffmpeg;
 
-#[derive(Copy, Clone, Debug)]
-pub enum TranscodeProfile {
-    H264_200K,
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    fn encoder(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1 => "libsvtav1",
+        }
+    }
+
+    fn max_crf(&self) -> u32 {
+        match self {
+            VideoCodec::H264 => 51,
+            VideoCodec::Vp9 | VideoCodec::Av1 => 63,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    fn encoder(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+        }
+    }
+}
 
-    AV1_200K,
-    AV1_400K,
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Mp4,
+    Webm,
 }
 
+impl Container {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+        }
+    }
+}
+
+/// How to transcode a video into an alt format. The built-in profiles can be
+/// overridden, and new ones added, in `FilerConfig::transcode_profiles`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TranscodeProfile {
+    /// Names the generated files. Filled in from the profile's key in the config.
+    #[serde(skip)]
+    pub name: String,
+    pub video_codec: VideoCodec,
+    /// Videos are scaled down to this height, keeping aspect ratio, but never up
+    pub height: u32,
+    /// Target video bitrate, or...
+    pub video_bitrate_kbps: Option<u32>,
+    /// ...constant quality, lower is better
+    pub crf: Option<u32>,
+    /// Encoder preset, e.g. "slower" for x264 or "5" for SVT-AV1
+    pub preset: Option<String>,
+    /// e.g. "yuv420p10le"
+    pub pixel_format: Option<String>,
+    /// Frames between keyframes
+    pub keyframe_interval: Option<u32>,
+    /// Passed to the encoder as -x264-params or -svtav1-params, e.g. "film-grain=8"
+    pub encoder_params: Option<String>,
+    pub audio_codec: AudioCodec,
+    pub audio_bitrate_kbps: u32,
+    pub container: Container,
+    #[serde(default)]
+    pub two_pass: bool,
+}
+
+pub const BUILTIN_PROFILE_NAMES: [&str; 4] = ["h264_200k", "h264_800k", "av1_200k", "av1_400k"];
+
 impl TranscodeProfile {
-    pub fn from_str(profile_str: &str) -> Option<TranscodeProfile> {
-        match profile_str {
-            "h264_200k" => Some(TranscodeProfile::H264_200K),
-            "av1_200k" => Some(TranscodeProfile::AV1_200K),
-            "av1_400k" => Some(TranscodeProfile::AV1_400K),
+    pub fn builtin(name: &str) -> Option<TranscodeProfile> {
+        let h264 = |height, video_bitrate_kbps, audio_bitrate_kbps| TranscodeProfile {
+            name: name.to_string(),
+            video_codec: VideoCodec::H264,
+            height,
+            video_bitrate_kbps: Some(video_bitrate_kbps),
+            crf: None,
+            preset: Some("slower".to_string()),
+            pixel_format: None,
+            keyframe_interval: None,
+            encoder_params: None,
+            audio_codec: AudioCodec::Aac,
+            audio_bitrate_kbps,
+            container: Container::Mp4,
+            two_pass: false,
+        };
+        // See SVT-AV1 docs for recommendations:
+        // https://gitlab.com/AOMediaCodec/SVT-AV1/-/blob/master/Docs/Ffmpeg.md#example-2-encoding-for-personal-use
+        let av1 = |height, video_bitrate_kbps, audio_bitrate_kbps, film_grain| TranscodeProfile {
+            name: name.to_string(),
+            video_codec: VideoCodec::Av1,
+            height,
+            video_bitrate_kbps: Some(video_bitrate_kbps),
+            crf: None,
+            preset: Some("5".to_string()),
+            pixel_format: Some("yuv420p10le".to_string()), // 10-bit improves encode quality
+            keyframe_interval: Some(300),
+            encoder_params: Some(format!("film-grain={}", film_grain)),
+            audio_codec: AudioCodec::Opus,
+            audio_bitrate_kbps,
+            container: Container::Webm,
+            two_pass: false, // SVT-AV1 doesn't have 2pass?
+        };
+
+        match name {
+            "h264_200k" => Some(h264(360, 200, 32)),
+            "h264_800k" => Some(h264(720, 800, 64)),
+            "av1_200k" => Some(av1(480, 200, 32, 8)),
+            "av1_400k" => Some(av1(720, 400, 48, 4)),
             _ => None,
         }
     }
 
+    /// Check that the profile's settings make sense together
+    pub fn validate(&self) -> Result<(), String> {
+        let err = |message: &str| Err(format!("transcode profile {:?}: {}", self.name, message));
+
+        let name_ok = !self.name.is_empty()
+            && self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            && !self.name.contains(BURNED_IN_SEPARATOR);
+        if !name_ok {
+            return err("names can only have letters, digits, '_' and '-', and can't contain \"_sub_\"");
+        }
+        if self.height == 0 || !self.height.is_multiple_of(2) {
+            return err("height must be even");
+        }
+        match (self.video_bitrate_kbps, self.crf) {
+            (Some(_), Some(_)) | (None, None) => return err("needs either video_bitrate_kbps or crf"),
+            (None, Some(crf)) if crf > self.video_codec.max_crf() => return err("crf is out of range"),
+            (None, Some(_)) if self.two_pass => return err("two_pass needs video_bitrate_kbps"),
+            _ => {}
+        }
+        if self.encoder_params.is_some() && self.video_codec == VideoCodec::Vp9 {
+            return err("encoder_params isn't supported for vp9");
+        }
+        if self.container == Container::Webm
+            && (self.video_codec == VideoCodec::H264 || self.audio_codec == AudioCodec::Aac)
+        {
+            return err("webm needs vp9 or av1 video and opus audio");
+        }
+        Ok(())
+    }
+
+    /// Total video and audio bitrate, in bits per second. None for constant quality profiles.
+    pub fn bandwidth_bps(&self) -> Option<u64> {
+        Some((self.video_bitrate_kbps? as u64 + self.audio_bitrate_kbps as u64) * 1000)
+    }
+
     pub fn to_gen_file(&self, entry_id: i64) -> GeneratedFile {
-        let metadata = match self {
-            TranscodeProfile::H264_200K => "h264_200k".to_string(),
-            TranscodeProfile::AV1_200K => "av1_200k".to_string(),
-            TranscodeProfile::AV1_400K => "av1_400k".to_string(),
-        };
-        let extension = match self {
-            TranscodeProfile::H264_200K => "mp4".to_string(),
-            TranscodeProfile::AV1_200K => "webm".to_string(),
-            TranscodeProfile::AV1_400K => "webm".to_string(),
-        };
         GeneratedFile {
             entry_id,
             file_type: crate::file_tree::GeneratedFileType::AltFormat,
-            metadata,
-            extension,
+            metadata: self.name.clone(),
+            extension: self.container.extension().to_string(),
         }
     }
 
-    fn two_pass(&self) -> bool {
-        match self {
-            TranscodeProfile::H264_200K => false,
-            TranscodeProfile::AV1_200K => false, // SVT-AV1 doesn't have 2pass?
-            TranscodeProfile::AV1_400K => false, // SVT-AV1 doesn't have 2pass?
-        }
+    /// Like to_gen_file, for an alt format with the given subtitles burned in
+    pub fn to_burned_in_gen_file(&self, entry_id: i64, subtitle_key: &str) -> GeneratedFile {
+        let mut gfile = self.to_gen_file(entry_id);
+        gfile.metadata = format!("{}{}{}", gfile.metadata, BURNED_IN_SEPARATOR, subtitle_key);
+        gfile
     }
 
-    fn transcode_args_video(&self, cmd: &mut std::process::Command) {
-        match self {
-            TranscodeProfile::H264_200K => {
-                // Scale to 360p, keeping aspect ratio
-                // TODO: prevent scaling up
-                cmd.arg("-vf").arg("scale=-4:360");
-                cmd.arg("-c:v").arg("libx264");
-                cmd.arg("-preset").arg("slower");
-                cmd.arg("-b:v").arg("200k");
+    /// Scale down to the profile's height, overlaying the burn_in subtitle stream (an
+    /// ffmpeg stream specifier like "0:3") if there is one
+    fn video_filter_args(&self, cmd: &mut std::process::Command, burn_in: Option<&str>) {
+        // Odd source heights would pass through min() and fail in the encoder
+        let scale = format!("scale=-4:'min({},trunc(ih/2)*2)'", self.height);
+        match burn_in {
+            None => {
+                cmd.arg("-vf").arg(scale);
             }
-            // See SVT-AV1 docs for recommendations:
-            // https://gitlab.com/AOMediaCodec/SVT-AV1/-/blob/master/Docs/Ffmpeg.md#example-2-encoding-for-personal-use
-            TranscodeProfile::AV1_200K => {
-                // Scale to 480p, keeping aspect ratio
-                // TODO: prevent scaling up
-                cmd.arg("-vf").arg("scale=-4:480");
-                cmd.arg("-pix_fmt").arg("yuv420p10le"); // 10-bit improves encode quality
-                cmd.arg("-c:v").arg("libsvtav1");
-                cmd.arg("-b:v").arg("200k");
-                cmd.arg("-preset").arg("5");
-                cmd.arg("-svtav1-params").arg("film-grain=8");
-                cmd.arg("-g").arg("300");
-            }
-            TranscodeProfile::AV1_400K => {
-                // Scale to 720p, keeping aspect ratio
-                // TODO: prevent scaling up
-                cmd.arg("-vf").arg("scale=-4:720");
-                cmd.arg("-pix_fmt").arg("yuv420p10le"); // 10-bit improves encode quality
-                cmd.arg("-c:v").arg("libsvtav1");
-                cmd.arg("-b:v").arg("400k");
-                cmd.arg("-preset").arg("5");
-                cmd.arg("-svtav1-params").arg("film-grain=4");
-                cmd.arg("-g").arg("300");
+            Some(subtitle_stream) => {
+                cmd.arg("-filter_complex")
+                    .arg(format!("[0:v:0][{}]overlay,{}[v]", subtitle_stream, scale));
+                cmd.arg("-map").arg("[v]").arg("-map").arg("0:a:0?");
             }
         }
     }
 
-    fn transcode_args_audio(&self, cmd: &mut std::process::Command) {
-        match self {
-            TranscodeProfile::H264_200K => {
-                cmd.arg("-c:a").arg("aac");
-                cmd.arg("-b:a").arg("32k");
+    fn transcode_args_video(&self, cmd: &mut std::process::Command, burn_in: Option<&str>) {
+        self.video_filter_args(cmd, burn_in);
+        if let Some(pixel_format) = &self.pixel_format {
+            cmd.arg("-pix_fmt").arg(pixel_format);
+        }
+        cmd.arg("-c:v").arg(self.video_codec.encoder());
+        if let Some(preset) = &self.preset {
+            match self.video_codec {
+                VideoCodec::Vp9 => cmd.arg("-cpu-used").arg(preset),
+                _ => cmd.arg("-preset").arg(preset),
+            };
+        }
+        match (self.video_bitrate_kbps, self.crf) {
+            (Some(video_bitrate_kbps), _) => {
+                cmd.arg("-b:v").arg(format!("{}k", video_bitrate_kbps));
             }
-            TranscodeProfile::AV1_200K => {
-                cmd.arg("-c:a").arg("libopus");
-                cmd.arg("-b:a").arg("32k");
+            (None, Some(crf)) => {
+                cmd.arg("-crf").arg(crf.to_string());
+                if self.video_codec == VideoCodec::Vp9 {
+                    // Otherwise libvpx treats the CRF as a cap on top of its default bitrate
+                    cmd.arg("-b:v").arg("0");
+                }
             }
-            TranscodeProfile::AV1_400K => {
-                cmd.arg("-c:a").arg("libopus");
-                cmd.arg("-b:a").arg("48k");
+            (None, None) => {}
+        }
+        if let Some(encoder_params) = &self.encoder_params {
+            match self.video_codec {
+                VideoCodec::H264 => cmd.arg("-x264-params").arg(encoder_params),
+                _ => cmd.arg("-svtav1-params").arg(encoder_params),
+            };
+        }
+        if let Some(keyframe_interval) = self.keyframe_interval {
+            cmd.arg("-g").arg(keyframe_interval.to_string());
+        }
+    }
+
+    fn transcode_args_audio(&self, cmd: &mut std::process::Command) {
+        cmd.arg("-c:a").arg(self.audio_codec.encoder());
+        cmd.arg("-b:a").arg(format!("{}k", self.audio_bitrate_kbps));
+    }
+}
+
+/// Arguments for transcoding a single HLS segment. Only H.264 profiles with a target
+/// bitrate are supported, since they play everywhere; the encoder preset is much
+/// faster than for whole-file transcodes, so that segments are ready before the
+/// player needs them.
+pub fn hls_segment_args(profile: &TranscodeProfile, cmd: &mut std::process::Command) -> Result<(), String> {
+    let video_bitrate = match (profile.video_codec, profile.video_bitrate_kbps) {
+        (VideoCodec::H264, Some(video_bitrate_kbps)) => format!("{}k", video_bitrate_kbps),
+        _ => return Err(format!("{:?} can't be used for HLS", profile.name)),
+    };
+
+    cmd.arg("-vf").arg(format!("scale=-2:'min({},trunc(ih/2)*2)'", profile.height));
+    cmd.arg("-c:v").arg("libx264");
+    cmd.arg("-preset").arg("veryfast");
+    cmd.arg("-pix_fmt").arg("yuv420p");
+    cmd.arg("-b:v").arg(&video_bitrate);
+    cmd.arg("-maxrate").arg(&video_bitrate);
+    cmd.arg("-bufsize").arg(&video_bitrate);
+    profile.transcode_args_audio(cmd);
+    Ok(())
+}
+
+/// Separates the profile from the subtitle key in the metadata of burned-in alt formats
+const BURNED_IN_SEPARATOR: &str = "_sub_";
+
+/// Key of the subtitles burned into an alt format, if any
+pub fn burned_in_subtitle_key(gfile: &GeneratedFile) -> Option<&str> {
+    gfile.metadata.split_once(BURNED_IN_SEPARATOR).map(|(_, key)| key)
+}
+
+/// Add the video (and bitmap subtitle file, if burning one in) as inputs. Returns
+/// the stream specifier of the subtitles to burn in.
+fn add_inputs(cmd: &mut std::process::Command, video_path: &Path, burn_in: Option<&Subtitle>) -> Option<String> {
+    cmd.arg("-i").arg(video_path);
+    match &burn_in?.source {
+        SubtitleSource::Embedded { stream, .. } => Some(format!("0:{}", stream)),
+        SubtitleSource::OtherFile(path) | SubtitleSource::WebVTTFile(path) => {
+            // ffmpeg reads VobSub through the .idx file
+            if path.extension().is_some_and(|ext| ext == "sub") {
+                cmd.arg("-i").arg(path.with_extension("idx"));
+            } else {
+                cmd.arg("-i").arg(path);
             }
+            Some("1:s:0".to_string())
         }
     }
 }
 
-pub fn transcode_alt_format(video_path: &Path, out_path: &Path, profile: TranscodeProfile) {
-    if profile.two_pass() {
+pub fn transcode_alt_format(
+    video_path: &Path,
+    out_path: &Path,
+    profile: &TranscodeProfile,
+) -> Result<(), Box<dyn std::error::Error>> {
+    transcode_alt_format_burn_in(video_path, out_path, profile, None)
+}
+
+/// Like transcode_alt_format, burning the given subtitles into the video
+pub fn transcode_alt_format_burn_in(
+    video_path: &Path,
+    out_path: &Path,
+    profile: &TranscodeProfile,
+    burn_in: Option<&Subtitle>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Write to a temporary file first, so nothing probes or serves a partial output
+    let temp_path = ffmpeg::temp_output_path(out_path)?;
+    let status = if profile.two_pass {
         let two_pass_log = out_path.with_extension("log");
 
         // Pass 1
         let mut cmd = std::process::Command::new("ffmpeg");
-        cmd.arg("-i")
-            .arg(video_path);
-        profile.transcode_args_video(&mut cmd);
+        let subtitle_stream = add_inputs(&mut cmd, video_path, burn_in);
+        profile.transcode_args_video(&mut cmd, subtitle_stream.as_deref());
         cmd.arg("-an")
             .arg("-pass").arg("1")
             .arg("-passlogfile").arg(&two_pass_log)
@@ -126,15 +333,14 @@ pub fn transcode_alt_format(video_path: &Path, out_path: &Path, profile: Transco
 
         // Pass 2
         let mut cmd = std::process::Command::new("ffmpeg");
-        cmd.arg("-i")
-            .arg(video_path);
-        profile.transcode_args_video(&mut cmd);
+        let subtitle_stream = add_inputs(&mut cmd, video_path, burn_in);
+        profile.transcode_args_video(&mut cmd, subtitle_stream.as_deref());
         profile.transcode_args_audio(&mut cmd);
         cmd.arg("-pass").arg("2")
             .arg("-passlogfile").arg(&two_pass_log);
-        cmd.arg(out_path);
+        cmd.arg("-y").arg(&temp_path);
 
-        ffmpeg::ffmpeg_progress_updates(&mut cmd, |time_secs| {
+        let status = ffmpeg::ffmpeg_progress_updates(&mut cmd, |time_secs| {
             println!("Pass 2: {:.1} seconds", time_secs);
         });
 
@@ -145,55 +351,27 @@ pub fn transcode_alt_format(video_path: &Path, out_path: &Path, profile: Transco
                 let path = entry.expect("entry");
                 std::fs::remove_file(&path).expect("remove two-pass log");
             });
+        status
     } else {
         let mut cmd = std::process::Command::new("ffmpeg");
-        cmd.arg("-i")
-            .arg(video_path);
-        profile.transcode_args_video(&mut cmd);
+        let subtitle_stream = add_inputs(&mut cmd, video_path, burn_in);
+        profile.transcode_args_video(&mut cmd, subtitle_stream.as_deref());
         profile.transcode_args_audio(&mut cmd);
         // TODO: should we ignore errors?
         cmd.arg("-err_detect").arg("ignore_err");
-        cmd.arg(out_path);
+        cmd.arg("-y").arg(&temp_path);
         ffmpeg::ffmpeg_progress_updates(&mut cmd, |time_secs| {
             println!("Transcoding progress: {:.1} seconds", time_secs);
-        });
+        })
+    };
+    if !status.success() {
+        return Err(format!("transcoding {:?} to {:?} failed: {}", video_path, profile.name, status).into());
     }
-}
 
-/// Predict whether Chrome can play a given video.
-/// If not, the video is a candidate for transcoding.
-pub fn chrome_can_play(video_info: &VideoInfo) -> bool {
-    let mime_type = video_info.mime_type.as_str();
-    let codec = video_info.codec.as_str();
-    let codec_rfc6381 = video_info.codec_rfc6381.as_str();
-
-    if mime_type == "video/mp4" {
-        match codec_rfc6381 {
-            "avc1" => true,
-            // Chrome can't consistently play HEVC yet
-            "hvc1" => false,
-            "vp09" => true,
-            "vp08" => true,
-            "av01" => true,
-            _ => false,
-        }
-    } else if mime_type == "video/webm" {
-        match codec {
-            "h264" => true,
-            // Neither Safari nor Chrome can play HEVC in mkv
-            // https://www.reddit.com/r/jellyfin/comments/yennxi/hevc_in_chromechromium/
-            "hevc" => false,
-            "vp9" => true,
-            "vp8" => true,
-            "av1" => true,
-            _ => false,
-        }
-    } else {
-        false
-    }
+    temp_path.persist(out_path)?;
+    Ok(())
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -211,7 +389,8 @@ mod tests {
             .count();
         let video_path = file_root.join("vidaud_h265_aac.mkv");
 
-        transcode_alt_format(&video_path, &tempdir.path().join("out.mp4"), TranscodeProfile::H264_200K);
+        let profile = TranscodeProfile::builtin("h264_200k").expect("builtin");
+        transcode_alt_format(&video_path, &tempdir.path().join("out.mp4"), &profile)?;
 
         assert!(tempdir.path().join("out.mp4").exists(), "Output file should exist");
 
@@ -235,12 +414,10 @@ mod tests {
             .count();
         let video_path = file_root.join("vidaud_h265_aac.mkv");
 
-        for profile in [
-            TranscodeProfile::AV1_200K,
-            TranscodeProfile::AV1_400K,
-        ] {
+        for name in ["av1_200k", "av1_400k"] {
+            let profile = TranscodeProfile::builtin(name).expect("builtin");
             let out_path = tempdir.path().join(format!("out_{}.webm", profile.to_gen_file(0).metadata));
-            transcode_alt_format(&video_path, &out_path, profile);
+            transcode_alt_format(&video_path, &out_path, &profile)?;
 
             assert!(out_path.exists(), "Output file should exist for {:?}", profile);
         }
@@ -255,27 +432,143 @@ mod tests {
     }
 
     #[test]
-    fn test_chrome_can_play() {
-        let video_info = VideoInfo {
-            mime_type: "video/mp4".to_string(),
-            codec: "h264".to_string(),
-            codec_rfc6381: "avc1".to_string(),
-            duration_secs: 120.0,
-            width: 1920,
-            height: 1080,
-            bitrate: 5000000,
+    fn test_builtin_profiles() {
+        for name in BUILTIN_PROFILE_NAMES {
+            let profile = TranscodeProfile::builtin(name).expect("builtin");
+            assert_eq!(profile.name, name);
+            profile.validate().expect("valid");
+        }
+        assert!(TranscodeProfile::builtin("h264_9000k").is_none());
+
+        let profile = TranscodeProfile::builtin("av1_400k").expect("builtin");
+        let gfile = profile.to_gen_file(1);
+        assert_eq!((gfile.metadata.as_str(), gfile.extension.as_str()), ("av1_400k", "webm"));
+        assert_eq!(profile.bandwidth_bps(), Some(448_000));
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let config: crate::FilerConfig = serde_json::from_str(
+            r#"{"transcode_profiles": {
+                "vp9_crf": {"video_codec": "vp9", "height": 1080, "crf": 32, "audio_codec": "opus",
+                    "audio_bitrate_kbps": 96, "container": "webm"},
+                "h264_800k": {"video_codec": "h264", "height": 480, "video_bitrate_kbps": 800,
+                    "audio_codec": "aac", "audio_bitrate_kbps": 64, "container": "mp4", "two_pass": true},
+                "bad": {"video_codec": "h264", "height": 480, "crf": 20, "audio_codec": "aac",
+                    "audio_bitrate_kbps": 64, "container": "webm"}
+            }}"#,
+        )
+        .expect("parse config");
+
+        // New profiles need no code change
+        let vp9 = config.transcode_profile("vp9_crf").expect("vp9_crf");
+        assert_eq!(vp9.to_gen_file(1).metadata, "vp9_crf");
+        assert_eq!(vp9.bandwidth_bps(), None);
+
+        // Built-in profiles can be overridden
+        assert_eq!(config.transcode_profile("h264_800k").expect("h264_800k").height, 480);
+        assert_eq!(config.transcode_profile("av1_200k").expect("av1_200k").height, 480);
+
+        assert!(config.transcode_profile("bad").is_err());
+        assert!(config.transcode_profile("missing").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = TranscodeProfile::builtin("h264_800k").expect("builtin");
+        let invalid = [
+            TranscodeProfile { name: "bad.name".to_string(), ..valid.clone() },
+            TranscodeProfile { name: "h264_sub_en".to_string(), ..valid.clone() },
+            TranscodeProfile { height: 721, ..valid.clone() },
+            TranscodeProfile { crf: Some(23), ..valid.clone() },
+            TranscodeProfile { video_bitrate_kbps: None, ..valid.clone() },
+            TranscodeProfile { video_bitrate_kbps: None, crf: Some(60), ..valid.clone() },
+            TranscodeProfile { video_bitrate_kbps: None, crf: Some(23), two_pass: true, ..valid.clone() },
+            TranscodeProfile { container: Container::Webm, ..valid.clone() },
+        ];
+        for profile in invalid {
+            assert!(profile.validate().is_err(), "{:?} should be invalid", profile);
+        }
+    }
+
+    #[test]
+    fn test_transcode_args() {
+        let args = |cmd: &std::process::Command| -> Vec<String> {
+            cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect()
         };
-        assert!(chrome_can_play(&video_info));
-
-        let video_info = VideoInfo {
-            mime_type: "video/mp4".to_string(),
-            codec: "hevc".to_string(),
-            codec_rfc6381: "hvc1".to_string(),
-            duration_secs: 120.0,
-            width: 1920,
-            height: 1080,
-            bitrate: 5000000,
+
+        let mut cmd = std::process::Command::new("ffmpeg");
+        let av1 = TranscodeProfile::builtin("av1_200k").expect("builtin");
+        av1.transcode_args_video(&mut cmd, None);
+        av1.transcode_args_audio(&mut cmd);
+        assert_eq!(
+            args(&cmd),
+            [
+                "-vf", "scale=-4:'min(480,trunc(ih/2)*2)'", "-pix_fmt", "yuv420p10le", "-c:v", "libsvtav1",
+                "-preset", "5", "-b:v", "200k", "-svtav1-params", "film-grain=8", "-g", "300",
+                "-c:a", "libopus", "-b:a", "32k",
+            ]
+        );
+
+        let mut cmd = std::process::Command::new("ffmpeg");
+        let vp9 = TranscodeProfile {
+            video_codec: VideoCodec::Vp9,
+            video_bitrate_kbps: None,
+            crf: Some(32),
+            preset: None,
+            pixel_format: None,
+            keyframe_interval: None,
+            encoder_params: None,
+            ..av1
+        };
+        vp9.transcode_args_video(&mut cmd, None);
+        assert_eq!(
+            args(&cmd),
+            ["-vf", "scale=-4:'min(480,trunc(ih/2)*2)'", "-c:v", "libvpx-vp9", "-crf", "32", "-b:v", "0"]
+        );
+
+        let mut cmd = std::process::Command::new("ffmpeg");
+        let h264 = TranscodeProfile::builtin("h264_800k").expect("builtin");
+        hls_segment_args(&h264, &mut cmd).expect("hls_segment_args");
+        assert_eq!(&args(&cmd)[..2], ["-vf", "scale=-2:'min(720,trunc(ih/2)*2)'"]);
+        assert!(hls_segment_args(&vp9, &mut cmd).is_err());
+    }
+
+    #[test]
+    fn test_burn_in_args() {
+        let profile = TranscodeProfile::builtin("h264_800k").expect("builtin");
+        let gfile = profile.to_burned_in_gen_file(1, "en_sdh");
+        assert_eq!(gfile.metadata, "h264_800k_sub_en_sdh");
+        assert_eq!(burned_in_subtitle_key(&gfile), Some("en_sdh"));
+        assert_eq!(burned_in_subtitle_key(&profile.to_gen_file(1)), None);
+
+        let args = |cmd: &std::process::Command| -> Vec<String> {
+            cmd.get_args().map(|arg| arg.to_string_lossy().to_string()).collect()
+        };
+
+        let mut cmd = std::process::Command::new("ffmpeg");
+        let embedded = crate::media::video::subtitle::Subtitle {
+            source: SubtitleSource::Embedded { video_path: "in.mkv".into(), stream: 3 },
+            key: "en".to_string(),
+            language: Some("en".to_string()),
+            forced: false,
+            hearing_impaired: false,
+            title: None,
+            is_bitmap: true,
+        };
+        let stream = add_inputs(&mut cmd, Path::new("in.mkv"), Some(&embedded));
+        profile.transcode_args_video(&mut cmd, stream.as_deref());
+        let embedded_args = args(&cmd);
+        assert_eq!(&embedded_args[..2], ["-i", "in.mkv"]);
+        assert!(embedded_args.contains(&"[0:v:0][0:3]overlay,scale=-4:'min(720,trunc(ih/2)*2)'[v]".to_string()));
+        assert!(!embedded_args.contains(&"-vf".to_string()));
+
+        let mut cmd = std::process::Command::new("ffmpeg");
+        let vobsub = crate::media::video::subtitle::Subtitle {
+            source: SubtitleSource::OtherFile("in.fr.sub".into()),
+            ..embedded
         };
-        assert!(!chrome_can_play(&video_info));
+        assert_eq!(add_inputs(&mut cmd, Path::new("in.mkv"), Some(&vobsub)).as_deref(), Some("1:s:0"));
+        assert_eq!(args(&cmd), ["-i", "in.mkv", "-i", "in.fr.idx"]);
     }
 }
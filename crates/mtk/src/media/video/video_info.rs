@@ -2,11 +2,20 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+use crate::catalog::{generated_notes, Catalog, DbEntry};
+use crate::file_tree::GeneratedFile;
 use crate::media::ffmpeg;
 
 pub const VIDEO_INFO_GROUP_NAME: &'static str = "video";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Version of the "video" group. Bump it when VideoInfo gains fields or they're
+/// computed differently, so cached infos are re-probed.
+///
+/// 2: frame rate, pixel format, HDR transfer, rotation, audio streams and chapters
+/// 3: .mkv files are video/x-matroska rather than video/webm
+pub const VIDEO_INFO_VERSION: u64 = 3;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct VideoInfo {
     pub mime_type: String,
     pub codec: String,
@@ -15,89 +24,426 @@ pub struct VideoInfo {
     pub width: u32,
     pub height: u32,
     pub bitrate: u64,
+
+    // Added in version 2, so older cached infos won't have these
+    #[serde(default)]
+    pub frame_rate: Option<f64>,
+    #[serde(default)]
+    pub pix_fmt: Option<String>,
+    /// Transfer characteristics, e.g. "smpte2084" for HDR10
+    #[serde(default)]
+    pub color_transfer: Option<String>,
+    /// Clockwise rotation (0, 90, 180 or 270) needed to display the video upright
+    #[serde(default)]
+    pub rotation: u32,
+    #[serde(default)]
+    pub audio_streams: Vec<AudioStreamInfo>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
 }
 
-/// Extract a VideoInfo from a video file using ffprobe.
-///
-/// This is slow, prefer using the cached version in Entry::generated_notes if
-/// many are needed (e.g. during dir listings).
-pub fn get_video_info(video_path: &Path) -> Result<VideoInfo, Box<dyn std::error::Error>> {
-    // Call ffprobe to get:
-    // - mime type
-    // - codec name
-    // - duration
-    // - resolution
-    // - bitrate
-    #[derive(Deserialize)]
-    struct FfprobeStream {
-        codec_name: String,
-        width: u32,
-        height: u32,
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioStreamInfo {
+    pub codec: String,
+    pub language: Option<String>,
+    pub channels: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_secs: f64,
+    pub end_secs: f64,
+    pub title: Option<String>,
+}
+
+impl VideoInfo {
+    /// PQ (HDR10, Dolby Vision) or HLG transfer
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.color_transfer.as_deref(), Some("smpte2084") | Some("arib-std-b67"))
     }
 
-    #[derive(Deserialize)]
-    struct FfprobeFormat {
-        duration: String,
-        bit_rate: String,
+    /// Width and height as displayed, after rotation
+    pub fn display_dimensions(&self) -> (u32, u32) {
+        if self.rotation % 180 == 90 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
     }
+}
 
-    #[derive(Deserialize)]
-    struct FfprobeOutput {
-        streams: Vec<FfprobeStream>,
-        format: FfprobeFormat,
+fn mime_type_for_extension(extension: &str) -> &'static str {
+    match extension.to_lowercase().as_str() {
+        "mp4" => "video/mp4",
+        "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "avi" => "video/x-msvideo",
+        "wmv" => "video/x-ms-wmv",
+        "mov" => "video/quicktime",
+        _ => "application/octet-stream",
     }
+}
+
+/// Parse ffprobe's "30000/1001"-style rates. "0/0" means unknown.
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    color_transfer: Option<String>,
+    channels: Option<u32>,
+    #[serde(default)]
+    tags: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    disposition: std::collections::HashMap<String, i64>,
+    #[serde(default)]
+    side_data_list: Vec<serde_json::Value>,
+}
 
+impl FfprobeStream {
+    fn is_cover_art(&self) -> bool {
+        self.disposition.get("attached_pic") == Some(&1)
+    }
+
+    fn rotation(&self) -> u32 {
+        // Newer ffprobe reports a display matrix (counterclockwise degrees), older
+        // versions a "rotate" tag (clockwise)
+        let display_matrix = self
+            .side_data_list
+            .iter()
+            .find_map(|side_data| side_data.get("rotation").and_then(|r| r.as_f64()))
+            .map(|degrees| -degrees);
+        let degrees = display_matrix
+            .or_else(|| self.tags.get("rotate").and_then(|r| r.parse().ok()))
+            .unwrap_or(0.0);
+        ((degrees.round() as i64).rem_euclid(360) as u32 / 90) * 90
+    }
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeChapter {
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    tags: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: FfprobeFormat,
+    #[serde(default)]
+    chapters: Vec<FfprobeChapter>,
+}
+
+/// Build a VideoInfo from ffprobe's JSON output. Anything but the video stream
+/// itself is optional.
+fn parse_ffprobe_output(json: &str, extension: &str) -> Result<VideoInfo, Box<dyn std::error::Error>> {
+    let ffprobe_output: FfprobeOutput = serde_json::from_str(json)?;
+
+    // Cover art shows up as a video stream too
+    let video_stream = ffprobe_output
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type.as_deref() == Some("video") && !stream.is_cover_art())
+        .ok_or("No video stream")?;
+    let codec_name = video_stream.codec_name.as_deref().unwrap_or("unknown");
+
+    let audio_streams = ffprobe_output
+        .streams
+        .iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("audio"))
+        .map(|stream| AudioStreamInfo {
+            codec: stream.codec_name.clone().unwrap_or_else(|| "unknown".to_string()),
+            language: stream.tags.get("language").filter(|lang| *lang != "und").cloned(),
+            channels: stream.channels,
+        })
+        .collect();
+
+    let chapters = ffprobe_output
+        .chapters
+        .iter()
+        .filter_map(|chapter| {
+            Some(Chapter {
+                start_secs: chapter.start_time.parse().ok()?,
+                end_secs: chapter.end_time.parse().ok()?,
+                title: chapter.tags.get("title").cloned(),
+            })
+        })
+        .collect();
+
+    Ok(VideoInfo {
+        mime_type: mime_type_for_extension(extension).to_string(),
+        codec: ffmpeg::codec_name_to_fancy(codec_name).to_string(),
+        codec_rfc6381: ffmpeg::codec_name_to_rfc6381(codec_name).to_string(),
+        duration_secs: ffprobe_output.format.duration.as_deref().and_then(|d| d.parse().ok()).unwrap_or(0.0),
+        width: video_stream.width.unwrap_or(0),
+        height: video_stream.height.unwrap_or(0),
+        bitrate: ffprobe_output.format.bit_rate.as_deref().and_then(|b| b.parse().ok()).unwrap_or(0),
+        frame_rate: video_stream
+            .avg_frame_rate
+            .as_deref()
+            .and_then(parse_frame_rate)
+            .or_else(|| video_stream.r_frame_rate.as_deref().and_then(parse_frame_rate)),
+        pix_fmt: video_stream.pix_fmt.clone(),
+        color_transfer: video_stream.color_transfer.clone().filter(|t| t != "unknown"),
+        rotation: video_stream.rotation(),
+        audio_streams,
+        chapters,
+    })
+}
+
+/// Extract a VideoInfo from a video file using ffprobe.
+///
+/// This is slow, prefer using the cached version in Entry::generated_notes if
+/// many are needed (e.g. during dir listings).
+pub fn get_video_info(video_path: &Path) -> Result<VideoInfo, Box<dyn std::error::Error>> {
     let output = std::process::Command::new("ffprobe")
         .arg("-v")
         .arg("error")
-        .arg("-select_streams")
-        .arg("v:0")
-        .arg("-show_entries")
-        .arg("stream=codec_name,width,height")
-        .arg("-show_entries")
-        .arg("format=duration,bit_rate")
+        .arg("-show_streams")
+        .arg("-show_format")
+        .arg("-show_chapters")
         .arg("-of")
         .arg("json")
         .arg(video_path)
-        .output()
-        .expect("ffprobe");
-
-    let ffprobe_output: FfprobeOutput =
-        serde_json::from_str(&String::from_utf8_lossy(&output.stdout))?;
-    if ffprobe_output.streams.len() != 1 {
-        return Err(
-            format!(
-                "Expected exactly one video stream, got {:?}",
-                ffprobe_output.streams.len()
-            )
-            .into(),
-        );
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed for {:?}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
     }
 
-    let mime_type = match video_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .expect("ext.to_str")
+    let extension = video_path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    parse_ffprobe_output(&String::from_utf8_lossy(&output.stdout), extension)
+}
+
+/// Generated notes group (on the main entry) for the info of one of its alt formats.
+pub fn alt_format_group_name(gfile: &GeneratedFile) -> String {
+    format!("alt_format_{}", gfile.metadata)
+}
+
+/// Like get_video_info, but uses the info cached in the entry's generated notes if
+/// it's up to date (and from the current version). Otherwise runs ffprobe and caches
+/// the result.
+pub fn get_video_info_cached(
+    entry: &crate::Entry,
+    catalog: &mut Catalog,
+) -> Result<VideoInfo, Box<dyn std::error::Error>> {
+    if !generated_notes::needs_update_versioned(entry, VIDEO_INFO_GROUP_NAME, VIDEO_INFO_VERSION)
+        && let Some(video_info) = generated_notes::read(&entry.db, VIDEO_INFO_GROUP_NAME)
     {
-        "mp4" => "video/mp4",
-        "m4v" => "video/mp4",
-        "webm" => "video/webm",
-        "mkv" => "video/webm",
-        "avi" => "video/x-msvideo",
-        "wmv" => "video/x-ms-wmv",
-        "mov" => "video/quicktime",
-        _ => panic!("Unknown video extension {:?}", video_path.extension()),
-    };
+        return Ok(video_info);
+    }
 
-    Ok(VideoInfo {
-        mime_type: mime_type.to_string(),
-        codec: ffmpeg::codec_name_to_fancy(&ffprobe_output.streams[0].codec_name)
-            .to_string(),
-        codec_rfc6381: ffmpeg::codec_name_to_rfc6381(&ffprobe_output.streams[0].codec_name)
-            .to_string(),
-        duration_secs: ffprobe_output.format.duration.parse()?,
-        width: ffprobe_output.streams[0].width,
-        height: ffprobe_output.streams[0].height,
-        bitrate: ffprobe_output.format.bit_rate.parse()?,
-    })
+    let video_info = get_video_info(&entry.fs.file_path)?;
+    generated_notes::update_versioned(catalog, entry.db.id, VIDEO_INFO_GROUP_NAME, VIDEO_INFO_VERSION, &video_info);
+    Ok(video_info)
+}
+
+/// Probe a newly generated alt format and cache its info on the main entry.
+pub fn record_alt_format_info(
+    catalog: &mut Catalog,
+    entry_id: i64,
+    gfile: &GeneratedFile,
+    alt_format_path: &Path,
+) -> Result<VideoInfo, Box<dyn std::error::Error>> {
+    let video_info = get_video_info(alt_format_path)?;
+    let group_name = alt_format_group_name(gfile);
+    generated_notes::update_versioned(catalog, entry_id, &group_name, VIDEO_INFO_VERSION, &video_info);
+    Ok(video_info)
+}
+
+/// Info for one of the entry's alt formats, from the cache if it was recorded by the
+/// current version.
+pub fn get_alt_format_info_cached(
+    db_entry: &DbEntry,
+    catalog: &mut Catalog,
+    gfile: &GeneratedFile,
+    alt_format_path: &Path,
+) -> Result<VideoInfo, Box<dyn std::error::Error>> {
+    let group_name = alt_format_group_name(gfile);
+    if generated_notes::version(db_entry, &group_name) >= VIDEO_INFO_VERSION
+        && let Some(video_info) = generated_notes::read(db_entry, &group_name)
+    {
+        return Ok(video_info);
+    }
+    record_alt_format_info(catalog, db_entry.id, gfile, alt_format_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::file_tree::GeneratedFileType;
+    use crate::testing;
+
+    fn video_info(codec: &str) -> VideoInfo {
+        VideoInfo {
+            mime_type: "video/webm".to_string(),
+            codec: codec.to_string(),
+            codec_rfc6381: codec.to_string(),
+            duration_secs: 10.0,
+            width: 320,
+            height: 240,
+            bitrate: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "video", "codec_name": "mjpeg", "width": 600, "height": 600, "disposition": {"attached_pic": 1}},
+                {
+                    "codec_type": "video", "codec_name": "hevc", "width": 3840, "height": 2160,
+                    "avg_frame_rate": "30000/1001", "r_frame_rate": "30/1",
+                    "pix_fmt": "yuv420p10le", "color_transfer": "smpte2084",
+                    "disposition": {"attached_pic": 0},
+                    "side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]
+                },
+                {"codec_type": "audio", "codec_name": "aac", "channels": 6, "tags": {"language": "eng"}},
+                {"codec_type": "audio", "codec_name": "opus", "channels": 2, "tags": {"language": "und"}},
+                {"codec_type": "subtitle", "codec_name": "subrip"}
+            ],
+            "format": {"duration": "125.500000", "bit_rate": "4000000"},
+            "chapters": [
+                {"start_time": "0.000000", "end_time": "60.000000", "tags": {"title": "Intro"}},
+                {"start_time": "60.000000", "end_time": "125.500000"}
+            ]
+        }"#;
+        let info = parse_ffprobe_output(json, "MOV").expect("parse_ffprobe_output");
+
+        assert_eq!(info.mime_type, "video/quicktime");
+        assert_eq!(info.codec, "hvc1");
+        assert_eq!((info.width, info.height), (3840, 2160));
+        assert_eq!(info.display_dimensions(), (2160, 3840));
+        assert_eq!(info.rotation, 90);
+        assert!((info.frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert!(info.is_hdr());
+        assert_eq!(info.duration_secs, 125.5);
+        assert_eq!(info.audio_streams, vec![
+            AudioStreamInfo { codec: "aac".to_string(), language: Some("eng".to_string()), channels: Some(6) },
+            AudioStreamInfo { codec: "opus".to_string(), language: None, channels: Some(2) },
+        ]);
+        assert_eq!(info.chapters.len(), 2);
+        assert_eq!(info.chapters[0].title.as_deref(), Some("Intro"));
+        assert_eq!(info.chapters[1].start_secs, 60.0);
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output_minimal() {
+        // Missing fields and unknown extensions don't fail
+        let json = r#"{"streams": [{"codec_type": "video", "codec_name": "h264"}], "format": {}}"#;
+        let info = parse_ffprobe_output(json, "xyz").expect("parse_ffprobe_output");
+        assert_eq!(info.mime_type, "application/octet-stream");
+        assert_eq!((info.width, info.height, info.bitrate), (0, 0, 0));
+        assert_eq!(info.duration_secs, 0.0);
+        assert_eq!(info.frame_rate, None);
+        assert!(!info.is_hdr());
+        assert!(info.audio_streams.is_empty());
+
+        let info = parse_ffprobe_output(json, "MKV").expect("parse_ffprobe_output");
+        assert_eq!(info.mime_type, "video/x-matroska");
+
+        // No video stream at all
+        let json = r#"{"streams": [{"codec_type": "audio", "codec_name": "mp3"}], "format": {}}"#;
+        assert!(parse_ffprobe_output(json, "mp4").is_err());
+    }
+
+    #[test]
+    fn test_parse_frame_rate() {
+        assert_eq!(parse_frame_rate("25/1"), Some(25.0));
+        assert_eq!(parse_frame_rate("0/0"), None);
+        assert_eq!(parse_frame_rate("bogus"), None);
+    }
+
+    #[test]
+    fn test_old_cached_info() {
+        // Infos cached before the extra fields were added still deserialize
+        let json = r#"{"mime_type": "video/mp4", "codec": "h264", "codec_rfc6381": "avc1",
+            "duration_secs": 1.0, "width": 2, "height": 3, "bitrate": 4}"#;
+        let info: VideoInfo = serde_json::from_str(json).expect("from_str");
+        assert_eq!(info.rotation, 0);
+        assert!(info.chapters.is_empty());
+    }
+
+    #[test]
+    fn test_cached_info_skips_ffprobe() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        // Not a real video, so ffprobe would fail on it
+        std::fs::write(file_root.path().join("fake.webm"), "")?;
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        let entry = testing::entry_for("fake.webm", &file_tree, &mut catalog)?;
+        let alt_format = GeneratedFile {
+            entry_id: entry.db.id,
+            file_type: GeneratedFileType::AltFormat,
+            metadata: "av1_400k".to_string(),
+            extension: "webm".to_string(),
+        };
+        let alt_format_path = vault.new_generated_tree().path_to_generated_file(&alt_format);
+
+        // Cached by an older version: probed again, which fails for this file
+        generated_notes::update(&mut catalog, entry.db.id, VIDEO_INFO_GROUP_NAME, &video_info("vp9"));
+        generated_notes::update(&mut catalog, entry.db.id, &alt_format_group_name(&alt_format), &video_info("av1"));
+        let entry = testing::entry_for("fake.webm", &file_tree, &mut catalog)?;
+        assert!(get_video_info_cached(&entry, &mut catalog).is_err());
+        assert!(get_alt_format_info_cached(&entry.db, &mut catalog, &alt_format, &alt_format_path).is_err());
+
+        generated_notes::update_versioned(&mut catalog, entry.db.id, VIDEO_INFO_GROUP_NAME, VIDEO_INFO_VERSION, &video_info("vp9"));
+        generated_notes::update_versioned(
+            &mut catalog,
+            entry.db.id,
+            &alt_format_group_name(&alt_format),
+            VIDEO_INFO_VERSION,
+            &video_info("av1"),
+        );
+        let entry = testing::entry_for("fake.webm", &file_tree, &mut catalog)?;
+        assert_eq!(get_video_info_cached(&entry, &mut catalog)?.codec, "vp9");
+        assert_eq!(get_alt_format_info_cached(&entry.db, &mut catalog, &alt_format, &alt_format_path)?.codec, "av1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_video_info_cached_writes_back() -> testing::TestResult {
+        let file_root = testing::testdata_path("preview");
+        let (_tempdir, vault) = testing::tempdir_vault(&file_root)?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        let entry = testing::entry_for("short_video.mp4", &file_tree, &mut catalog)?;
+        let video_info = get_video_info_cached(&entry, &mut catalog)?;
+
+        let entry = testing::entry_for("short_video.mp4", &file_tree, &mut catalog)?;
+        let cached: VideoInfo = generated_notes::read(&entry.db, VIDEO_INFO_GROUP_NAME).expect("cached");
+        assert_eq!(cached.codec, video_info.codec);
+
+        Ok(())
+    }
 }
@@ -1,14 +1,45 @@
 use std::path::Path;
 
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::{generated_notes, DbEntry};
+use crate::media::ffmpeg;
 use crate::preview;
 use crate::file_tree::{GeneratedFile, GeneratedFileType};
 
-#[allow(unused)]
-fn minivid_gfile(entry_id: i64) -> GeneratedFile {
+pub const PREVIEW_GROUP_NAME: &str = "preview";
+
+/// Candidate preview frames are spread this fraction of the duration apart
+const CANDIDATE_SPREAD: f64 = 0.05;
+/// Number of frames the thumbnail filter picks from, at each candidate
+const CANDIDATE_BATCH_FRAMES: usize = 24;
+/// Size of the grayscale thumbnail that candidate frames are scored on
+const SCORE_WIDTH: usize = 64;
+const SCORE_HEIGHT: usize = 36;
+/// Pixels outside this range count as black or white when scoring frames
+const DARK_PIXEL_THRESHOLD: u8 = 24;
+const BRIGHT_PIXEL_THRESHOLD: u8 = 232;
+
+/// Number of clips in a minivid, and the length of each
+const MINIVID_NUM_CLIPS: usize = 5;
+const MINIVID_CLIP_SECS: f64 = 1.5;
+/// Shorter dimension of a minivid, in pixels
+const MINIVID_DIMENSION: i32 = 240;
+
+/// Which frame of the video the preview image was taken from.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreviewFrame {
+    pub timestamp_secs: f64,
+    /// Whether the timestamp was pinned by the user, rather than picked automatically
+    pub pinned: bool,
+}
+
+pub fn minivid_gfile(entry_id: i64) -> GeneratedFile {
     GeneratedFile {
         entry_id: entry_id,
         file_type: GeneratedFileType::Minivid,
-        metadata: "".to_string(),
+        // Non-empty so that the file can be served from /generated/...
+        metadata: "montage".to_string(),
         extension: "webm".to_string(),
     }
 }
@@ -43,8 +74,8 @@ fn pick_preview_timestamp(duration_secs: f64) -> f64 {
     frame_timestamp_secs
 }
 
-pub fn make_preview_image(video_path: &Path, out_path: &Path) {
-    // Get total duration of video
+/// Get total duration of video using ffprobe
+fn probe_duration_secs(video_path: &Path) -> Option<f64> {
     let output = std::process::Command::new("ffprobe")
         .arg("-v")
         .arg("error")
@@ -55,32 +86,247 @@ pub fn make_preview_image(video_path: &Path, out_path: &Path) {
         .arg(video_path)
         .output()
         .expect("failed to execute ffprobe");
-    let duration_secs_result = std::str::from_utf8(&output.stdout).expect("from_utf8").trim().parse::<f64>();
-    if duration_secs_result.is_err() {
-        // TODO: actually return the errro
-        eprintln!("Failed to get duration of video: {:?}", video_path);
-        return;
+    std::str::from_utf8(&output.stdout).expect("from_utf8").trim().parse::<f64>().ok()
+}
+
+/// Score a frame for use as a preview, from grayscale pixels in row-major order.
+/// Higher is better; frames that are mostly black or white (fades, title cards) score
+/// close to zero, however sharp they are.
+fn score_frame(pixels: &[u8], width: usize) -> f64 {
+    if pixels.is_empty() {
+        return 0.0;
+    }
+    let height = pixels.len() / width;
+
+    let extreme_pixels = pixels
+        .iter()
+        .filter(|p| **p < DARK_PIXEL_THRESHOLD || **p > BRIGHT_PIXEL_THRESHOLD)
+        .count();
+    let extreme_fraction = extreme_pixels as f64 / pixels.len() as f64;
+
+    // Sharpness: mean absolute difference between neighboring pixels
+    let mut gradient_sum = 0u64;
+    let mut num_gradients = 0u64;
+    for y in 0..height {
+        for x in 0..width {
+            let p = pixels[y * width + x] as i32;
+            if x + 1 < width {
+                gradient_sum += (p - pixels[y * width + x + 1] as i32).unsigned_abs() as u64;
+                num_gradients += 1;
+            }
+            if y + 1 < height {
+                gradient_sum += (p - pixels[(y + 1) * width + x] as i32).unsigned_abs() as u64;
+                num_gradients += 1;
+            }
+        }
+    }
+    let sharpness = gradient_sum as f64 / num_gradients.max(1) as f64;
+
+    sharpness * (1.0 - extreme_fraction)
+}
+
+/// Timestamps to consider for the preview, best guess first: the fixed-percentage
+/// timestamp from pick_preview_timestamp, then a few on either side of it.
+fn pick_candidate_timestamps(duration_secs: f64) -> Vec<f64> {
+    let base = pick_preview_timestamp(duration_secs);
+    let spread = duration_secs * CANDIDATE_SPREAD;
+    let last = (duration_secs - 1.0).max(0.0);
+
+    let mut candidates = Vec::new();
+    for offset in [0.0, -1.0, 1.0, -2.0, 2.0] {
+        let timestamp = (base + offset * spread).clamp(0.0, last);
+        if !candidates.contains(&timestamp) {
+            candidates.push(timestamp);
+        }
+    }
+    candidates
+}
+
+/// Let ffmpeg's thumbnail filter pick the most representative frame in a short batch
+/// starting at `start_secs`, and score it. Returns the frame's timestamp and score.
+fn score_candidate(video_path: &Path, start_secs: f64) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-ss")
+        .arg(format!("{}s", start_secs))
+        .arg("-i")
+        .arg(video_path)
+        .arg("-an")
+        .arg("-vf")
+        .arg(format!(
+            "thumbnail={},showinfo,scale={}:{}:flags=area,format=gray",
+            CANDIDATE_BATCH_FRAMES, SCORE_WIDTH, SCORE_HEIGHT,
+        ))
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("ffmpeg failed for {:?}: {}", video_path, stderr).into());
     }
-    let duration_secs = duration_secs_result.expect("parse");
-    let frame_timestamp_secs = pick_preview_timestamp(duration_secs);
 
-    // Pick the frame at the time and save it to disk
+    // showinfo logs the picked frame's time, relative to the seek point
+    let re = regex::Regex::new(r"pts_time:\s*(-?[0-9.]+)").expect("regex");
+    let pts_time: f64 = re
+        .captures(&stderr)
+        .and_then(|caps| caps[1].parse().ok())
+        .ok_or_else(|| format!("no showinfo output for {:?}", video_path))?;
+
+    Ok((start_secs + pts_time, score_frame(&output.stdout, SCORE_WIDTH)))
+}
+
+/// Pick the best-looking frame among a few candidates around pick_preview_timestamp.
+fn pick_best_preview_timestamp(video_path: &Path, duration_secs: f64) -> f64 {
+    let candidates = pick_candidate_timestamps(duration_secs);
+
+    let mut best: Option<(f64, f64)> = None;
+    for candidate in &candidates {
+        match score_candidate(video_path, *candidate) {
+            Ok((timestamp, score)) => {
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some((timestamp, score));
+                }
+            }
+            Err(e) => eprintln!("Failed to score preview candidate at {}s: {}", candidate, e),
+        }
+    }
+
+    best.map(|(timestamp, _)| timestamp).unwrap_or(candidates[0])
+}
+
+/// Save the frame at `timestamp_secs` as a preview image.
+fn extract_preview_frame(video_path: &Path, out_path: &Path, timestamp_secs: f64) -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = std::process::Command::new("ffmpeg");
-    cmd.arg("-ss")
-        .arg(format!("{}s", frame_timestamp_secs))
+    cmd.arg("-y")
+        .arg("-ss")
+        .arg(format!("{}s", timestamp_secs))
         .arg("-i")
         .arg(video_path)
         .arg("-vframes")
         .arg("1");
     preview::ffmpeg_preview_args(&mut cmd);
-    cmd.arg(out_path)
-        .output()
-        .expect("failed to execute ffmpeg");
+    let output = cmd.arg(out_path).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg failed for {:?}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    Ok(())
+}
+
+/// Make a preview image for the video. Uses `pinned_timestamp_secs` if given (from the
+/// entry's "preview_timestamp" note), otherwise the best of several candidate frames.
+pub fn make_preview_image(
+    video_path: &Path,
+    out_path: &Path,
+    pinned_timestamp_secs: Option<f64>,
+) -> Result<PreviewFrame, Box<dyn std::error::Error>> {
+    let frame = match pinned_timestamp_secs {
+        Some(timestamp_secs) => PreviewFrame { timestamp_secs, pinned: true },
+        None => {
+            let duration_secs = probe_duration_secs(video_path)
+                .ok_or_else(|| format!("Failed to get duration of video: {:?}", video_path))?;
+            PreviewFrame {
+                timestamp_secs: pick_best_preview_timestamp(video_path, duration_secs),
+                pinned: false,
+            }
+        }
+    };
+
+    extract_preview_frame(video_path, out_path, frame.timestamp_secs)?;
+    Ok(frame)
+}
+
+/// Whether an existing preview should be remade because the pinned timestamp changed.
+pub fn is_preview_stale(db_entry: &DbEntry) -> bool {
+    let current = generated_notes::read::<PreviewFrame>(db_entry, PREVIEW_GROUP_NAME);
+    match (db_entry.preview_timestamp(), current) {
+        (Some(pinned), Some(current)) => !current.pinned || current.timestamp_secs != pinned,
+        (Some(_), None) => true,
+        // Pin was removed: go back to picking a frame automatically
+        (None, Some(current)) => current.pinned,
+        (None, None) => false,
+    }
 }
 
-#[allow(unused)]
-fn make_minivid(video_path: &Path, out_path: &Path) {
-    todo!();
+/// Pick start times for the clips in a minivid, spread evenly across the video
+/// (skipping the very beginning and end, which are often intros and credits).
+fn pick_minivid_clips(duration_secs: f64) -> Vec<f64> {
+    let total_clip_secs = MINIVID_NUM_CLIPS as f64 * MINIVID_CLIP_SECS;
+    if duration_secs <= total_clip_secs * 2.0 {
+        // Too short for a montage; just use the start of the video
+        return vec![0.0];
+    }
+
+    let start = duration_secs * 0.05;
+    let end = duration_secs * 0.95 - MINIVID_CLIP_SECS;
+    let step = (end - start) / (MINIVID_NUM_CLIPS - 1) as f64;
+    (0..MINIVID_NUM_CLIPS).map(|i| start + step * i as f64).collect()
+}
+
+/// Make a short, silent, low-bitrate montage of clips from across the video, for hover previews.
+pub fn make_minivid(video_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let duration_secs = probe_duration_secs(video_path)
+        .ok_or_else(|| format!("Failed to get duration of video: {:?}", video_path))?;
+    let clip_starts = pick_minivid_clips(duration_secs);
+
+    // The minivid job skips videos that already have one, so don't leave a partial one
+    let temp_path = ffmpeg::temp_output_path(out_path)?;
+    let mut cmd = std::process::Command::new("ffmpeg");
+    cmd.arg("-y").arg("-v").arg("error");
+    for clip_start in &clip_starts {
+        cmd.arg("-ss")
+            .arg(format!("{}s", clip_start))
+            .arg("-t")
+            .arg(format!("{}s", MINIVID_CLIP_SECS))
+            .arg("-i")
+            .arg(video_path);
+    }
+
+    // Scale each clip so the shorter side is MINIVID_DIMENSION, then concatenate
+    let mut filter = String::new();
+    for i in 0..clip_starts.len() {
+        filter += &format!(
+            "[{i}:v]scale=w='if(gt(iw,ih),-2,{dim})':h='if(gt(iw,ih),{dim},-2)',setsar=1,fps=24[v{i}];",
+            i = i,
+            dim = MINIVID_DIMENSION,
+        );
+    }
+    for i in 0..clip_starts.len() {
+        filter += &format!("[v{}]", i);
+    }
+    filter += &format!("concat=n={}:v=1:a=0[out]", clip_starts.len());
+
+    let output = cmd
+        .arg("-filter_complex")
+        .arg(filter)
+        .arg("-map")
+        .arg("[out]")
+        .arg("-an")
+        .arg("-c:v")
+        .arg("libvpx-vp9")
+        .arg("-b:v")
+        .arg("150k")
+        .arg("-deadline")
+        .arg("good")
+        .arg("-cpu-used")
+        .arg("4")
+        .arg(&temp_path)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "make_minivid failed for {:?}: {}",
+            video_path,
+            String::from_utf8_lossy(&output.stderr)
+        ).into());
+    }
+    temp_path.persist(out_path)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -124,6 +370,85 @@ mod tests {
         assert!((pick_preview_timestamp(2100.0) - 525.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_pick_candidate_timestamps() {
+        assert_eq!(pick_candidate_timestamps(600.0), vec![210.0, 180.0, 240.0, 150.0, 270.0]);
+
+        // Clamped to the video, without duplicates
+        assert_eq!(pick_candidate_timestamps(0.5), vec![0.0]);
+        let short = pick_candidate_timestamps(4.0);
+        assert!(short.iter().all(|t| (0.0..=3.0).contains(t)));
+    }
+
+    #[test]
+    fn test_score_frame() {
+        let width = 8;
+        let black = vec![0u8; width * 4];
+        let flat_gray = vec![128u8; width * 4];
+        let checkerboard: Vec<u8> = (0..width * 4)
+            .map(|i| if (i % width + i / width) % 2 == 0 { 60 } else { 180 })
+            .collect();
+        // Title card: white text on black
+        let title_card: Vec<u8> = (0..width * 4)
+            .map(|i| if i % width == 3 { 255 } else { 0 })
+            .collect();
+
+        assert_eq!(score_frame(&black, width), 0.0);
+        assert_eq!(score_frame(&flat_gray, width), 0.0);
+        assert!(score_frame(&checkerboard, width) > score_frame(&title_card, width));
+        assert_eq!(score_frame(&[], width), 0.0);
+    }
+
+    #[test]
+    fn test_is_preview_stale() {
+        let mut db_entry = DbEntry::default(1, crate::RepoPathBuf::from("video.mp4"));
+        let set_current = |db_entry: &mut DbEntry, timestamp_secs: f64, pinned: bool| {
+            db_entry.notes_generated = serde_json::json!({
+                "preview::timestamp_secs": timestamp_secs,
+                "preview::pinned": pinned,
+            });
+        };
+
+        // Old previews without a recorded frame are left alone
+        assert!(!is_preview_stale(&db_entry));
+
+        set_current(&mut db_entry, 30.0, false);
+        assert!(!is_preview_stale(&db_entry));
+
+        db_entry.notes_user = serde_json::json!({"preview_timestamp": 12.5});
+        assert!(is_preview_stale(&db_entry));
+        set_current(&mut db_entry, 12.5, true);
+        assert!(!is_preview_stale(&db_entry));
+
+        db_entry.notes_user = serde_json::json!({});
+        assert!(is_preview_stale(&db_entry));
+    }
+
+    #[test]
+    fn test_pick_minivid_clips() {
+        assert_eq!(pick_minivid_clips(5.0), vec![0.0]);
+
+        let clips = pick_minivid_clips(1000.0);
+        assert_eq!(clips.len(), MINIVID_NUM_CLIPS);
+        assert_eq!(clips[0], 50.0);
+        assert!((clips[MINIVID_NUM_CLIPS - 1] - (950.0 - MINIVID_CLIP_SECS)).abs() < 0.01);
+        assert!(clips.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_make_minivid() {
+        let root = testing::testdata_path("preview");
+        let dest_file = tempfile::Builder::new()
+            .suffix(".webm")
+            .tempfile()
+            .expect("temp file");
+
+        make_minivid(&root.join("short_video.mp4"), dest_file.path()).expect("make_minivid");
+        assert!(std::fs::metadata(dest_file.path()).expect("metadata").len() > 0);
+        // Shorter side is scaled to MINIVID_DIMENSION
+        assert_eq!(get_image_dimensions(dest_file.path()), (320, 240));
+    }
+
     #[test]
     fn test_make_preview_image() {
         let root = testing::testdata_path("preview");
@@ -133,8 +458,15 @@ mod tests {
             .expect("temp file");
         std::fs::remove_file(dest_file.path()).expect("remove temp file");
 
-        make_preview_image(&root.join("short_video.mp4"), dest_file.path());
+        let frame = make_preview_image(&root.join("short_video.mp4"), dest_file.path(), None)
+            .expect("make_preview_image");
         assert!(dest_file.path().exists());
+        assert!(!frame.pinned);
         assert_eq!(get_image_dimensions(dest_file.path()), (320, 240));
+
+        // Pinned timestamp is used as-is, and overwrites the existing preview
+        let frame = make_preview_image(&root.join("short_video.mp4"), dest_file.path(), Some(1.0))
+            .expect("make_preview_image");
+        assert_eq!(frame, PreviewFrame { timestamp_secs: 1.0, pinned: true });
     }
 }
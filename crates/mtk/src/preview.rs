@@ -16,6 +16,11 @@ fn preview_image_gfile(entry_id: i64) -> GeneratedFile {
 
 /// FFmpeg arguments for scaling and saving a preview image
 pub fn ffmpeg_preview_args(cmd: &mut std::process::Command) {
+    ffmpeg_preview_args_with_filter(cmd, None);
+}
+
+/// Like `ffmpeg_preview_args`, but applies `pre_filter` (e.g. a rotation) before scaling
+pub fn ffmpeg_preview_args_with_filter(cmd: &mut std::process::Command, pre_filter: Option<&str>) {
     // Resize the frame so that:
     // 1) The shorter dimension is ideally 300 pixels, but
     // 2) The longer dimension does not exceed 900 pixels
@@ -28,8 +33,13 @@ pub fn ffmpeg_preview_args(cmd: &mut std::process::Command) {
         maxd=crate::preview::PREVIEW_IMAGE_MAX_DIMENSION,
     );
 
+    let filter = match pre_filter {
+        Some(pre_filter) => format!("{},{}", pre_filter, scale_arg),
+        None => scale_arg,
+    };
+
     cmd.arg("-vf")
-        .arg(scale_arg)
+        .arg(filter)
         // Set the jpeg quality
         /*.arg("-q:v")
         .arg("8");*/
@@ -0,0 +1,135 @@
+use std::collections::{btree_map, BTreeMap};
+
+use crate::media::phash::hamming_distance;
+
+/// A BK-tree over 64-bit hashes, for finding all items within a Hamming
+/// distance of a query hash without comparing against every item.
+#[derive(Debug)]
+pub struct BkTree<T> {
+    root: Option<Node<T>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    hash: u64,
+    // Items with exactly this hash
+    items: Vec<T>,
+    children: BTreeMap<u32, Node<T>>,
+}
+
+impl<T> BkTree<T> {
+    pub fn new() -> Self {
+        BkTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, hash: u64, item: T) {
+        self.len += 1;
+
+        let Some(mut node) = self.root.as_mut() else {
+            self.root = Some(Node { hash, items: vec![item], children: BTreeMap::new() });
+            return;
+        };
+
+        loop {
+            let distance = hamming_distance(node.hash, hash);
+            if distance == 0 {
+                node.items.push(item);
+                return;
+            }
+
+            match node.children.entry(distance) {
+                btree_map::Entry::Vacant(slot) => {
+                    slot.insert(Node { hash, items: vec![item], children: BTreeMap::new() });
+                    return;
+                }
+                btree_map::Entry::Occupied(child) => node = child.into_mut(),
+            }
+        }
+    }
+
+    /// All items within `max_distance` of `hash`, closest first.
+    pub fn find(&self, hash: u64, max_distance: u32) -> Vec<(&T, u32)> {
+        let mut results = Vec::new();
+        let mut to_visit: Vec<&Node<T>> = self.root.iter().collect();
+
+        while let Some(node) = to_visit.pop() {
+            let distance = hamming_distance(node.hash, hash);
+            if distance <= max_distance {
+                results.extend(node.items.iter().map(|item| (item, distance)));
+            }
+
+            // By the triangle inequality, matches can only be under children
+            // whose distance from this node is within max_distance of ours
+            let low = distance.saturating_sub(max_distance);
+            let high = distance + max_distance;
+            to_visit.extend(node.children.range(low..=high).map(|(_, child)| child));
+        }
+
+        results.sort_by_key(|(_, distance)| *distance);
+        results
+    }
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_find_exact_and_near() {
+        let mut tree = BkTree::new();
+        tree.insert(0b0000, "a");
+        tree.insert(0b0001, "b");
+        tree.insert(0b0011, "c");
+        tree.insert(0b1111, "d");
+        tree.insert(0b0000, "a2");
+        assert_eq!(tree.len(), 5);
+
+        let exact: Vec<_> = tree.find(0b0000, 0).into_iter().map(|(item, _)| *item).collect();
+        assert_eq!(exact, vec!["a", "a2"]);
+
+        let near = tree.find(0b0000, 2);
+        assert_eq!(near.len(), 4);
+        assert_eq!(near.last(), Some(&(&"c", 2)));
+
+        assert!(BkTree::<&str>::new().find(0, 64).is_empty());
+    }
+
+    #[test]
+    fn test_find_matches_linear_scan() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let hashes: Vec<u64> = (0..500).map(|_| rng.random()).collect();
+
+        let mut tree = BkTree::new();
+        for (i, hash) in hashes.iter().enumerate() {
+            tree.insert(*hash, i);
+        }
+
+        for query in hashes.iter().take(20) {
+            for max_distance in [0, 20, 28] {
+                let mut found: Vec<usize> = tree.find(*query, max_distance).into_iter().map(|(i, _)| *i).collect();
+                found.sort();
+                let expected: Vec<usize> = (0..hashes.len())
+                    .filter(|i| hamming_distance(hashes[*i], *query) <= max_distance)
+                    .collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+}
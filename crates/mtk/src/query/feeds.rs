@@ -0,0 +1,229 @@
+//! Feeds for the home page: videos and documents that are partially watched or read,
+//! and new arrivals.
+
+use crate::catalog::Catalog;
+use crate::userdata::{HistoryDb, ViewHistory};
+use crate::{filetype, Entry, FileTree, RepoPathBuf};
+
+/// Videos watched for less than this many seconds don't count as started.
+pub const CONTINUE_MIN_TS: i64 = 10;
+/// Videos watched past this ratio of their duration count as finished.
+pub const CONTINUE_MAX_RATIO: f32 = 0.95;
+/// Documents read up to this ratio count as finished. Higher than for videos, since
+/// there are no end credits to skip.
+pub const CONTINUE_READING_MAX_RATIO: f32 = 0.98;
+
+/// In-progress videos, most recently viewed first.
+pub fn continue_watching(
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    history_db: &HistoryDb,
+    num_entries: usize,
+) -> Vec<Entry> {
+    let histories = history_db
+        .list_in_progress_videos(CONTINUE_MIN_TS, CONTINUE_MAX_RATIO)
+        .expect("list_in_progress_videos");
+    entries_for_histories(histories, file_tree, catalog, num_entries, filetype::is_video)
+}
+
+/// In-progress documents, most recently read first.
+pub fn continue_reading(
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    history_db: &HistoryDb,
+    num_entries: usize,
+) -> Vec<Entry> {
+    let histories = history_db
+        .list_in_progress_documents(0.0, CONTINUE_READING_MAX_RATIO)
+        .expect("list_in_progress_documents");
+    entries_for_histories(histories, file_tree, catalog, num_entries, filetype::is_document)
+}
+
+/// Entries for the first `num_entries` histories whose files still exist, aren't
+/// hidden, and match `is_file_type`.
+fn entries_for_histories(
+    histories: Vec<ViewHistory>,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    num_entries: usize,
+    is_file_type: fn(&std::path::Path) -> bool,
+) -> Vec<Entry> {
+    let mut results = Vec::new();
+    for history in histories {
+        if results.len() >= num_entries {
+            break;
+        }
+
+        let Some(db_entry) = catalog.get_by_id(history.entry_id) else {
+            continue;
+        };
+        if crate::browse::should_hide_entry(&db_entry) {
+            continue;
+        }
+
+        // The file may have been moved or deleted since it was viewed
+        let Ok(fs_entry) = file_tree.get_fs_entry(&db_entry.repo_path) else {
+            continue;
+        };
+        if !is_file_type(&fs_entry.file_path) {
+            continue;
+        }
+
+        results.push(Entry { fs: fs_entry, db: db_entry });
+    }
+
+    results
+}
+
+/// Files under `base_path`, most recently modified first. Only files that have been
+/// listed (and so cataloged) are included.
+pub fn recently_added(
+    base_path: &RepoPathBuf,
+    file_tree: &FileTree,
+    catalog: &Catalog,
+    num_entries: usize,
+) -> Vec<Entry> {
+    let mut results = Vec::new();
+    let mut offset = 0;
+    loop {
+        let db_entries = catalog.list_recently_modified(base_path, num_entries, offset);
+        if db_entries.is_empty() {
+            return results;
+        }
+        offset += db_entries.len();
+
+        for db_entry in db_entries {
+            if results.len() >= num_entries {
+                return results;
+            }
+            if crate::browse::should_hide_entry(&db_entry) {
+                continue;
+            }
+
+            // The file may have been moved or deleted since it was listed
+            let Ok(fs_entry) = file_tree.get_fs_entry(&db_entry.repo_path) else {
+                continue;
+            };
+            results.push(Entry { fs: fs_entry, db: db_entry });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use crate::testing;
+
+    fn write_file_with_age(file_root: &Path, entry_path: &str, age_secs: u64) {
+        let fs_path = file_root.join(entry_path);
+        std::fs::create_dir_all(fs_path.parent().unwrap()).expect("create_dir_all");
+        let file = std::fs::File::create(&fs_path).expect("create");
+        file.set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .expect("set_modified");
+    }
+
+    fn repo_paths(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.fs.repo_path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_recently_added() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        write_file_with_age(file_root.path(), "old.mp4", 3000);
+        write_file_with_age(file_root.path(), "dir1/newest.mp4", 10);
+        write_file_with_age(file_root.path(), "dir1/middle.jpg", 1000);
+        write_file_with_age(file_root.path(), "dir2/newer.mp4", 100);
+
+        write_file_with_age(file_root.path(), "deleted.mp4", 0);
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        crate::browse::list_recursive(&mut catalog, &file_tree, &RepoPathBuf::from(""))?;
+        std::fs::remove_file(file_root.path().join("deleted.mp4"))?;
+
+        let all = recently_added(&RepoPathBuf::from(""), &file_tree, &catalog, 10);
+        assert_eq!(
+            repo_paths(&all),
+            vec!["dir1/newest.mp4", "dir2/newer.mp4", "dir1/middle.jpg", "old.mp4"]
+        );
+
+        let limited = recently_added(&RepoPathBuf::from(""), &file_tree, &catalog, 2);
+        assert_eq!(repo_paths(&limited), vec!["dir1/newest.mp4", "dir2/newer.mp4"]);
+
+        let subdir = recently_added(&RepoPathBuf::from("dir1"), &file_tree, &catalog, 10);
+        assert_eq!(repo_paths(&subdir), vec!["dir1/newest.mp4", "dir1/middle.jpg"]);
+
+        // Files that haven't been listed yet aren't in the catalog
+        write_file_with_age(file_root.path(), "unlisted.mp4", 0);
+        assert_eq!(repo_paths(&recently_added(&RepoPathBuf::from(""), &file_tree, &catalog, 1)), vec!["dir1/newest.mp4"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_continue_watching() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        write_file_with_age(file_root.path(), "started.mp4", 0);
+        write_file_with_age(file_root.path(), "finished.mp4", 0);
+        write_file_with_age(file_root.path(), "image.jpg", 0);
+        write_file_with_age(file_root.path(), "deleted.mp4", 0);
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let mut history_db = HistoryDb::new_in_memory();
+
+        for (path, ts, ratio) in [
+            ("started.mp4", 100, 0.5),
+            ("finished.mp4", 200, 0.99),
+            ("image.jpg", 100, 0.5),
+            ("deleted.mp4", 100, 0.5),
+        ] {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            history_db.mark_viewed(entry.db.id, Some((ts, ratio)))?;
+        }
+        std::fs::remove_file(file_root.path().join("deleted.mp4"))?;
+
+        let entries = continue_watching(&file_tree, &mut catalog, &history_db, 10);
+        assert_eq!(repo_paths(&entries), vec!["started.mp4"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_continue_reading() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        write_file_with_age(file_root.path(), "started.pdf", 0);
+        write_file_with_age(file_root.path(), "finished.epub", 0);
+        write_file_with_age(file_root.path(), "notes.md", 0);
+        write_file_with_age(file_root.path(), "video.mp4", 0);
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let mut history_db = HistoryDb::new_in_memory();
+
+        for (path, page, ratio) in [
+            ("started.pdf", Some(10), 0.3),
+            ("finished.epub", Some(500), 0.99),
+            ("notes.md", None, 0.5),
+            ("video.mp4", None, 0.5),
+        ] {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            history_db.mark_read(entry.db.id, page, ratio)?;
+        }
+
+        // All read in the same second, so in no particular order
+        let entries = continue_reading(&file_tree, &mut catalog, &history_db, 10);
+        let mut paths = repo_paths(&entries);
+        paths.sort();
+        assert_eq!(paths, vec!["notes.md", "started.pdf"]);
+
+        Ok(())
+    }
+}
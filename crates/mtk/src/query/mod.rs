@@ -2,3 +2,8 @@ mod sampler;
 pub mod surprise;
 
 pub mod search;
+pub mod feeds;
+pub mod series;
+pub mod similar;
+pub mod bk_tree;
+pub mod visual;
@@ -1,5 +1,5 @@
-use rand::rngs::ThreadRng;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -87,18 +87,27 @@ impl<T: Clone> Reservoir<T> {
 pub struct WeightedGroupSampler<T> {
     sample_size: usize,
     reservoir: Reservoir<T>,
-    rng: ThreadRng,
+    rng: StdRng,
 }
 
 impl<T: Clone> WeightedGroupSampler<T> {
     pub fn new(sample_size: usize) -> Self {
+        Self::with_rng(sample_size, StdRng::from_rng(&mut rand::rng()))
+    }
+
+    /// A sampler that picks the same sample every time, given the same items in the same order.
+    pub fn with_seed(sample_size: usize, seed: u64) -> Self {
+        Self::with_rng(sample_size, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(sample_size: usize, rng: StdRng) -> Self {
         Self {
             sample_size,
             reservoir: Reservoir {
                 items: Vec::with_capacity(sample_size),
                 group_counts: HashMap::new(),
             },
-            rng: rand::rng(),
+            rng,
         }
     }
 
@@ -360,4 +369,44 @@ mod tests {
         assert!(sample.contains(&3));
         assert_eq!(false, sample.contains(&1));
     }
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let sample_with_seed = |seed| {
+            let mut sampler = WeightedGroupSampler::with_seed(5, seed);
+            for i in 0..100 {
+                sampler.add(i, 1.0 + (i % 7) as f64, (i % 10).to_string());
+            }
+            sampler.get_sample()
+        };
+
+        assert_eq!(sample_with_seed(42), sample_with_seed(42));
+        assert_ne!(sample_with_seed(42), sample_with_seed(43));
+    }
+
+    #[test]
+    fn test_seeded_group_diversity() {
+        for seed in 0..20 {
+            let mut sampler = WeightedGroupSampler::with_seed(4, seed);
+
+            // A few low-weight singleton groups, then one big high-weight group
+            for group in ["A", "B", "C"] {
+                sampler.add((group, 0), 0.01, group.to_string());
+            }
+            for i in 0..100 {
+                sampler.add(("big", i), 100.0, "big".to_string());
+            }
+
+            // The big group can only displace its own items, so every group
+            // keeps its one representative
+            let sample = sampler.get_sample();
+            assert_eq!(sample.len(), 4);
+            for group in ["A", "B", "C", "big"] {
+                assert!(
+                    sample.iter().any(|(g, _)| *g == group),
+                    "seed {}: {:?} is missing group {}", seed, sample, group
+                );
+            }
+        }
+    }
 }
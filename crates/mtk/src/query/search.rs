@@ -1,14 +1,69 @@
-use crate::catalog::Catalog;
+use crate::catalog::{Catalog, DbEntry};
+use crate::media::image::image_info;
 use crate::{Entry, RepoPathBuf};
 use crate::FileTree;
 
+/// A `field:value` term in a search query, matched against image metadata.
+#[derive(Debug, PartialEq)]
+enum Filter {
+    /// Camera make or model contains the text (case-insensitive)
+    Camera(String),
+    Lens(String),
+    /// Capture date starts with the prefix, e.g. "2023" or "2023-08"
+    Taken(String),
+    HasGps,
+}
+
+impl Filter {
+    fn parse(term: &str) -> Option<Filter> {
+        let (field, value) = term.split_once(':')?;
+        match (field, value) {
+            ("camera", v) if !v.is_empty() => Some(Filter::Camera(v.to_lowercase())),
+            ("lens", v) if !v.is_empty() => Some(Filter::Lens(v.to_lowercase())),
+            ("taken", v) if !v.is_empty() => Some(Filter::Taken(v.to_string())),
+            ("has", "gps") => Some(Filter::HasGps),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, db_entry: &DbEntry) -> bool {
+        let Some(info) = image_info::read_image_info(db_entry) else {
+            return false;
+        };
+        let contains = |field: &Option<String>, text: &str| field.as_ref().is_some_and(|f| f.to_lowercase().contains(text));
+        match self {
+            Filter::Camera(text) => contains(&info.camera_make, text) || contains(&info.camera_model, text),
+            Filter::Lens(text) => contains(&info.lens, text),
+            Filter::Taken(prefix) => info.capture_date.is_some_and(|date| date.starts_with(prefix.as_str())),
+            Filter::HasGps => info.gps.is_some(),
+        }
+    }
+}
+
+/// Split a query into its filters and the remaining text (a regex).
+fn parse_query(query: &str) -> (Vec<Filter>, String) {
+    let mut filters = Vec::new();
+    let mut text_terms = Vec::new();
+    for term in query.split_whitespace() {
+        match Filter::parse(term) {
+            Some(filter) => filters.push(filter),
+            None => text_terms.push(term),
+        }
+    }
+    (filters, text_terms.join(" "))
+}
+
+/// Search for entries whose file name or notes match `query`, a case-insensitive
+/// regex. The query can also contain filters on image metadata: `camera:<text>`,
+/// `lens:<text>`, `taken:<date prefix>` and `has:gps`.
 pub fn search(
     file_tree: &FileTree,
     catalog: &mut Catalog,
     search_root: &RepoPathBuf,
     query: &str,
 ) -> Vec<Entry> {
-    let re_string = format!("(?i){}", query);
+    let (filters, text) = parse_query(query);
+    let re_string = format!("(?i){}", text);
     let re = regex::Regex::new(&re_string).unwrap();
     println!("Searching for \"{}\"", query);
 
@@ -21,6 +76,10 @@ pub fn search(
         }
 
         let db_entry = catalog.get_or_create(&fs_entry);
+        if !filters.iter().all(|filter| filter.matches(&db_entry)) {
+            continue;
+        }
+
         let is_match = if re.is_match(&fs_entry.file_name) {
             true
         } else {
@@ -92,6 +151,55 @@ mod tests {
         assert!(results_contain(&s_berlin, "Videos/berlin_wall.mp4"));
     }
 
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query("beach camera:Canon taken:2023-08 sunset"),
+            (vec![Filter::Camera("canon".to_string()), Filter::Taken("2023-08".to_string())], "beach sunset".to_string())
+        );
+        // Unknown fields are part of the text
+        assert_eq!(parse_query("has:gps foo:bar"), (vec![Filter::HasGps], "foo:bar".to_string()));
+    }
+
+    #[test]
+    fn test_search_image_info() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for path in ["canon.jpg", "fuji.jpg", "no_info.jpg"] {
+            std::fs::write(file_root.path().join(path), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        let infos = [
+            ("canon.jpg", image_info::ImageInfo {
+                capture_date: Some("2023-08-14T16:02:11".to_string()),
+                camera_make: Some("Canon".to_string()),
+                camera_model: Some("Canon EOS R6".to_string()),
+                ..Default::default()
+            }),
+            ("fuji.jpg", image_info::ImageInfo {
+                capture_date: Some("2021-05-06T07:08:09".to_string()),
+                camera_make: Some("FUJIFILM".to_string()),
+                gps: Some(image_info::GpsPosition { latitude: 1.0, longitude: 2.0, altitude: None }),
+                ..Default::default()
+            }),
+        ];
+        for (path, info) in infos {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            crate::catalog::generated_notes::update(&mut catalog, entry.db.id, image_info::IMAGE_INFO_GROUP_NAME, &info);
+        }
+
+        let root = RepoPathBuf::from("");
+        let paths = |entries: Vec<Entry>| -> Vec<String> { entries.into_iter().map(|e| e.fs.repo_path.0).collect() };
+        assert_eq!(paths(search(&file_tree, &mut catalog, &root, "camera:eos")), vec!["canon.jpg"]);
+        assert_eq!(paths(search(&file_tree, &mut catalog, &root, "taken:2021")), vec!["fuji.jpg"]);
+        assert_eq!(paths(search(&file_tree, &mut catalog, &root, "has:gps")), vec!["fuji.jpg"]);
+        assert!(search(&file_tree, &mut catalog, &root, "camera:canon fuji").is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_search_no_metadata() {
         let conn = testing::in_memory_conn("");
@@ -0,0 +1,177 @@
+//! Episodes in a series folder, and where to carry on watching from.
+
+use crate::browse::sort::natural_cmp;
+use crate::catalog::{Catalog, SpecialEntryType};
+use crate::userdata::{HistoryDb, ViewHistory};
+use crate::{filetype, Entry, FileTree, RepoPathBuf};
+
+use super::feeds::CONTINUE_MAX_RATIO;
+
+/// The videos in `dir_path`, in episode order.
+pub fn episodes(
+    dir_path: &RepoPathBuf,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let listing = crate::browse::listdir(catalog, file_tree, dir_path)?;
+    let mut episodes: Vec<Entry> = listing
+        .visible
+        .into_iter()
+        .filter(|entry| filetype::is_video(&entry.fs.file_path))
+        .collect();
+    // File names rather than titles, since they're where the episode numbers are
+    episodes.sort_by(|a, b| natural_cmp(&a.fs.file_name, &b.fs.file_name));
+    Ok(episodes)
+}
+
+/// Whether the video has been watched to the end
+pub fn is_watched(history: &ViewHistory) -> bool {
+    history
+        .video_history
+        .as_ref()
+        .is_some_and(|video_history| video_history.farthest_ts_ratio >= CONTINUE_MAX_RATIO)
+}
+
+/// Index of the episode to watch next: the last one started if it isn't finished,
+/// otherwise the one after it. None once the last episode has been watched.
+pub fn next_unwatched(episodes: &[Entry], history_db: &HistoryDb) -> Option<usize> {
+    let histories: Vec<ViewHistory> = episodes
+        .iter()
+        .map(|episode| history_db.get(episode.db.id).expect("HistoryDb::get"))
+        .collect();
+    let Some(last_started) = histories.iter().rposition(|history| history.video_history.is_some()) else {
+        return if episodes.is_empty() { None } else { Some(0) };
+    };
+
+    if !is_watched(&histories[last_started]) {
+        Some(last_started)
+    } else if last_started + 1 < episodes.len() {
+        Some(last_started + 1)
+    } else {
+        None
+    }
+}
+
+/// Where an episode is in its series
+pub struct SeriesPosition {
+    pub series_path: RepoPathBuf,
+    pub episodes: Vec<Entry>,
+    pub index: usize,
+}
+
+impl SeriesPosition {
+    pub fn prev(&self) -> Option<&Entry> {
+        self.index.checked_sub(1).map(|index| &self.episodes[index])
+    }
+
+    pub fn next(&self) -> Option<&Entry> {
+        self.episodes.get(self.index + 1)
+    }
+
+    /// The first later episode that hasn't been watched to the end
+    pub fn next_unwatched(&self, history_db: &HistoryDb) -> Option<&Entry> {
+        self.episodes[self.index + 1..].iter().find(|episode| {
+            !is_watched(&history_db.get(episode.db.id).expect("HistoryDb::get"))
+        })
+    }
+}
+
+/// The entry's place in its series, if it's a video in a series folder.
+pub fn series_position(
+    entry: &Entry,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+) -> Result<Option<SeriesPosition>, Box<dyn std::error::Error>> {
+    if !filetype::is_video(&entry.fs.file_path) {
+        return Ok(None);
+    }
+    let Some(series_path) = entry.fs.repo_path.parent() else {
+        return Ok(None);
+    };
+    let is_series = catalog
+        .path_to_id(&series_path)
+        .and_then(|id| catalog.get_by_id(id))
+        .is_some_and(|dir| dir.special_type == Some(SpecialEntryType::SeriesDir));
+    if !is_series {
+        return Ok(None);
+    }
+
+    let episodes = episodes(&series_path, file_tree, catalog)?;
+    let Some(index) = episodes.iter().position(|episode| episode.db.id == entry.db.id) else {
+        return Ok(None);
+    };
+    Ok(Some(SeriesPosition { series_path, episodes, index }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    #[test]
+    fn test_episodes_and_next_unwatched() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for name in ["Show - 10.mp4", "Show - 2.mp4", "Show - 1.mp4", "cover.jpg"] {
+            std::fs::write(file_root.path().join(name), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let mut history_db = HistoryDb::new_in_memory();
+
+        let episodes = episodes(&RepoPathBuf::from(""), &file_tree, &mut catalog)?;
+        let names: Vec<&str> = episodes.iter().map(|e| e.fs.file_name.as_str()).collect();
+        assert_eq!(names, vec!["Show - 1.mp4", "Show - 2.mp4", "Show - 10.mp4"]);
+        assert_eq!(next_unwatched(&episodes, &history_db), Some(0));
+
+        // Part way through the second episode
+        history_db.mark_viewed(episodes[1].db.id, Some((100, 0.5)))?;
+        assert_eq!(next_unwatched(&episodes, &history_db), Some(1));
+
+        history_db.mark_viewed(episodes[1].db.id, Some((200, 0.99)))?;
+        assert_eq!(next_unwatched(&episodes, &history_db), Some(2));
+
+        history_db.mark_viewed(episodes[2].db.id, Some((200, 0.99)))?;
+        assert_eq!(next_unwatched(&episodes, &history_db), None);
+
+        assert_eq!(next_unwatched(&[], &history_db), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_series_position() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::create_dir(file_root.path().join("Show"))?;
+        for name in ["Show - 1.mp4", "Show - 2.mp4", "Show - 3.mp4"] {
+            std::fs::write(file_root.path().join("Show").join(name), "")?;
+        }
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let mut history_db = HistoryDb::new_in_memory();
+
+        let first = testing::entry_for("Show/Show - 1.mp4", &file_tree, &mut catalog)?;
+        assert!(series_position(&first, &file_tree, &mut catalog)?.is_none());
+
+        let show = testing::entry_for("Show", &file_tree, &mut catalog)?;
+        catalog.set_special_type(show.db.id, Some(SpecialEntryType::SeriesDir));
+        let position = series_position(&first, &file_tree, &mut catalog)?.expect("in a series");
+        assert_eq!(position.series_path, RepoPathBuf::from("Show"));
+        assert!(position.prev().is_none());
+        assert_eq!(position.next().map(|e| e.fs.file_name.as_str()), Some("Show - 2.mp4"));
+
+        // Skips episodes that have already been watched
+        history_db.mark_viewed(position.episodes[1].db.id, Some((200, 0.99)))?;
+        assert_eq!(position.next_unwatched(&history_db).map(|e| e.fs.file_name.as_str()), Some("Show - 3.mp4"));
+
+        let last = testing::entry_for("Show/Show - 3.mp4", &file_tree, &mut catalog)?;
+        let position = series_position(&last, &file_tree, &mut catalog)?.expect("in a series");
+        assert_eq!(position.prev().map(|e| e.fs.file_name.as_str()), Some("Show - 2.mp4"));
+        assert!(position.next().is_none());
+        assert!(position.next_unwatched(&history_db).is_none());
+
+        Ok(())
+    }
+}
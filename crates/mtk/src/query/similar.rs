@@ -0,0 +1,332 @@
+//! "More like this": rank other entries by how much they have in common with a target entry.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::generated_notes;
+use crate::catalog::{Catalog, DbEntry};
+use crate::media::video;
+use crate::userdata::HistoryDb;
+use crate::{Entry, FileTree, RepoPathBuf};
+
+use super::sampler::WeightedGroupSampler;
+
+const SHARED_TAG_SCORE: f64 = 2.0;
+const SAME_UPLOADER_SCORE: f64 = 3.0;
+const SAME_PARENT_SCORE: f64 = 1.0;
+const DURATION_SCORE: f64 = 1.0;
+const RESOLUTION_SCORE: f64 = 0.5;
+const CO_VIEWED_SCORE: f64 = 1.5;
+
+/// Entries last viewed within this many seconds of each other count as co-viewed.
+pub const CO_VIEW_WINDOW_SECS: i64 = 3600;
+
+/// Similar entries are worked out by the "similar" job, since it means comparing
+/// against every other entry.
+pub const SIMILAR_GROUP_NAME: &str = "similar";
+/// How many similar entries the job keeps
+pub const NUM_STORED_SIMILAR: usize = 100;
+/// Redo the list after this long, to pick up new entries and viewing history
+pub const SIMILAR_MAX_AGE_SECS: u64 = 7 * 24 * 3600;
+
+/// The "similar" generated notes group
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SimilarEntries {
+    pub entry_ids: Vec<i64>,
+}
+
+/// The parts of an entry that are compared when scoring similarity.
+#[derive(Clone, Debug, Default)]
+struct Features {
+    tags: Vec<String>,
+    uploader: Option<String>,
+    parent: String,
+    duration_secs: Option<f64>,
+    // Shorter side of a video, in pixels
+    min_dimension: Option<i64>,
+}
+
+impl Features {
+    fn from(entry: &Entry) -> Features {
+        let video_info =
+            generated_notes::read::<video::VideoInfo>(&entry.db, video::VIDEO_INFO_GROUP_NAME);
+        Features {
+            tags: entry.db.tags(),
+            uploader: entry.db.uploader(),
+            parent: entry.fs.repo_path.parent_or_empty().0,
+            duration_secs: video_info.as_ref().map(|info| info.duration_secs),
+            min_dimension: video_info.as_ref().map(|info| std::cmp::min(info.width, info.height) as i64),
+        }
+    }
+}
+
+/// How much each kind of similarity contributed to an entry's score.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SimilarityScore {
+    pub tags: f64,
+    pub uploader: f64,
+    pub parent: f64,
+    pub duration: f64,
+    pub resolution: f64,
+    pub co_viewed: f64,
+    pub total: f64,
+}
+
+fn score(target: &Features, candidate: &Features, co_viewed: bool) -> SimilarityScore {
+    let shared_tags = candidate.tags.iter().filter(|tag| target.tags.contains(tag)).count();
+    let tags = shared_tags as f64 * SHARED_TAG_SCORE;
+
+    let uploader = match (&target.uploader, &candidate.uploader) {
+        (Some(a), Some(b)) if a == b => SAME_UPLOADER_SCORE,
+        _ => 0.0,
+    };
+
+    let parent = if target.parent == candidate.parent { SAME_PARENT_SCORE } else { 0.0 };
+
+    // 1.0 for identical durations, falling to 0.0 as one becomes twice as long as the other
+    let duration = match (target.duration_secs, candidate.duration_secs) {
+        (Some(a), Some(b)) if a > 0.0 && b > 0.0 => {
+            let ratio = a.min(b) / a.max(b);
+            ((ratio - 0.5) * 2.0).max(0.0) * DURATION_SCORE
+        }
+        _ => 0.0,
+    };
+
+    let resolution = match (target.min_dimension, candidate.min_dimension) {
+        (Some(a), Some(b)) if a == b => RESOLUTION_SCORE,
+        _ => 0.0,
+    };
+
+    let co_viewed = if co_viewed { CO_VIEWED_SCORE } else { 0.0 };
+
+    SimilarityScore {
+        tags,
+        uploader,
+        parent,
+        duration,
+        resolution,
+        co_viewed,
+        total: tags + uploader + parent + duration + resolution + co_viewed,
+    }
+}
+
+/// Other entries that are similar to `target`, most similar first. Compares against
+/// every cataloged entry in the repo, so this is run by the "similar" job rather than
+/// for each page view; see `read_similar_entries`.
+///
+/// Entries are sampled by score, grouped by parent directory so that a single
+/// large directory doesn't fill the whole list. The sample is seeded by the
+/// target's ID, so it's stable between runs.
+pub fn similar_entries(
+    target: &Entry,
+    file_tree: &FileTree,
+    catalog: &Catalog,
+    history_db: &HistoryDb,
+    num_entries: usize,
+) -> Vec<(Entry, SimilarityScore)> {
+    let target_features = Features::from(target);
+    let co_viewed: HashSet<i64> = history_db
+        .list_viewed_near(target.db.id, CO_VIEW_WINDOW_SECS)
+        .expect("list_viewed_near")
+        .into_iter()
+        .collect();
+
+    let mut sampler = WeightedGroupSampler::with_seed(num_entries, target.db.id as u64);
+    let mut candidates = Vec::new();
+    let mut children: Vec<_> = file_tree
+        .list_recursive(&RepoPathBuf::from(""))
+        .expect("list_recursive")
+        .filter(|child| !child.is_metadata_file && !child.file_type.is_dir)
+        .collect();
+    // Directory listing order isn't stable, but the sample depends on it
+    children.sort_by(|a, b| a.repo_path.cmp(&b.repo_path));
+    for child in children {
+        if child.repo_path == target.fs.repo_path {
+            continue;
+        }
+
+        // Entries that haven't been listed yet have no notes to compare anyway
+        let Some(db_entry) = catalog.get_by_path(&child.repo_path) else {
+            continue;
+        };
+        if crate::browse::should_hide_entry(&db_entry) {
+            continue;
+        }
+
+        let entry = Entry { fs: child, db: db_entry };
+        let s = score(&target_features, &Features::from(&entry), co_viewed.contains(&entry.db.id));
+        if s.total <= 0.0 {
+            continue;
+        }
+
+        // Square the score so that strong matches win out over many weak ones
+        sampler.add(candidates.len(), s.total * s.total, entry.fs.repo_path.parent_or_empty().0);
+        candidates.push((entry, s));
+    }
+
+    let mut sampled: Vec<_> = sampler
+        .get_sample()
+        .into_iter()
+        .map(|idx| candidates[idx].clone())
+        .collect();
+    sampled.sort_by(|(_, a), (_, b)| b.total.total_cmp(&a.total));
+    sampled
+}
+
+/// Whether the "similar" job should (re)make the list for `entry`.
+pub fn needs_update(entry: &Entry) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("duration_since")
+        .as_secs();
+    let is_old = generated_notes::last_update(&entry.db, SIMILAR_GROUP_NAME)
+        .is_none_or(|last_update| now.saturating_sub(last_update) > SIMILAR_MAX_AGE_SECS);
+    is_old || generated_notes::needs_update(entry, SIMILAR_GROUP_NAME)
+}
+
+/// Up to `num_entries` of the similar entries found by the "similar" job, most similar
+/// first. Empty if the job hasn't run yet.
+pub fn read_similar_entries(
+    target: &DbEntry,
+    file_tree: &FileTree,
+    catalog: &Catalog,
+    num_entries: usize,
+) -> Vec<Entry> {
+    let Some(similar) = generated_notes::read::<SimilarEntries>(target, SIMILAR_GROUP_NAME) else {
+        return Vec::new();
+    };
+
+    let mut results = Vec::new();
+    for entry_id in similar.entry_ids {
+        if results.len() >= num_entries {
+            break;
+        }
+
+        let Some(db_entry) = catalog.get_by_id(entry_id) else {
+            continue;
+        };
+        if crate::browse::should_hide_entry(&db_entry) {
+            continue;
+        }
+        // The file may have been moved or deleted since the job ran
+        let Ok(fs_entry) = file_tree.get_fs_entry(&db_entry.repo_path) else {
+            continue;
+        };
+        results.push(Entry { fs: fs_entry, db: db_entry });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::catalog::sqlite_catalog::WhichNotes;
+    use crate::testing;
+
+    fn features(tags: &[&str], uploader: Option<&str>, parent: &str) -> Features {
+        Features {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            uploader: uploader.map(|u| u.to_string()),
+            parent: parent.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_score_tags_and_uploader() {
+        let target = features(&["a", "b"], Some("chan"), "x");
+
+        let unrelated = score(&target, &features(&["c"], Some("other"), "y"), false);
+        assert_eq!(unrelated.total, 0.0);
+
+        let shared = score(&target, &features(&["a", "b", "c"], Some("chan"), "y"), false);
+        assert_eq!(shared.tags, 2.0 * SHARED_TAG_SCORE);
+        assert_eq!(shared.uploader, SAME_UPLOADER_SCORE);
+        assert_eq!(shared.parent, 0.0);
+
+        let sibling = score(&target, &features(&[], None, "x"), true);
+        assert_eq!(sibling.total, SAME_PARENT_SCORE + CO_VIEWED_SCORE);
+    }
+
+    #[test]
+    fn test_score_duration() {
+        let with_duration = |secs| Features {
+            duration_secs: Some(secs),
+            ..Default::default()
+        };
+        let target = with_duration(100.0);
+
+        assert_eq!(score(&target, &with_duration(100.0), false).duration, DURATION_SCORE);
+        assert!(score(&target, &with_duration(80.0), false).duration > score(&target, &with_duration(60.0), false).duration);
+        assert_eq!(score(&target, &with_duration(300.0), false).duration, 0.0);
+    }
+
+    #[test]
+    fn test_similar_entries() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for path in ["show/1.mp4", "show/2.mp4", "show/3.mp4", "other/tagged.mp4", "other/unrelated.mp4", "unlisted/4.mp4"] {
+            let fs_path = file_root.path().join(path);
+            std::fs::create_dir_all(fs_path.parent().unwrap())?;
+            std::fs::write(&fs_path, "")?;
+        }
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let history_db = HistoryDb::new_in_memory();
+        for dir in ["show", "other"] {
+            crate::browse::list_recursive(&mut catalog, &file_tree, &RepoPathBuf::from(dir))?;
+        }
+
+        let tagged = testing::entry_for("other/tagged.mp4", &file_tree, &mut catalog)?;
+        catalog.set_notes_json(tagged.db.id, WhichNotes::User, r#"{"tags": ["cats", "dogs"]}"#);
+        let target = testing::entry_for("show/1.mp4", &file_tree, &mut catalog)?;
+        catalog.set_notes_json(target.db.id, WhichNotes::User, r#"{"tags": ["cats", "dogs"]}"#);
+        let target = testing::entry_for("show/1.mp4", &file_tree, &mut catalog)?;
+
+        let similar = similar_entries(&target, &file_tree, &catalog, &history_db, 10);
+        let paths: Vec<&str> = similar.iter().map(|(e, _)| e.fs.repo_path.as_str()).collect();
+        // Two shared tags outrank the same directory; unrelated and uncataloged entries are left out
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], "other/tagged.mp4");
+        assert!(paths.contains(&"show/2.mp4"));
+        assert!(paths.contains(&"show/3.mp4"));
+        assert!(!catalog.contains_path(&RepoPathBuf::from("unlisted/4.mp4")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_similar_entries() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for path in ["1.mp4", "2.mp4", "3.mp4", "deleted.mp4"] {
+            std::fs::write(file_root.path().join(path), "")?;
+        }
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        let target = testing::entry_for("1.mp4", &file_tree, &mut catalog)?;
+        assert!(needs_update(&target));
+        assert!(read_similar_entries(&target.db, &file_tree, &catalog, 10).is_empty());
+
+        let mut entry_ids = Vec::new();
+        for path in ["3.mp4", "deleted.mp4", "2.mp4"] {
+            entry_ids.push(testing::entry_for(path, &file_tree, &mut catalog)?.db.id);
+        }
+        std::fs::remove_file(file_root.path().join("deleted.mp4"))?;
+        generated_notes::update(&mut catalog, target.db.id, SIMILAR_GROUP_NAME, &SimilarEntries { entry_ids });
+
+        let target = testing::entry_for("1.mp4", &file_tree, &mut catalog)?;
+        assert!(!needs_update(&target));
+        let similar = read_similar_entries(&target.db, &file_tree, &catalog, 10);
+        let paths: Vec<&str> = similar.iter().map(|e| e.fs.repo_path.as_str()).collect();
+        assert_eq!(paths, vec!["3.mp4", "2.mp4"]);
+        assert_eq!(read_similar_entries(&target.db, &file_tree, &catalog, 1).len(), 1);
+
+        Ok(())
+    }
+}
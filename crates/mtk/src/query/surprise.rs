@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+
 use crate::catalog::Catalog;
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
-use crate::Entry;
+use crate::catalog::generated_notes;
+use crate::media::{audio, video};
+use crate::vault::SurpriseProfile;
+use crate::{filetype, Entry};
 use crate::{FileTree, RepoPathBuf};
 use crate::userdata::HistoryDb;
 
@@ -12,6 +18,9 @@ struct WeightInputs {
     days_since_viewed: Option<i64>,
     rating: Option<i64>,
     seconds_since_mod: i64,
+    tags: Vec<String>,
+    file_type: &'static str,
+    duration_secs: Option<f64>,
 
     descendant_weight: f64,
 }
@@ -22,6 +31,9 @@ impl Default for WeightInputs {
             days_since_viewed: None,
             rating: None,
             seconds_since_mod: 0,
+            tags: Vec::new(),
+            file_type: "other",
+            duration_secs: None,
 
             descendant_weight: 1.0,
         }
@@ -57,20 +69,81 @@ impl WeightInputsExtractor for StandardInputs<'_> {
                 .num_seconds()
         };
 
+        let file_path = &entry.fs.file_path;
+        let file_type = if filetype::is_video(file_path) {
+            "video"
+        } else if filetype::is_image(file_path) {
+            "image"
+        } else if filetype::is_audio(file_path) {
+            "audio"
+        } else if filetype::is_document(file_path) {
+            "document"
+        } else {
+            "other"
+        };
+
+        let duration_secs = generated_notes::read::<video::VideoInfo>(&entry.db, video::VIDEO_INFO_GROUP_NAME)
+            .map(|info| info.duration_secs)
+            .or_else(|| audio::read_audio_info(&entry.db).map(|info| info.duration_secs));
+
         WeightInputs {
             days_since_viewed: days,
             rating: entry.db.rating(),
             seconds_since_mod: mod_seconds,
+            tags: entry.db.tags(),
+            file_type,
+            duration_secs,
             descendant_weight: 1.0,
         }
     }
 }
 
-type WeightComputer = fn(&WeightInputs) -> f64;
+/// How each input contributed to an entry's surprise weight.
+///
+/// `weight = (history + rating + recency) * tag_boost * descendant`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct WeightBreakdown {
+    pub history: f64,
+    pub rating: f64,
+    pub recency: f64,
+    pub tag_boost: f64,
+    pub descendant: f64,
+    pub weight: f64,
+}
+
+/// Whether the entry passes the profile's type, history, and duration filters.
+fn passes_filters(profile: &SurpriseProfile, inputs: &WeightInputs) -> bool {
+    if !profile.file_types.is_empty() && !profile.file_types.iter().any(|t| t == inputs.file_type) {
+        return false;
+    }
+
+    if profile.unviewed_only && inputs.days_since_viewed.is_some() {
+        return false;
+    }
+
+    if profile.min_duration_secs.is_some() || profile.max_duration_secs.is_some() {
+        let Some(duration) = inputs.duration_secs else {
+            return false;
+        };
+        if profile.min_duration_secs.is_some_and(|min| duration < min) {
+            return false;
+        }
+        if profile.max_duration_secs.is_some_and(|max| duration > max) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Compute the weight for an entry, or None if the profile excludes it.
+fn compute_weight(profile: &SurpriseProfile, inputs: &WeightInputs) -> Option<WeightBreakdown> {
+    if !passes_filters(profile, inputs) {
+        return None;
+    }
 
-fn surprise_weight(inputs: &WeightInputs) -> f64 {
-    let hist_score = if let Some(days_ago) = inputs.days_since_viewed {
-        let x = (days_ago as f64) / (720.0 / 2.95);
+    let history = if let Some(days_ago) = inputs.days_since_viewed {
+        let x = (days_ago as f64) / (profile.history_scale_days / 2.95);
         let sig = 1.0 / (1.0 + (-x).exp());
         (sig - 0.5) * 2.0
     } else {
@@ -79,55 +152,82 @@ fn surprise_weight(inputs: &WeightInputs) -> f64 {
     };
 
     let rating = inputs.rating.unwrap_or(0);
-    let rating_score = 1.0 / (1.0 + std::f64::consts::E.powi((-rating).try_into().expect("rating to i32")));
+    let rating = 1.0 / (1.0 + std::f64::consts::E.powi((-rating).try_into().expect("rating to i32")));
 
-    (hist_score + rating_score) * inputs.descendant_weight
-}
+    let recency = if profile.recency_boost > 0.0 {
+        // Assigns a high weight to recently modified entries, which dominates the other scores
+        let x = (inputs.seconds_since_mod as f64) / ((profile.recency_halflife_days * 86400.0) / 1.1);
+        let sig = 1.0 / (1.0 + (-x).exp());
+        (1.0 - sig) * 2.0 * profile.recency_boost
+    } else {
+        0.0
+    };
 
-fn recent_mod_weight(inputs: &WeightInputs) -> f64 {
-    // Assigns a high weight to recently modified entries, then combines with low weight from surprise_weight
-    let x = (inputs.seconds_since_mod as f64) / ((30.0 * 86400.0) / 1.1);
-    let sig = 1.0 / (1.0 + (-x).exp());
-    let recency_score = ((1.0 - sig) * 2.0) * 50.0;
+    let tag_boost: f64 = inputs
+        .tags
+        .iter()
+        .filter_map(|tag| profile.tag_boosts.get(tag))
+        .product();
+
+    let weight = (history + rating + recency) * tag_boost * inputs.descendant_weight;
+    if weight <= 0.0 {
+        return None;
+    }
 
-    (recency_score * inputs.descendant_weight) + surprise_weight(inputs)
+    Some(WeightBreakdown {
+        history,
+        rating,
+        recency,
+        tag_boost,
+        descendant: inputs.descendant_weight,
+        weight,
+    })
 }
 
 struct EntrySampler<'a> {
     inputs: &'a dyn WeightInputsExtractor,
-    weight_computer: Box<WeightComputer>,
+    profile: &'a SurpriseProfile,
     weighted_sampler: WeightedGroupSampler<RepoPathBuf>,
+    // Only populated when explaining
+    breakdowns: Option<HashMap<RepoPathBuf, WeightBreakdown>>,
 }
 
 impl EntrySampler<'_> {
     fn new<'a>(
         inputs: &'a dyn WeightInputsExtractor,
         max_entries: usize,
-        recency_weight: bool,
+        profile: &'a SurpriseProfile,
+        seed: Option<u64>,
     ) -> EntrySampler<'a> {
-        let computer = if recency_weight {
-            recent_mod_weight as WeightComputer
-        } else {
-            surprise_weight as WeightComputer
+        let weighted_sampler = match seed {
+            Some(seed) => WeightedGroupSampler::with_seed(max_entries, seed),
+            None => WeightedGroupSampler::new(max_entries),
         };
 
         EntrySampler {
             inputs: inputs,
-            weight_computer: Box::new(computer),
-            weighted_sampler: WeightedGroupSampler::new(max_entries),
+            profile,
+            weighted_sampler,
+            breakdowns: None,
         }
     }
 
     fn process_entry(&mut self, repo_path: &RepoPathBuf, entry: &Entry) {
         let inputs = self.inputs.inputs(repo_path, entry);
-        let weight = (self.weight_computer)(&inputs);
+        let Some(breakdown) = compute_weight(self.profile, &inputs) else {
+            return;
+        };
         let parent_path = repo_path.parent().unwrap_or(RepoPathBuf::from("")).to_string();
 
         self.weighted_sampler.add(
             repo_path.clone(),
-            weight,
+            breakdown.weight,
             parent_path,
         );
+
+        if let Some(breakdowns) = self.breakdowns.as_mut() {
+            breakdowns.insert(repo_path.clone(), breakdown);
+        }
     }
 
     fn get_samples(&mut self) -> Vec<RepoPathBuf> {
@@ -135,30 +235,25 @@ impl EntrySampler<'_> {
     }
 }
 
-pub fn surprise_entries(
+fn sample_with(
+    sampler: &mut EntrySampler,
     base_path: &RepoPathBuf,
     file_tree: &FileTree,
     catalog: &mut Catalog,
-    history_db: &HistoryDb,
-    num_entries: usize,
-    recency_weight: bool,
+    seed: Option<u64>,
 ) -> Vec<Entry> {
-    let standard_inputs = StandardInputs {
-        history_db: history_db,
-    };
-
-    let mut sampler = EntrySampler::new(&standard_inputs, num_entries, recency_weight);
-
     let start = std::time::Instant::now();
-    for child in file_tree.list_recursive(base_path).expect("list_recursive") {
-        if child.is_metadata_file {
-            continue;
-        }
-
-        if child.file_type.is_dir {
-            continue;
-        }
+    let mut children: Vec<_> = file_tree
+        .list_recursive(base_path)
+        .expect("list_recursive")
+        .filter(|child| !child.is_metadata_file && !child.file_type.is_dir)
+        .collect();
+    if seed.is_some() {
+        // Directory listing order isn't stable, but the sample depends on it
+        children.sort_by(|a, b| a.repo_path.cmp(&b.repo_path));
+    }
 
+    for child in children {
         let db_entry = catalog.get_or_create(&child);
         let entry = Entry {
             fs: child,
@@ -181,12 +276,59 @@ pub fn surprise_entries(
         .collect()
 }
 
+/// Sample `num_entries` entries under `base_path`, weighted according to `profile`.
+///
+/// With a `seed`, the same sample is returned as long as the files, their
+/// notes, and view history don't change.
+pub fn surprise_entries(
+    base_path: &RepoPathBuf,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    history_db: &HistoryDb,
+    num_entries: usize,
+    profile: &SurpriseProfile,
+    seed: Option<u64>,
+) -> Vec<Entry> {
+    let standard_inputs = StandardInputs {
+        history_db,
+    };
+
+    let mut sampler = EntrySampler::new(&standard_inputs, num_entries, profile, seed);
+    sample_with(&mut sampler, base_path, file_tree, catalog, seed)
+}
+
+/// Same as `surprise_entries`, but also returns how each sampled entry's weight was computed.
+pub fn surprise_entries_explained(
+    base_path: &RepoPathBuf,
+    file_tree: &FileTree,
+    catalog: &mut Catalog,
+    history_db: &HistoryDb,
+    num_entries: usize,
+    profile: &SurpriseProfile,
+    seed: Option<u64>,
+) -> Vec<(Entry, WeightBreakdown)> {
+    let standard_inputs = StandardInputs {
+        history_db,
+    };
+
+    let mut sampler = EntrySampler::new(&standard_inputs, num_entries, profile, seed);
+    sampler.breakdowns = Some(HashMap::new());
+    let entries = sample_with(&mut sampler, base_path, file_tree, catalog, seed);
+
+    let mut breakdowns = sampler.breakdowns.take().unwrap_or_default();
+    entries
+        .into_iter()
+        .map(|entry| {
+            let breakdown = breakdowns.remove(&entry.fs.repo_path).unwrap_or_default();
+            (entry, breakdown)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::collections::HashMap;
-
     use crate::testing::fake_entry;
 
     struct FixedInputs(HashMap<&'static str, WeightInputs>);
@@ -196,6 +338,15 @@ mod tests {
         }
     }
 
+    fn surprise_weight(inputs: &WeightInputs) -> f64 {
+        compute_weight(&SurpriseProfile::default(), inputs).unwrap().weight
+    }
+
+    fn recent_mod_weight(inputs: &WeightInputs) -> f64 {
+        let profile = SurpriseProfile::builtin("recent").unwrap();
+        compute_weight(&profile, inputs).unwrap().weight
+    }
+
     #[test]
     fn test_surprise_weight_rating() {
         let w_none = surprise_weight(&WeightInputs {
@@ -241,6 +392,99 @@ mod tests {
         assert!(w_recent > w_high_rating_old);
     }
 
+    #[test]
+    fn test_tag_boost() {
+        let profile = SurpriseProfile {
+            tag_boosts: [("favorite".to_string(), 3.0), ("boring".to_string(), 0.0)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        let untagged = compute_weight(&profile, &WeightInputs::default()).unwrap();
+        assert_eq!(untagged.tag_boost, 1.0);
+
+        let favorite = compute_weight(&profile, &WeightInputs {
+            tags: vec!["favorite".to_string(), "other".to_string()],
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(favorite.tag_boost, 3.0);
+        assert!((favorite.weight - untagged.weight * 3.0).abs() < 1e-9);
+
+        // Zero boost excludes the entry
+        assert!(compute_weight(&profile, &WeightInputs {
+            tags: vec!["favorite".to_string(), "boring".to_string()],
+            ..Default::default()
+        }).is_none());
+    }
+
+    #[test]
+    fn test_filters() {
+        let profile = SurpriseProfile {
+            file_types: vec!["video".to_string()],
+            unviewed_only: true,
+            min_duration_secs: Some(60.0),
+            ..Default::default()
+        };
+        let video = WeightInputs {
+            file_type: "video",
+            duration_secs: Some(120.0),
+            ..Default::default()
+        };
+        assert!(compute_weight(&profile, &video).is_some());
+
+        assert!(compute_weight(&profile, &WeightInputs { file_type: "image", ..video.clone() }).is_none());
+        assert!(compute_weight(&profile, &WeightInputs { days_since_viewed: Some(100), ..video.clone() }).is_none());
+        assert!(compute_weight(&profile, &WeightInputs { duration_secs: Some(30.0), ..video.clone() }).is_none());
+        assert!(compute_weight(&profile, &WeightInputs { duration_secs: None, ..video.clone() }).is_none());
+    }
+
+    #[test]
+    fn test_breakdown_sums() {
+        let profile = SurpriseProfile::builtin("recent").unwrap();
+        let b = compute_weight(&profile, &WeightInputs {
+            days_since_viewed: Some(10),
+            rating: Some(2),
+            seconds_since_mod: 86400,
+            descendant_weight: 0.5,
+            ..Default::default()
+        }).unwrap();
+        assert!(b.recency > 0.0);
+        assert!((b.weight - (b.history + b.rating + b.recency) * b.tag_boost * b.descendant).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_surprise_entries_seeded() -> crate::testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for dir in ["a", "b", "c", "d"] {
+            for i in 0..5 {
+                let fs_path = file_root.path().join(dir).join(format!("{}.mp4", i));
+                std::fs::create_dir_all(fs_path.parent().unwrap())?;
+                std::fs::write(&fs_path, "")?;
+            }
+        }
+
+        let (_tempdir, vault) = crate::testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let history_db = HistoryDb::new_in_memory();
+        let profile = SurpriseProfile::default();
+
+        let mut sample = |seed| -> Vec<String> {
+            surprise_entries(&RepoPathBuf::from(""), &file_tree, &mut catalog, &history_db, 5, &profile, Some(seed))
+                .into_iter()
+                .map(|entry| entry.fs.repo_path.0)
+                .collect()
+        };
+
+        let first = sample(1234);
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, sample(1234));
+        assert_ne!(first, sample(5678));
+
+        Ok(())
+    }
+
     #[test]
     fn test_sampler_favors_high_scores() {
         let inputs = FixedInputs(
@@ -271,7 +515,8 @@ mod tests {
         let mut count_2 = 0;
 
         for _ in 0..10 {
-            let mut sampler = EntrySampler::new(&inputs, 1, false);
+            let profile = SurpriseProfile::default();
+            let mut sampler = EntrySampler::new(&inputs, 1, &profile, None);
             sampler.process_entry(&RepoPathBuf::from("1.mp4"), &fake_entry("1.mp4"));
             sampler.process_entry(&RepoPathBuf::from("2.mp4"), &fake_entry("2.mp4"));
 
@@ -0,0 +1,165 @@
+//! Find visually similar images and videos, using perceptual hashes from the "phash" job.
+
+use std::sync::{Arc, Mutex};
+
+use crate::catalog::generated_notes;
+use crate::catalog::{Catalog, DbEntry};
+use crate::media::phash;
+use crate::{Entry, FileTree};
+
+use super::bk_tree::BkTree;
+
+/// Max Hamming distance for two entries to count as visually similar.
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// The entry's perceptual hash, if the phash job has run on it.
+pub fn read_phash(db_entry: &DbEntry) -> Option<u64> {
+    generated_notes::read::<phash::PerceptualHash>(db_entry, phash::PHASH_GROUP_NAME)
+        .and_then(|hash| hash.dhash())
+}
+
+/// Save the entry's perceptual hash, both in its generated notes and in the catalog's
+/// phash index.
+pub fn record_phash(catalog: &mut Catalog, entry_id: i64, hash: &phash::PerceptualHash) {
+    generated_notes::update(catalog, entry_id, phash::PHASH_GROUP_NAME, hash);
+    catalog.set_phash(entry_id, &hash.dhash);
+}
+
+/// Entry IDs, indexed by their perceptual hash.
+pub struct VisualIndex {
+    // The catalog's phashes_version when the index was built
+    version: i64,
+    tree: BkTree<i64>,
+}
+
+impl VisualIndex {
+    pub fn build(catalog: &Catalog) -> VisualIndex {
+        let version = catalog.phashes_version();
+        let mut tree = BkTree::new();
+        for (entry_id, dhash) in catalog.list_phashes() {
+            if let Ok(hash) = u64::from_str_radix(&dhash, 16) {
+                tree.insert(hash, entry_id);
+            }
+        }
+        VisualIndex { version, tree }
+    }
+
+    /// Whether no hashes were added or changed since the index was built
+    pub fn is_current(&self, catalog: &Catalog) -> bool {
+        catalog.phashes_version() == self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+/// Keeps the index between queries, only rebuilding it when the phash job has
+/// written new hashes.
+#[derive(Default)]
+pub struct VisualIndexCache {
+    index: Mutex<Option<Arc<VisualIndex>>>,
+}
+
+impl VisualIndexCache {
+    pub fn get(&self, catalog: &Catalog) -> Arc<VisualIndex> {
+        let mut index = self.index.lock().expect("lock");
+        match index.as_ref() {
+            Some(current) if current.is_current(catalog) => current.clone(),
+            _ => {
+                let rebuilt = Arc::new(VisualIndex::build(catalog));
+                *index = Some(rebuilt.clone());
+                rebuilt
+            }
+        }
+    }
+}
+
+/// Entries that look like `hash`, closest first, along with their distance.
+pub fn visually_similar(
+    hash: u64,
+    index: &VisualIndex,
+    file_tree: &FileTree,
+    catalog: &Catalog,
+    max_distance: u32,
+) -> Vec<(Entry, u32)> {
+    let mut results = Vec::new();
+    for (entry_id, distance) in index.tree.find(hash, max_distance) {
+        let Some(db_entry) = catalog.get_by_id(*entry_id) else {
+            continue;
+        };
+        if crate::browse::should_hide_entry(&db_entry) {
+            continue;
+        }
+        // The file may have been moved or deleted since it was hashed
+        let Ok(fs_entry) = file_tree.get_fs_entry(&db_entry.repo_path) else {
+            continue;
+        };
+        results.push((Entry { fs: fs_entry, db: db_entry }, distance));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    #[test]
+    fn test_visually_similar() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        for path in ["a.jpg", "a_resized.jpg", "b.jpg", "unhashed.jpg", "deleted.jpg"] {
+            std::fs::write(file_root.path().join(path), "")?;
+        }
+
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+
+        for (path, hash) in [
+            ("a.jpg", 0xffff_0000_ffff_0000u64),
+            ("a_resized.jpg", 0xffff_0000_ffff_0003),
+            ("b.jpg", 0x1234_5678_9abc_def0),
+            ("deleted.jpg", 0xffff_0000_ffff_0001),
+        ] {
+            let entry = testing::entry_for(path, &file_tree, &mut catalog)?;
+            record_phash(&mut catalog, entry.db.id, &phash::PerceptualHash::from_dhash(hash));
+        }
+        std::fs::remove_file(file_root.path().join("deleted.jpg"))?;
+
+        let index = VisualIndex::build(&catalog);
+        assert_eq!(index.len(), 4);
+        let similar = visually_similar(0xffff_0000_ffff_0000, &index, &file_tree, &catalog, DEFAULT_MAX_DISTANCE);
+        let results: Vec<(&str, u32)> = similar.iter().map(|(e, d)| (e.fs.repo_path.as_str(), *d)).collect();
+        assert_eq!(results, vec![("a.jpg", 0), ("a_resized.jpg", 2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_visual_index_cache() -> testing::TestResult {
+        let file_root = tempfile::tempdir()?;
+        std::fs::write(file_root.path().join("a.jpg"), "")?;
+        let (_tempdir, vault) = testing::tempdir_vault(file_root.path())?;
+        let file_tree = vault.new_file_tree();
+        let mut catalog = vault.open_catalog()?;
+        let cache = VisualIndexCache::default();
+
+        let index = cache.get(&catalog);
+        assert!(index.is_empty());
+        assert!(Arc::ptr_eq(&index, &cache.get(&catalog)));
+
+        // Hashes written from another connection (e.g. by the phash job) are picked up
+        let entry = testing::entry_for("a.jpg", &file_tree, &mut catalog)?;
+        record_phash(&mut vault.open_catalog()?, entry.db.id, &phash::PerceptualHash::from_dhash(1));
+        assert!(!index.is_current(&catalog));
+        assert_eq!(cache.get(&catalog).len(), 1);
+
+        Ok(())
+    }
+}
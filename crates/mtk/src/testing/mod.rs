@@ -19,6 +19,7 @@ pub fn test_fs_entry(repo_path_str: &str) -> FsEntry {
         size_bytes: 42,
         mod_time: chrono::DateTime::from_timestamp(0, 0).expect("from_timestamp"),
         is_metadata_file: false,
+        archive_member: None,
     }
 }
 
@@ -13,6 +13,15 @@ pub struct VideoHistory {
     pub farthest_ts_date: String,
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct ReadingHistory {
+    /// Last read page (PDF), or location (EPUB). None for documents that scroll.
+    pub page: Option<i64>,
+    /// How far through the document, from 0 at the start to 1 at the end
+    pub ratio: f32,
+    pub date: String,
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct ViewHistory {
     pub entry_id: i64,
@@ -20,8 +29,13 @@ pub struct ViewHistory {
 
     // Video watch history
     pub video_history: Option<VideoHistory>,
+    /// Where playback (video or audio) was last at, in seconds, for resuming.
+    /// Unlike `farthest_ts`, this can go backwards.
+    pub playback_position: Option<i64>,
 
-    // TODO: documents. Last read page, etc.
+    // Document reading position. Unlike videos, this is where the reader left off,
+    // rather than the farthest point reached.
+    pub reading_history: Option<ReadingHistory>,
 }
 
 impl ViewHistory {
@@ -30,11 +44,24 @@ impl ViewHistory {
             entry_id: entry_id,
             last_viewed_date: None,
             video_history: None,
+            playback_position: None,
+            reading_history: None,
         }
     }
 }
 
-const ALL_COLUMN_NAMES: &[&'static str] = &["entry_id", "last_viewed_date", "farthest_ts", "farthest_ts_ratio", "farthest_ts_date"];
+const ALL_COLUMN_NAMES: &[&str] = &[
+    "entry_id", "last_viewed_date", "farthest_ts", "farthest_ts_ratio", "farthest_ts_date", "playback_position",
+    "read_page", "read_ratio", "read_date",
+];
+
+/// Columns added after the table was first created, which older databases need added
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("playback_position", "INT64"),
+    ("read_page", "INT64"),
+    ("read_ratio", "FLOAT"),
+    ("read_date", "DATETIME"),
+];
 
 fn row_to_view_history(row: &Row) -> Result<ViewHistory> {
     let farthest_ts: Option<i64> = row.get(2)?;
@@ -48,10 +75,22 @@ fn row_to_view_history(row: &Row) -> Result<ViewHistory> {
         None
     };
 
+    let read_ratio: Option<f32> = row.get(7)?;
+    let reading_history = match read_ratio {
+        Some(ratio) => Some(ReadingHistory {
+            page: row.get(6)?,
+            ratio,
+            date: row.get(8)?,
+        }),
+        None => None,
+    };
+
     Ok(ViewHistory {
         entry_id: row.get(0)?,
         last_viewed_date: row.get(1)?,
         video_history,
+        playback_position: row.get(5)?,
+        reading_history,
     })
 }
 
@@ -68,11 +107,25 @@ impl HistoryDb {
                 last_viewed_date DATETIME,
                 farthest_ts INT64,
                 farthest_ts_ratio FLOAT,
-                farthest_ts_date DATETIME
+                farthest_ts_date DATETIME,
+                playback_position INT64,
+                read_page INT64,
+                read_ratio FLOAT,
+                read_date DATETIME
             )",
             [],
         ).unwrap();
 
+        for (column, column_type) in ADDED_COLUMNS {
+            let has_column = conn
+                .prepare(&format!("SELECT {} FROM ViewHistory LIMIT 0", column))
+                .is_ok();
+            if !has_column {
+                conn.execute(&format!("ALTER TABLE ViewHistory ADD COLUMN {} {}", column, column_type), [])
+                    .unwrap();
+            }
+        }
+
         HistoryDb {
             conn,
         }
@@ -119,6 +172,9 @@ impl HistoryDb {
         assert!(rows_updated == 1);
 
         if let Some((ts, ts_ratio)) = video_info {
+            tx.execute("UPDATE OR FAIL ViewHistory SET playback_position = ?2
+                WHERE entry_id = ?1", params![entry_id, ts])?;
+
             if existing.video_history.is_none() || ts >= existing.video_history.unwrap().farthest_ts {
                 tx.execute("UPDATE OR FAIL ViewHistory SET
                         farthest_ts = ?2,
@@ -131,6 +187,60 @@ impl HistoryDb {
         tx.commit()
     }
 
+    /// Record the reading position in a document, which also counts as viewing it.
+    /// `ratio` is how far through the document the position is (0-1).
+    pub fn mark_read(&mut self, entry_id: i64, page: Option<i64>, ratio: f32) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO ViewHistory (entry_id, last_viewed_date, read_page, read_ratio, read_date)
+            VALUES (?1, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), ?2, ?3, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+            ON CONFLICT (entry_id) DO UPDATE SET
+                last_viewed_date = excluded.last_viewed_date,
+                read_page = excluded.read_page,
+                read_ratio = excluded.read_ratio,
+                read_date = excluded.read_date",
+            params![entry_id, page, ratio],
+        )?;
+        Ok(())
+    }
+
+    /// List documents that were started but not finished, most recently read first.
+    /// A document counts as started once its reading position is past `min_ratio`,
+    /// and as finished once it reaches `max_ratio`.
+    pub fn list_in_progress_documents(&self, min_ratio: f32, max_ratio: f32) -> Result<Vec<ViewHistory>> {
+        let mut stmt = self.conn.prepare(
+            &format!("SELECT {} FROM ViewHistory
+                      WHERE read_ratio > ?1 AND read_ratio < ?2
+                      ORDER BY read_date DESC", ALL_COLUMN_NAMES.join(",")),
+        )?;
+        let rows = stmt.query_map(params![min_ratio, max_ratio], row_to_view_history)?;
+        rows.collect()
+    }
+
+    /// List videos that were started but not finished, most recently viewed first.
+    /// A video counts as started once `farthest_ts` reaches `min_ts` seconds, and as
+    /// finished once `farthest_ts_ratio` reaches `max_ratio`.
+    pub fn list_in_progress_videos(&self, min_ts: i64, max_ratio: f32) -> Result<Vec<ViewHistory>> {
+        let mut stmt = self.conn.prepare(
+            &format!("SELECT {} FROM ViewHistory
+                      WHERE farthest_ts >= ?1 AND farthest_ts_ratio < ?2
+                      ORDER BY last_viewed_date DESC", ALL_COLUMN_NAMES.join(",")),
+        )?;
+        let rows = stmt.query_map(params![min_ts, max_ratio], row_to_view_history)?;
+        rows.collect()
+    }
+
+    /// List entries (other than `entry_id`) whose last view was within `window_secs`
+    /// of the last view of `entry_id`.
+    pub fn list_viewed_near(&self, entry_id: i64, window_secs: i64) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT other.entry_id FROM ViewHistory other, ViewHistory target
+             WHERE target.entry_id = ?1 AND other.entry_id != ?1
+                AND abs(julianday(other.last_viewed_date) - julianday(target.last_viewed_date)) * 86400 <= ?2",
+        )?;
+        let rows = stmt.query_map(params![entry_id, window_secs], |row| row.get(0))?;
+        rows.collect()
+    }
+
     pub fn clear_history(&mut self, entry_id: i64) -> Result<()> {
         self.conn.execute("DELETE FROM ViewHistory
             WHERE entry_id = ?1", params![entry_id])?;
@@ -176,6 +286,81 @@ mod tests {
         let video_hist2 = hist2.video_history.unwrap();
         assert_eq!(video_hist2.farthest_ts, 2);
         assert_eq!(video_hist2.farthest_ts_ratio, 0.45);
+        // ...but the playback position follows seeking backwards
+        assert_eq!(hist2.playback_position, Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_columns() -> Result<()> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let db_path = tempdir.path().join("history.db");
+        {
+            let conn = Connection::open(&db_path)?;
+            conn.execute(
+                "CREATE TABLE ViewHistory (
+                    entry_id INTEGER PRIMARY KEY,
+                    last_viewed_date DATETIME,
+                    farthest_ts INT64,
+                    farthest_ts_ratio FLOAT,
+                    farthest_ts_date DATETIME
+                )",
+                [],
+            )?;
+            conn.execute("INSERT INTO ViewHistory (entry_id, last_viewed_date) VALUES (1, '2024-01-01T00:00:00Z')", [])?;
+        }
+
+        let mut db = HistoryDb::new(&db_path);
+        assert_eq!(db.get(1)?.playback_position, None);
+        db.mark_viewed(1, Some((20, 0.1)))?;
+        assert_eq!(db.get(1)?.playback_position, Some(20));
+        db.mark_read(1, Some(3), 0.5)?;
+        assert_eq!(db.get(1)?.reading_history.unwrap().page, Some(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_read() -> Result<()> {
+        let mut db = HistoryDb::new_in_memory();
+        db.mark_read(42, Some(12), 0.25)?;
+        let hist = db.get(42)?;
+        assert!(hist.last_viewed_date.is_some());
+        assert!(hist.video_history.is_none());
+        let reading_hist = hist.reading_history.unwrap();
+        assert_eq!(reading_hist.page, Some(12));
+        assert_eq!(reading_hist.ratio, 0.25);
+
+        // Going back a few pages moves the position back
+        db.mark_read(42, Some(10), 0.2)?;
+        assert_eq!(db.get(42)?.reading_history.unwrap().page, Some(10));
+
+        // Scrolled documents don't have pages
+        db.mark_read(43, None, 0.5)?;
+        assert_eq!(db.get(43)?.reading_history.unwrap().page, None);
+
+        // Viewing doesn't lose the position
+        db.mark_viewed(42, None)?;
+        assert_eq!(db.get(42)?.reading_history.unwrap().page, Some(10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_in_progress_documents() -> Result<()> {
+        let mut db = HistoryDb::new_in_memory();
+        db.mark_read(1, Some(40), 0.4)?;
+        db.mark_read(2, Some(1), 0.0)?; // Only opened
+        db.mark_read(3, Some(100), 1.0)?; // Finished
+        db.mark_viewed(4, Some((120, 0.5)))?; // Not a document
+
+        thread::sleep(time::Duration::from_secs(1));
+        db.mark_read(5, None, 0.1)?;
+
+        let in_progress = db.list_in_progress_documents(0.0, 0.98)?;
+        let ids: Vec<i64> = in_progress.iter().map(|h| h.entry_id).collect();
+        assert_eq!(ids, vec![5, 1]);
 
         Ok(())
     }
@@ -196,6 +381,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_list_in_progress_videos() -> Result<()> {
+        let mut db = HistoryDb::new_in_memory();
+        db.mark_viewed(1, Some((120, 0.5)))?;
+        db.mark_viewed(2, Some((3, 0.01)))?; // Barely started
+        db.mark_viewed(3, Some((590, 0.99)))?; // Finished
+        db.mark_viewed(4, None)?; // Not a video
+
+        thread::sleep(time::Duration::from_secs(1));
+        db.mark_viewed(5, Some((60, 0.2)))?;
+
+        let in_progress = db.list_in_progress_videos(10, 0.95)?;
+        let ids: Vec<i64> = in_progress.iter().map(|h| h.entry_id).collect();
+        // Most recently viewed first
+        assert_eq!(ids, vec![5, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_viewed_near() -> Result<()> {
+        let mut db = HistoryDb::new_in_memory();
+        db.mark_viewed(1, None)?;
+        db.mark_viewed(2, None)?;
+        db.mark_viewed(3, None)?;
+
+        let mut near = db.list_viewed_near(1, 60)?;
+        near.sort();
+        assert_eq!(near, vec![2, 3]);
+
+        // Never viewed
+        assert!(db.list_viewed_near(42, 60)?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_non_video() -> Result<()> {
         let mut db = HistoryDb::new_in_memory();
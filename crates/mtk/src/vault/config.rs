@@ -3,6 +3,8 @@ use std::collections::BTreeMap;
 
 use serde::Deserialize;
 
+use crate::media::video::{transcode::TranscodeProfile, Client};
+
 pub const CONFIG_FILENAME: &str = "config.json";
 
 // TODO(fyhuang): make config generic for plugins
@@ -18,6 +20,88 @@ pub struct FilerConfig {
     pub default_save_parent: BTreeMap<String, String>,
     #[serde(default)]
     pub local_path_prefixes: Vec<String>,
+    /// Named weighting profiles for Surprise Me, in addition to the built-in
+    /// "default" and "recent" profiles (which can be overridden here).
+    #[serde(default)]
+    pub surprise_profiles: BTreeMap<String, SurpriseProfile>,
+    /// Languages to show subtitles in by default, most preferred first (e.g. ["en-US", "ja"]).
+    /// Subtitles are off by default if empty.
+    #[serde(default)]
+    pub preferred_subtitle_languages: Vec<String>,
+    /// Transcode profile (e.g. "h264_800k") for burning bitmap subtitles into an alt
+    /// format, for videos that don't have any text subtitles. Off if unset.
+    #[serde(default)]
+    pub burn_in_subtitles_profile: Option<String>,
+    /// Transcode profiles for alt formats, in addition to the built-in ones (which can
+    /// be overridden here). See `media::video::transcode`.
+    #[serde(default)]
+    pub transcode_profiles: BTreeMap<String, TranscodeProfile>,
+    /// Transcode profiles the "transcode" job tries, most preferred first. Each video
+    /// gets the first one that all of the playback clients can play.
+    #[serde(default = "default_transcode_job_profiles")]
+    pub transcode_job_profiles: Vec<String>,
+    /// Browsers that videos should play in ("chrome", "firefox", "safari"). Videos
+    /// that any of them can't play get transcoded.
+    #[serde(default = "default_playback_clients")]
+    pub playback_clients: Vec<Client>,
+}
+
+pub fn default_playback_clients() -> Vec<Client> {
+    vec![Client::Chrome]
+}
+
+pub fn default_transcode_job_profiles() -> Vec<String> {
+    vec!["av1_400k".to_string(), "h264_800k".to_string()]
+}
+
+/// Tunables for how Surprise Me weights entries. See `query::surprise`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SurpriseProfile {
+    /// Entries last viewed this many days ago score ~90% as high as never-viewed ones.
+    pub history_scale_days: f64,
+    /// Score added for just-modified entries (0 disables).
+    pub recency_boost: f64,
+    /// Entries modified this many days ago get about half of the recency boost.
+    pub recency_halflife_days: f64,
+    /// Multiply the weight of entries with a given tag. A boost of 0 excludes the tag.
+    pub tag_boosts: BTreeMap<String, f64>,
+    /// Only include these file types ("video", "image", "audio", "document", "other"). Empty means all.
+    pub file_types: Vec<String>,
+    /// Only include entries that were never viewed.
+    pub unviewed_only: bool,
+    /// Only include videos (or audio) at least/at most this long. Entries with unknown
+    /// duration are excluded when either bound is set.
+    pub min_duration_secs: Option<f64>,
+    pub max_duration_secs: Option<f64>,
+}
+
+impl Default for SurpriseProfile {
+    fn default() -> Self {
+        SurpriseProfile {
+            history_scale_days: 720.0,
+            recency_boost: 0.0,
+            recency_halflife_days: 30.0,
+            tag_boosts: BTreeMap::new(),
+            file_types: Vec::new(),
+            unviewed_only: false,
+            min_duration_secs: None,
+            max_duration_secs: None,
+        }
+    }
+}
+
+impl SurpriseProfile {
+    pub fn builtin(name: &str) -> Option<SurpriseProfile> {
+        match name {
+            "default" => Some(SurpriseProfile::default()),
+            "recent" => Some(SurpriseProfile {
+                recency_boost: 50.0,
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
 }
 
 impl FilerConfig {
@@ -28,6 +112,46 @@ impl FilerConfig {
             return None;
         }
 
-        serde_json::from_str(data_str).expect(&format!("couldn't load config from {:?}", filepath))
+        let mut config: FilerConfig = serde_json::from_str(data_str)
+            .unwrap_or_else(|e| panic!("couldn't load config from {:?}: {}", filepath, e));
+        for (name, profile) in &mut config.transcode_profiles {
+            profile.name = name.clone();
+            if let Err(e) = profile.validate() {
+                panic!("couldn't load config from {:?}: {}", filepath, e);
+            }
+        }
+        Some(config)
+    }
+
+    /// Look up a Surprise Me profile by name, falling back to the built-in profiles.
+    pub fn surprise_profile(&self, name: &str) -> Option<SurpriseProfile> {
+        self.surprise_profiles
+            .get(name)
+            .cloned()
+            .or_else(|| SurpriseProfile::builtin(name))
+    }
+
+    /// Look up a transcode profile by name, falling back to the built-in profiles, and
+    /// check that it's valid.
+    pub fn transcode_profile(&self, name: &str) -> Result<TranscodeProfile, String> {
+        let mut profile = self
+            .transcode_profiles
+            .get(name)
+            .cloned()
+            .or_else(|| TranscodeProfile::builtin(name))
+            .ok_or_else(|| format!("Unknown transcode profile {:?}", name))?;
+        profile.name = name.to_string();
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    pub fn surprise_profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = ["default", "recent"].iter().map(|s| s.to_string()).collect();
+        for name in self.surprise_profiles.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
     }
 }
@@ -5,3 +5,4 @@ pub use vault::Vault;
 
 mod config;
 pub use config::FilerConfig;
+pub use config::SurpriseProfile;
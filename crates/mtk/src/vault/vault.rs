@@ -52,6 +52,12 @@ impl Vault {
                 include_non_media: false,
                 default_save_parent: BTreeMap::new(),
                 local_path_prefixes: Vec::new(),
+                surprise_profiles: BTreeMap::new(),
+                preferred_subtitle_languages: Vec::new(),
+                burn_in_subtitles_profile: None,
+                transcode_profiles: BTreeMap::new(),
+                transcode_job_profiles: super::config::default_transcode_job_profiles(),
+                playback_clients: super::config::default_playback_clients(),
             }),
         }
     }
@@ -8,11 +8,18 @@ mod save;
 
 // TODO(fyhuang): make this private
 pub use partial::ListingLayout;
+pub use partial::ShelfPartial;
+pub use renderers::AudioPlayerRenderer;
+pub use renderers::DocumentReaderRenderer;
+pub use renderers::EntryLink;
 pub use renderers::EntryRenderer;
+pub use renderers::ImageInfoRenderer;
+pub use renderers::SeriesNavRenderer;
 pub use renderers::VideoPlayerRenderer;
 
 pub use template::EntryListTemplate;
 pub use template::DirIndexTemplate;
+pub use template::SurpriseDebugTemplate;
 pub use template::ViewEntryTemplate;
 
 pub use edit::EntryEditorPartial;
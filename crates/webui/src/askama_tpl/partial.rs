@@ -1,5 +1,7 @@
 use askama::Template;
 
+use mtk::query::series;
+use mtk::userdata::HistoryDb;
 use mtk::{RepoPathBuf, Entry};
 
 use super::filters;
@@ -10,6 +12,8 @@ pub enum ListingLayout {
     List,
     GalleryGrid,
     CompactCardGrid,
+    /// One image at a time, for archives of comic pages
+    Reader,
 }
 
 impl ListingLayout {
@@ -17,6 +21,7 @@ impl ListingLayout {
         match s {
             "grid" => ListingLayout::GalleryGrid,
             "list" => ListingLayout::List,
+            "reader" => ListingLayout::Reader,
             _ => ListingLayout::CompactCardGrid,
         }
     }
@@ -50,6 +55,84 @@ impl DirListingPartial {
     }
 }
 
+#[derive(Template)]
+#[template(path = "shelf_partial.ask.html")]
+pub struct ShelfPartial {
+    pub title: String,
+    pub more_url: String,
+    pub entries: Vec<EntryRenderer>,
+}
+
+impl ShelfPartial {
+    pub fn from(title: &str, more_url: &str, entries: &[Entry]) -> ShelfPartial {
+        ShelfPartial {
+            title: title.to_string(),
+            more_url: more_url.to_string(),
+            entries: entries.iter().map(|entry| {
+                let mut r = EntryRenderer::from(entry);
+                r.render_video_stats(entry);
+                r
+            }).collect(),
+        }
+    }
+}
+
+pub struct EpisodeRow {
+    pub entry: EntryRenderer,
+    pub watched: bool,
+    /// How far through the episode has been watched, if it's been started
+    pub progress_percent: Option<u32>,
+}
+
+/// Episode list for series folders
+#[derive(Template)]
+#[template(path = "series_listing_partial.ask.html")]
+pub struct SeriesListingPartial {
+    pub episodes: Vec<EpisodeRow>,
+    pub next_up: Option<usize>,
+}
+
+impl SeriesListingPartial {
+    pub fn from(episodes: &[Entry], history_db: &HistoryDb) -> SeriesListingPartial {
+        let rows = episodes.iter().map(|episode| {
+            let history = history_db.get(episode.db.id).expect("HistoryDb::get");
+            let mut entry = EntryRenderer::from(episode);
+            entry.render_video_stats(episode);
+            EpisodeRow {
+                entry,
+                watched: series::is_watched(&history),
+                progress_percent: history
+                    .video_history
+                    .map(|video_history| (video_history.farthest_ts_ratio * 100.0).round() as u32),
+            }
+        }).collect();
+
+        SeriesListingPartial {
+            episodes: rows,
+            next_up: series::next_unwatched(episodes, history_db),
+        }
+    }
+
+    fn next_episode(&self) -> Option<&EpisodeRow> {
+        self.next_up.map(|index| &self.episodes[index])
+    }
+}
+
+/// Grid of images for gallery folders, which open in a lightbox
+#[derive(Template)]
+#[template(path = "gallery_listing_partial.ask.html")]
+pub struct GalleryListingPartial {
+    pub images: Vec<EntryRenderer>,
+}
+
+impl GalleryListingPartial {
+    pub fn from(images: &[Entry]) -> GalleryListingPartial {
+        GalleryListingPartial {
+            images: images.iter().map(EntryRenderer::from).collect(),
+        }
+    }
+}
+
 pub struct ParentCrumb {
     dir_name: String,
     repo_path: String,
@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use mtk::media::audio;
+use mtk::userdata::ViewHistory;
+use mtk::Entry;
+
+use super::file_renderer::RawFileRenderer;
+use super::video_stats_renderer::format_duration;
+
+#[derive(Clone, Serialize)]
+pub struct AudioPlayerRenderer {
+    pub file: RawFileRenderer,
+    pub mime_type: Option<String>,
+    pub has_cover_art: bool,
+
+    pub duration_str: Option<String>,
+    // e.g. "flac, 44.1 kHz, stereo"
+    pub format_str: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+
+    /// Where to resume playback from, in seconds
+    pub resume_secs: Option<i64>,
+}
+
+impl AudioPlayerRenderer {
+    /// Works before the "audio_info" job has run, just without the extra info.
+    pub fn new(entry: &Entry, history: &ViewHistory) -> AudioPlayerRenderer {
+        let audio_info = audio::read_audio_info(&entry.db);
+        let file = RawFileRenderer { repo_path: entry.fs.repo_path.0.clone() };
+        let Some(audio_info) = audio_info else {
+            return AudioPlayerRenderer {
+                file,
+                mime_type: None,
+                has_cover_art: false,
+                duration_str: None,
+                format_str: None,
+                artist: None,
+                album: None,
+                track: None,
+                date: None,
+                genre: None,
+                resume_secs: history.playback_position,
+            };
+        };
+
+        AudioPlayerRenderer {
+            file,
+            format_str: Some(format_audio(&audio_info)),
+            mime_type: Some(audio_info.mime_type),
+            has_cover_art: audio_info.has_cover_art,
+            duration_str: Some(format_duration(audio_info.duration_secs)),
+            artist: audio_info.artist,
+            album: audio_info.album,
+            track: audio_info.track,
+            date: audio_info.date,
+            genre: audio_info.genre,
+            resume_secs: history.playback_position,
+        }
+    }
+}
+
+fn format_audio(audio_info: &audio::AudioInfo) -> String {
+    let mut parts = vec![audio_info.codec.clone()];
+    if let Some(sample_rate) = audio_info.sample_rate {
+        parts.push(format!("{} kHz", sample_rate as f64 / 1000.));
+    }
+    match audio_info.channels {
+        Some(1) => parts.push("mono".to_string()),
+        Some(2) => parts.push("stereo".to_string()),
+        Some(channels) => parts.push(format!("{} channels", channels)),
+        None => {}
+    }
+    if audio_info.bitrate > 0 {
+        parts.push(format!("{} kbps", audio_info.bitrate / 1000));
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_audio() {
+        let info = audio::AudioInfo {
+            codec: "flac".to_string(),
+            sample_rate: Some(44100),
+            channels: Some(2),
+            bitrate: 912345,
+            ..Default::default()
+        };
+        assert_eq!(format_audio(&info), "flac, 44.1 kHz, stereo, 912 kbps");
+
+        let info = audio::AudioInfo { codec: "opus".to_string(), channels: Some(1), ..Default::default() };
+        assert_eq!(format_audio(&info), "opus, mono");
+    }
+}
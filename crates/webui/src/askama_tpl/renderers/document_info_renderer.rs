@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+use mtk::media::document;
+use mtk::Entry;
+
+#[derive(Clone, Serialize)]
+pub struct DocumentInfoRenderer {
+    // e.g. "PDF"
+    pub format_str: String,
+    // e.g. "42 pages"
+    pub pages_str: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl DocumentInfoRenderer {
+    /// None until the "document_info" job has run.
+    pub fn from(entry: &Entry) -> Option<DocumentInfoRenderer> {
+        let document_info = document::read_document_info(&entry.db)?;
+        Some(DocumentInfoRenderer {
+            format_str: document_info.format.to_uppercase(),
+            pages_str: document_info.page_count.map(format_pages),
+            title: document_info.title,
+            author: document_info.author,
+        })
+    }
+}
+
+fn format_pages(page_count: u32) -> String {
+    if page_count == 1 {
+        "1 page".to_string()
+    } else {
+        format!("{} pages", page_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_pages() {
+        assert_eq!(format_pages(1), "1 page");
+        assert_eq!(format_pages(320), "320 pages");
+    }
+}
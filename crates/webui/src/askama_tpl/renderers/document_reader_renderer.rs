@@ -0,0 +1,129 @@
+use serde::Serialize;
+
+use mtk::media::document::DocumentFormat;
+use mtk::userdata::ViewHistory;
+use mtk::Entry;
+
+use super::file_renderer::RawFileRenderer;
+
+/// Bigger text files are linked to instead of being shown inline
+const MAX_INLINE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Clone, Serialize)]
+pub struct DocumentReaderRenderer {
+    pub file: RawFileRenderer,
+    /// "markdown", "text", "pdf" or "epub"
+    pub kind: String,
+
+    /// Rendered Markdown
+    pub html: Option<String>,
+    /// Plain text, shown as-is
+    pub text: Option<String>,
+
+    /// Where to resume reading from
+    pub resume_page: Option<i64>,
+    pub resume_ratio: Option<f32>,
+}
+
+impl DocumentReaderRenderer {
+    /// None if the entry isn't a document that can be read in the browser.
+    pub fn new(entry: &Entry, history: &ViewHistory) -> Option<DocumentReaderRenderer> {
+        let extension = entry.fs.file_path.extension()?.to_str()?.to_lowercase();
+        let kind = match DocumentFormat::from_path(&entry.fs.file_path) {
+            Some(DocumentFormat::Pdf) => "pdf",
+            Some(DocumentFormat::Epub) => "epub",
+            None if extension == "md" => "markdown",
+            None if extension == "txt" => "text",
+            None => return None,
+        };
+
+        let contents = if (kind == "markdown" || kind == "text") && entry.fs.size_bytes <= MAX_INLINE_BYTES {
+            std::fs::read(&entry.fs.file_path)
+                .ok()
+                .map(|data| String::from_utf8_lossy(&data).into_owned())
+        } else {
+            None
+        };
+
+        Some(DocumentReaderRenderer {
+            file: RawFileRenderer { repo_path: entry.fs.repo_path.0.clone() },
+            kind: kind.to_string(),
+            html: contents.as_deref().filter(|_| kind == "markdown").map(render_markdown),
+            text: contents.filter(|_| kind == "text"),
+            resume_page: history.reading_history.as_ref().and_then(|h| h.page),
+            resume_ratio: history.reading_history.as_ref().map(|h| h.ratio),
+        })
+    }
+}
+
+/// Whether a link or image URL can go in the page as-is: http(s), mailto, or
+/// relative. Other schemes (javascript:, data:, ...) can run script.
+fn is_safe_url(url: &str) -> bool {
+    match url.split_once(':') {
+        None => true,
+        // A colon after the path starts, e.g. "notes/a:b.md", is still relative
+        Some((scheme, _)) if scheme.contains(['/', '?', '#']) => true,
+        Some((scheme, _)) => ["http", "https", "mailto"].iter().any(|safe| scheme.eq_ignore_ascii_case(safe)),
+    }
+}
+
+/// Render Markdown to HTML. Any HTML in the Markdown is shown as text rather than
+/// passed through, and link and image URLs with other schemes are dropped, since it
+/// can come from anywhere.
+fn render_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let parser = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) if !is_safe_url(&dest_url) => {
+            Event::Start(Tag::Link { link_type, dest_url: CowStr::Borrowed("#"), title, id })
+        }
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) if !is_safe_url(&dest_url) => {
+            Event::Start(Tag::Image { link_type, dest_url: CowStr::Borrowed("#"), title, id })
+        }
+        event => event,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        assert_eq!(render_markdown("# Title\n\nSome *text*"), "<h1>Title</h1>\n<p>Some <em>text</em></p>\n");
+        assert_eq!(
+            render_markdown("Hi <script>alert(1)</script>"),
+            "<p>Hi &lt;script&gt;alert(1)&lt;/script&gt;</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_urls() {
+        // Allowed schemes and relative URLs are kept
+        assert_eq!(render_markdown("[a](https://example.com/)"), "<p><a href=\"https://example.com/\">a</a></p>\n");
+        assert_eq!(render_markdown("[a](mailto:me@example.com)"), "<p><a href=\"mailto:me@example.com\">a</a></p>\n");
+        assert_eq!(render_markdown("[a](other.md#part)"), "<p><a href=\"other.md#part\">a</a></p>\n");
+        assert_eq!(render_markdown("![a](img/a:b.png)"), "<p><img src=\"img/a:b.png\" alt=\"a\" /></p>\n");
+
+        // Anything else is dropped, however it's written
+        for markdown in [
+            "[a](javascript:alert(1))",
+            "[a](JavaScript:alert(1))",
+            "[a](javascript&#58;alert(1))",
+            "[a]\n\n[a]: javascript:alert(1)",
+            "<javascript:alert(1)>",
+        ] {
+            assert!(render_markdown(markdown).contains("<a href=\"#\">"), "{}", markdown);
+        }
+        assert_eq!(render_markdown("![a](data:image/svg+xml,x)"), "<p><img src=\"#\" alt=\"a\" /></p>\n");
+    }
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+
+use mtk::Entry;
+
+/// Just enough to link to an entry
+#[derive(Clone, Serialize)]
+pub struct EntryLink {
+    pub entry_id: i64,
+    pub repo_path: String,
+    pub display_title: String,
+}
+
+impl EntryLink {
+    pub fn from(entry: &Entry) -> EntryLink {
+        EntryLink {
+            entry_id: entry.db.id,
+            repo_path: entry.fs.repo_path.0.clone(),
+            display_title: entry.display_title(),
+        }
+    }
+}
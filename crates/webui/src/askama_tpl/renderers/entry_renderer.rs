@@ -4,6 +4,7 @@ use mtk::Entry;
 use mtk::{catalog, file_tree};
 
 use mtk::filetype;
+use mtk::media::{audio, image, video};
 
 #[derive(Clone, Serialize)]
 pub struct EntryRenderer {
@@ -19,17 +20,35 @@ pub struct EntryRenderer {
     // Filetype hints
     pub is_image: bool,
     pub is_video: bool,
+    pub is_audio: bool,
+    pub is_document: bool,
+    /// Archive of images, browsed like a directory
+    pub is_archive: bool,
 
     pub catalog: catalog::DbEntry,
 
     // Media type-specific fields
     pub video_stats: Option<super::VideoStatsRenderer>,
     pub video_player: Option<super::VideoPlayerRenderer>,
+    pub series_nav: Option<super::SeriesNavRenderer>,
+    pub audio_duration_str: Option<String>,
+    pub audio_player: Option<super::AudioPlayerRenderer>,
+    pub image_info: Option<super::ImageInfoRenderer>,
+    pub document_info: Option<super::DocumentInfoRenderer>,
+    pub document_reader: Option<super::DocumentReaderRenderer>,
+    // Hover preview; may not have been generated yet
+    pub minivid: Option<super::file_renderer::GeneratedFileRenderer>,
+    // Looping webm shown instead of a large GIF, if it's been transcoded
+    pub animated_alt: Option<super::file_renderer::GeneratedFileRenderer>,
 }
 
 impl EntryRenderer {
     pub fn from(entry: &Entry) -> EntryRenderer {
         let repo_path = &entry.fs.repo_path.0;
+        let is_video = filetype::is_video(&entry.fs.file_path);
+        let is_audio = filetype::is_audio(&entry.fs.file_path);
+        let is_image = filetype::is_image(&entry.fs.file_path);
+        let is_animated = is_image && image::image_info::read_image_info(&entry.db).is_some_and(|info| info.animated);
 
         EntryRenderer {
             repo_path: repo_path.to_string(),
@@ -38,11 +57,29 @@ impl EntryRenderer {
             display_title: entry.display_title(),
             file_name: entry.fs.repo_path.file_name().to_string(),
             file_type: entry.fs.file_type.clone(),
-            is_image: filetype::is_image(&entry.fs.file_path),
-            is_video: filetype::is_video(&entry.fs.file_path),
+            is_image,
+            is_video,
+            is_audio,
+            is_document: filetype::is_document(&entry.fs.file_path),
+            is_archive: entry.fs.is_archive(),
             catalog: entry.db.clone(),
             video_stats: None,
             video_player: None,
+            series_nav: None,
+            audio_duration_str: if is_audio {
+                audio::read_audio_info(&entry.db)
+                    .map(|info| super::video_stats_renderer::format_duration(info.duration_secs))
+            } else {
+                None
+            },
+            audio_player: None,
+            image_info: None,
+            document_info: super::DocumentInfoRenderer::from(entry),
+            document_reader: None,
+            minivid: (is_video || is_animated).then(|| {
+                super::file_renderer::GeneratedFileRenderer::new(&video::video_preview::minivid_gfile(entry.db.id))
+            }),
+            animated_alt: None,
         }
     }
 
@@ -52,6 +89,14 @@ impl EntryRenderer {
         }
     }
 
+    /// Use the transcoded webm of a large GIF, if there is one
+    pub fn render_animated_alt(&mut self, gen_tree: &file_tree::GeneratedTree) {
+        let gfile = image::animated::gif_webm_gfile(self.entry_id);
+        if self.is_image && gen_tree.existing_generated_file(&gfile).is_some() {
+            self.animated_alt = Some(super::file_renderer::GeneratedFileRenderer::new(&gfile));
+        }
+    }
+
     pub fn as_json(&self) -> String {
         serde_json::to_string(&self).unwrap()
     }
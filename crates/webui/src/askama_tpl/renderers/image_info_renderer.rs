@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+use mtk::media::image::image_info;
+use mtk::Entry;
+
+#[derive(Clone, Serialize)]
+pub struct ImageInfoRenderer {
+    // e.g. "6000 × 4000"
+    pub dimensions_str: String,
+    // e.g. "2023-08-14 16:02:11"
+    pub capture_date_str: Option<String>,
+    pub camera: Option<String>,
+    pub lens: Option<String>,
+    // e.g. "35.65820, -139.70000"
+    pub gps_str: Option<String>,
+    pub map_url: Option<String>,
+}
+
+impl ImageInfoRenderer {
+    /// None until the "image_info" job has run.
+    pub fn from(entry: &Entry) -> Option<ImageInfoRenderer> {
+        let image_info = image_info::read_image_info(&entry.db)?;
+        Some(ImageInfoRenderer {
+            dimensions_str: format!("{} × {}", image_info.width, image_info.height),
+            capture_date_str: image_info.capture_time().map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+            camera: image_info.camera(),
+            lens: image_info.lens.clone(),
+            gps_str: image_info.gps.as_ref().map(|gps| format!("{:.5}, {:.5}", gps.latitude, gps.longitude)),
+            map_url: image_info.gps.as_ref().map(|gps| {
+                format!(
+                    "https://www.openstreetmap.org/?mlat={lat:.5}&mlon={lon:.5}#map=15/{lat:.5}/{lon:.5}",
+                    lat = gps.latitude,
+                    lon = gps.longitude,
+                )
+            }),
+        })
+    }
+}
@@ -1,8 +1,26 @@
+mod audio_player_renderer;
+pub use audio_player_renderer::AudioPlayerRenderer;
+
+mod document_info_renderer;
+pub use document_info_renderer::DocumentInfoRenderer;
+
+mod document_reader_renderer;
+pub use document_reader_renderer::DocumentReaderRenderer;
+
+mod entry_link;
+pub use entry_link::EntryLink;
+
 mod entry_renderer;
 pub use entry_renderer::EntryRenderer;
 
 mod file_renderer;
 
+mod image_info_renderer;
+pub use image_info_renderer::ImageInfoRenderer;
+
+mod series_nav_renderer;
+pub use series_nav_renderer::SeriesNavRenderer;
+
 mod video_player_renderer;
 pub use video_player_renderer::VideoPlayerRenderer;
 
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+use mtk::query::series::SeriesPosition;
+use mtk::userdata::HistoryDb;
+
+use super::EntryLink;
+
+/// Previous/next links for videos in a series folder
+#[derive(Clone, Serialize)]
+pub struct SeriesNavRenderer {
+    pub series_path: String,
+    // e.g. "Episode 2 of 12"
+    pub position_str: String,
+    pub prev: Option<EntryLink>,
+    pub next: Option<EntryLink>,
+    // Only set when it's not the same as `next`
+    pub next_unwatched: Option<EntryLink>,
+}
+
+impl SeriesNavRenderer {
+    pub fn new(position: &SeriesPosition, history_db: &HistoryDb) -> SeriesNavRenderer {
+        let next = position.next();
+        let next_unwatched = position
+            .next_unwatched(history_db)
+            .filter(|unwatched| next.is_none_or(|next| next.db.id != unwatched.db.id));
+        SeriesNavRenderer {
+            series_path: position.series_path.0.clone(),
+            position_str: format!("Episode {} of {}", position.index + 1, position.episodes.len()),
+            prev: position.prev().map(EntryLink::from),
+            next: next.map(EntryLink::from),
+            next_unwatched: next_unwatched.map(EntryLink::from),
+        }
+    }
+}
@@ -1,24 +1,23 @@
-use std::path::Path;
-
 use serde::Serialize;
 
-use mtk::{file_tree::GeneratedFile, Entry};
+use mtk::{catalog::Catalog, file_tree::GeneratedFile, Entry};
 use mtk::media::video;
 
 use super::file_renderer::{RawFileRenderer, GeneratedFileRenderer, ServableFileRenderer};
 
-fn get_type_with_codecs(video_info: &video::VideoInfo) -> String {
-    if video_info.mime_type == "video/mp4" {
-        format!("video/mp4; codecs=\"{}\"", video_info.codec_rfc6381)
-    } else {
-        format!("{}; codecs=\"{}\"", video_info.mime_type, video_info.codec)
+fn get_type_with_codecs(video_info: &video::VideoInfo) -> Option<String> {
+    match video_info.mime_type.as_str() {
+        "video/mp4" => Some(format!("video/mp4; codecs=\"{}\"", video_info.codec_rfc6381)),
+        // Browsers disagree on the MIME type for Matroska, so leave it to them to sniff
+        "video/x-matroska" => None,
+        mime_type => Some(format!("{}; codecs=\"{}\"", mime_type, video_info.codec)),
     }
 }
 
 #[derive(Clone, Serialize)]
 pub struct VideoSourceRenderer {
     pub file: ServableFileRenderer,
-    pub type_with_codecs: String,
+    pub type_with_codecs: Option<String>,
 }
 
 impl VideoSourceRenderer {
@@ -27,15 +26,14 @@ impl VideoSourceRenderer {
             file: ServableFileRenderer::RawFile(RawFileRenderer {
                 repo_path: le.fs.repo_path.0.clone(),
             }),
-            type_with_codecs: get_type_with_codecs(&video_info),
+            type_with_codecs: get_type_with_codecs(video_info),
         }
     }
 
-    pub fn from_generated(gfile: &GeneratedFile, file_path: &Path) -> VideoSourceRenderer {
-        let video_info = video::get_video_info(file_path).expect("Failed to get video info");
+    pub fn from_generated(gfile: &GeneratedFile, video_info: &video::VideoInfo) -> VideoSourceRenderer {
         VideoSourceRenderer {
             file: ServableFileRenderer::GeneratedFile(GeneratedFileRenderer::new(gfile)),
-            type_with_codecs: get_type_with_codecs(&video_info),
+            type_with_codecs: get_type_with_codecs(video_info),
         }
     }
 }
@@ -44,72 +42,168 @@ impl VideoSourceRenderer {
 pub struct SubtitleRenderer {
     pub file: ServableFileRenderer,
     pub srclang: String,
+    pub label: String,
+    pub is_default: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ChapterRenderer {
+    pub start_secs: f64,
+    pub start_str: String,
+    pub title: String,
+}
+
+impl ChapterRenderer {
+    fn from_chapter(index: usize, chapter: &video::Chapter) -> ChapterRenderer {
+        let total_secs = chapter.start_secs.max(0.).floor() as u64;
+        let (hours, minutes, seconds) = (total_secs / 3600, total_secs / 60 % 60, total_secs % 60);
+        ChapterRenderer {
+            start_secs: chapter.start_secs,
+            start_str: if hours > 0 {
+                format!("{}:{:02}:{:02}", hours, minutes, seconds)
+            } else {
+                format!("{:02}:{:02}", minutes, seconds)
+            },
+            title: chapter.title.clone().unwrap_or_else(|| format!("Chapter {}", index + 1)),
+        }
+    }
 }
 
 #[derive(Clone, Serialize)]
 pub struct VideoPlayerRenderer {
-    pub main_source: VideoSourceRenderer,
-    pub alt_formats: Vec<VideoSourceRenderer>,
+    /// The original video and its alt formats, best for the requesting browser first
+    pub sources: Vec<VideoSourceRenderer>,
+    /// Label of the subtitles burned into the first source, e.g. "English [SDH]"
+    pub burned_in_label: Option<String>,
     pub vtt_subtitles: Vec<SubtitleRenderer>,
+    /// WebVTT track of sprite sheet regions, for seek-bar previews
+    pub thumbnails: Option<GeneratedFileRenderer>,
+    /// HLS master playlist, for videos that the browser probably can't play directly
+    pub hls_url: Option<String>,
+    pub chapters: Vec<ChapterRenderer>,
+    pub stats: super::VideoStatsRenderer,
 
     pub loop_and_autoplay: bool,
 }
 
 impl VideoPlayerRenderer {
-    pub fn new(vault: &mtk::Vault, entry: &Entry) -> VideoPlayerRenderer {
+    /// `client` is the requesting browser. If it's unknown, sources are picked for
+    /// all the configured playback clients.
+    pub fn new(
+        vault: &mtk::Vault,
+        catalog: &mut Catalog,
+        entry: &Entry,
+        client: Option<video::Client>,
+    ) -> VideoPlayerRenderer {
         let file_tree = vault.new_file_tree();
         let gen_tree = vault.new_generated_tree();
-        let video_info = video::get_video_info(&entry.fs.file_path).expect("get_video_info");
+        let video_info = video::get_video_info_cached(entry, catalog).expect("get_video_info_cached");
+        let can_play = |info: &video::VideoInfo| match client {
+            Some(client) => client.can_play(info),
+            None => video::playability::all_can_play(&vault.config.playback_clients, info),
+        };
+
+        struct Candidate {
+            source: VideoSourceRenderer,
+            playable: bool,
+            height: u32,
+            burned_in_label: Option<String>,
+        }
 
         let mut alt_formats = Vec::new();
         for alt_format_gfile in gen_tree
             .query_generated_files(entry.db.id, mtk::file_tree::GeneratedFileType::AltFormat)
         {
             let path = gen_tree.path_to_generated_file(&alt_format_gfile);
-            alt_formats.push(VideoSourceRenderer::from_generated(
-                &alt_format_gfile,
-                &path,
-            ));
+            let alt_format_info =
+                video::video_info::get_alt_format_info_cached(&entry.db, catalog, &alt_format_gfile, &path)
+                    .expect("get_alt_format_info_cached");
+            alt_formats.push(Candidate {
+                source: VideoSourceRenderer::from_generated(&alt_format_gfile, &alt_format_info),
+                playable: can_play(&alt_format_info),
+                height: alt_format_info.height,
+                burned_in_label: video::transcode::burned_in_subtitle_key(&alt_format_gfile)
+                    .map(video::subtitle::key_label),
+            });
         }
+        alt_formats.sort_by_key(|candidate| std::cmp::Reverse(candidate.height));
+
+        // Burned-in subtitles first (they're only made when there are no others), then
+        // the original, then the best quality alt format. Playable sources go first.
+        let (burned_in, alt_formats): (Vec<Candidate>, Vec<Candidate>) =
+            alt_formats.into_iter().partition(|candidate| candidate.burned_in_label.is_some());
+        let mut candidates = burned_in;
+        candidates.push(Candidate {
+            source: VideoSourceRenderer::from_entry(entry, &video_info),
+            playable: can_play(&video_info),
+            height: video_info.height,
+            burned_in_label: None,
+        });
+        candidates.extend(alt_formats);
+        candidates.sort_by_key(|candidate| !candidate.playable);
 
+        // Stream a transcoded version until there's something the browser can play
+        let hls_url = (!candidates.iter().any(|candidate| candidate.playable))
+            .then(|| format!("/hls/{}/master.m3u8", entry.db.id));
+        let burned_in_label = candidates[0].burned_in_label.clone();
+        let sources = candidates.into_iter().map(|candidate| candidate.source).collect();
+
+        let subtitles = video::find_all_vtt_subtitles(&entry.fs.file_path, &gen_tree, entry.db.id);
+        let default_subtitle =
+            video::subtitle::default_subtitle(&subtitles, &vault.config.preferred_subtitle_languages);
         let mut vtt_subtitles = Vec::new();
-        for subtitle in video::find_all_vtt_subtitles(&entry.fs.file_path, &gen_tree, entry.db.id) {
+        for (i, subtitle) in subtitles.iter().enumerate() {
             if let video::SubtitleSource::WebVTTFile(path) = &subtitle.source {
-                if gen_tree.is_generated(&path) {
-                    vtt_subtitles.push(SubtitleRenderer {
-                        // TODO: better if we could just return GeneratedFile from video::find_all_vtt_subtitles
-                        file: ServableFileRenderer::GeneratedFile(GeneratedFileRenderer {
-                            entry_id: entry.db.id,
-                            file_type_2l: mtk::file_tree::GeneratedFileType::Subtitle
-                                .to_two_letter_code()
-                                .to_string(),
-                            metadata: subtitle.lang_country.clone(),
-                            extension: "vtt".to_string(),
-                        }),
-                        srclang: subtitle.lang_country.clone(),
-                    });
+                let file = if gen_tree.is_generated(path) {
+                    // TODO: better if we could just return GeneratedFile from video::find_all_vtt_subtitles
+                    ServableFileRenderer::GeneratedFile(GeneratedFileRenderer {
+                        entry_id: entry.db.id,
+                        file_type_2l: mtk::file_tree::GeneratedFileType::Subtitle
+                            .to_two_letter_code()
+                            .to_string(),
+                        metadata: subtitle.key.clone(),
+                        extension: "vtt".to_string(),
+                    })
                 } else {
                     let repo_path = file_tree
-                        .full_to_repo_path(&path)
+                        .full_to_repo_path(path)
                         .expect("full_to_repo_path");
-                    vtt_subtitles.push(SubtitleRenderer {
-                        file: ServableFileRenderer::RawFile(RawFileRenderer {
-                            repo_path: repo_path.to_string(),
-                        }),
-                        srclang: subtitle.lang_country.clone(),
+                    ServableFileRenderer::RawFile(RawFileRenderer {
+                        repo_path: repo_path.to_string(),
                     })
-                }
+                };
+                vtt_subtitles.push(SubtitleRenderer {
+                    file,
+                    srclang: subtitle.language.clone().unwrap_or_else(|| "und".to_string()),
+                    label: subtitle.label(),
+                    is_default: default_subtitle == Some(i),
+                });
             } else {
                 panic!("Only WebVTTFile can be rendered, not {:?}", subtitle);
             }
         }
 
+        let thumbnails_gfile = video::thumbnails::thumbnails_vtt_gfile(entry.db.id);
+        let thumbnails = gen_tree
+            .path_to_generated_file(&thumbnails_gfile)
+            .exists()
+            .then(|| GeneratedFileRenderer::new(&thumbnails_gfile));
+
         VideoPlayerRenderer {
-            main_source: VideoSourceRenderer::from_entry(entry, &video_info),
-            alt_formats: alt_formats,
+            sources,
+            burned_in_label,
             vtt_subtitles: vtt_subtitles,
+            thumbnails,
+            hls_url,
+            chapters: video_info
+                .chapters
+                .iter()
+                .enumerate()
+                .map(|(i, chapter)| ChapterRenderer::from_chapter(i, chapter))
+                .collect(),
 
             loop_and_autoplay: video_info.duration_secs <= 180.,
+            stats: super::VideoStatsRenderer::from_info(video_info),
         }
     }
 }
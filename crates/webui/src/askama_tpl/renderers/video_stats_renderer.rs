@@ -10,6 +10,14 @@ pub struct VideoStatsRenderer {
     pub resolution_label: String,
     // Is this a VR video? (180, fisheye, or 360; or 3D SBS including non-surround)
     pub is_vr: bool,
+    // e.g. "29.97 fps"
+    pub frame_rate_str: Option<String>,
+    // High frame rate (48 fps and up), worth calling out in listings
+    pub is_high_frame_rate: bool,
+    pub is_hdr: bool,
+    // One per audio stream, e.g. "aac 5.1 (eng)"
+    pub audio_summary: Vec<String>,
+    pub num_chapters: usize,
 }
 
 impl VideoStatsRenderer {
@@ -22,16 +30,18 @@ impl VideoStatsRenderer {
                 vertical: false,
                 resolution_label: "??".to_string(),
                 is_vr: false,
+                frame_rate_str: None,
+                is_high_frame_rate: false,
+                is_hdr: false,
+                audio_summary: Vec::new(),
+                num_chapters: 0,
             }
         }
     }
 
-    fn from_info(video_info: video::VideoInfo) -> VideoStatsRenderer {
-        let hours = (video_info.duration_secs / 3600.).floor();
-        let minutes = ((video_info.duration_secs - (hours * 3600.)) / 60.).floor();
-        let seconds = video_info.duration_secs - (hours * 3600.) - (minutes * 60.);
-
-        let min_dimension = std::cmp::min(video_info.width, video_info.height);
+    pub fn from_info(video_info: video::VideoInfo) -> VideoStatsRenderer {
+        let (width, height) = video_info.display_dimensions();
+        let min_dimension = std::cmp::min(width, height);
         let resolution_label = if min_dimension >= 4320 {
             "8k"
         } else if min_dimension >= 2160 {
@@ -43,18 +53,57 @@ impl VideoStatsRenderer {
         };
 
         VideoStatsRenderer {
-            duration_str: if hours.floor() > 0. {
-                format!("{:.0}:{:02.0}:{:02.0}", hours, minutes, seconds.floor())
-            } else {
-                format!("{:02.0}:{:02.0}", minutes, seconds.floor())
-            },
-            vertical: video_info.height > video_info.width,
+            duration_str: format_duration(video_info.duration_secs),
+            vertical: height > width,
             resolution_label: resolution_label.to_string(),
             is_vr: false, // TODO: implement VR detection
+            frame_rate_str: video_info.frame_rate.map(format_frame_rate),
+            is_high_frame_rate: video_info.frame_rate.is_some_and(|fps| fps >= 48.),
+            is_hdr: video_info.is_hdr(),
+            audio_summary: video_info.audio_streams.iter().map(format_audio_stream).collect(),
+            num_chapters: video_info.chapters.len(),
         }
     }
 }
 
+/// "mm:ss", or "h:mm:ss" for an hour or longer
+pub fn format_duration(duration_secs: f64) -> String {
+    let hours = (duration_secs / 3600.).floor();
+    let minutes = ((duration_secs - (hours * 3600.)) / 60.).floor();
+    let seconds = duration_secs - (hours * 3600.) - (minutes * 60.);
+
+    if hours.floor() > 0. {
+        format!("{:.0}:{:02.0}:{:02.0}", hours, minutes, seconds.floor())
+    } else {
+        format!("{:02.0}:{:02.0}", minutes, seconds.floor())
+    }
+}
+
+fn format_frame_rate(fps: f64) -> String {
+    // Whole rates without decimals, NTSC-style ones like 29.97 with them
+    if (fps - fps.round()).abs() < 0.005 {
+        format!("{:.0} fps", fps)
+    } else {
+        format!("{:.2} fps", fps)
+    }
+}
+
+fn format_audio_stream(stream: &video::AudioStreamInfo) -> String {
+    let mut summary = stream.codec.clone();
+    match stream.channels {
+        Some(1) => summary += " mono",
+        Some(2) => summary += " stereo",
+        Some(6) => summary += " 5.1",
+        Some(8) => summary += " 7.1",
+        Some(channels) => summary += &format!(" {}ch", channels),
+        None => {}
+    }
+    if let Some(language) = &stream.language {
+        summary += &format!(" ({})", language);
+    }
+    summary
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,6 +118,7 @@ mod tests {
             width: 0,
             height: 0,
             bitrate: 0,
+            ..Default::default()
         });
         assert_eq!(r.duration_str, "02:03");
     }
@@ -83,6 +133,7 @@ mod tests {
             width: 0,
             height: 0,
             bitrate: 0,
+            ..Default::default()
         });
         assert_eq!(r.duration_str, "3:25:45");
     }
@@ -97,6 +148,7 @@ mod tests {
             width: 1280,
             height: 720,
             bitrate: 0,
+            ..Default::default()
         }).vertical, false);
 
         // Square is not considered vertical
@@ -108,6 +160,7 @@ mod tests {
             width: 512,
             height: 512,
             bitrate: 0,
+            ..Default::default()
         }).vertical, false);
 
         assert_eq!(VideoStatsRenderer::from_info(video::VideoInfo {
@@ -118,6 +171,7 @@ mod tests {
             width: 1080,
             height: 1920,
             bitrate: 0,
+            ..Default::default()
         }).vertical, true);
     }
 
@@ -131,6 +185,7 @@ mod tests {
             width: 640,
             height: 480,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "sd");
 
         // Aspect ratio doesn't matter
@@ -142,6 +197,7 @@ mod tests {
             width: 480,
             height: 640,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "sd");
 
         // Both 720p and 1080p considered "hd"
@@ -153,6 +209,7 @@ mod tests {
             width: 1280,
             height: 720,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "hd");
 
         assert_eq!(VideoStatsRenderer::from_info(video::VideoInfo {
@@ -163,6 +220,7 @@ mod tests {
             width: 1920,
             height: 1080,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "hd");
 
         // 4k
@@ -174,6 +232,7 @@ mod tests {
             width: 3840,
             height: 2160,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "4k");
 
         // 8k
@@ -185,6 +244,34 @@ mod tests {
             width: 7680,
             height: 4320,
             bitrate: 0,
+            ..Default::default()
         }).resolution_label, "8k");
     }
+
+    #[test]
+    fn test_video_stats_extra_info() {
+        let r = VideoStatsRenderer::from_info(video::VideoInfo {
+            width: 2160,
+            height: 3840,
+            rotation: 90,
+            frame_rate: Some(60000. / 1001.),
+            color_transfer: Some("arib-std-b67".to_string()),
+            audio_streams: vec![
+                video::AudioStreamInfo { codec: "aac".to_string(), language: Some("eng".to_string()), channels: Some(6) },
+                video::AudioStreamInfo { codec: "opus".to_string(), language: None, channels: Some(3) },
+            ],
+            chapters: vec![video::Chapter::default(); 3],
+            ..Default::default()
+        });
+        // Rotated to landscape
+        assert!(!r.vertical);
+        assert_eq!(r.resolution_label, "4k");
+        assert_eq!(r.frame_rate_str.as_deref(), Some("59.94 fps"));
+        assert!(r.is_high_frame_rate);
+        assert!(r.is_hdr);
+        assert_eq!(r.audio_summary, vec!["aac 5.1 (eng)", "opus 3ch"]);
+        assert_eq!(r.num_chapters, 3);
+
+        assert_eq!(format_frame_rate(25.), "25 fps");
+    }
 }
@@ -2,6 +2,7 @@ use askama::Template;
 
 use mtk::FilerConfig;
 use mtk::Entry;
+use mtk::query::surprise::WeightBreakdown;
 
 use super::renderers;
 use super::filters;
@@ -15,6 +16,8 @@ use super::save;
 pub struct EntryListTemplate {
     pub title: String,
     pub entry_list: partial::DirListingPartial,
+    // (label, url) pairs shown under the title
+    pub header_links: Vec<(String, String)>,
 }
 
 impl EntryListTemplate {
@@ -22,6 +25,43 @@ impl EntryListTemplate {
         EntryListTemplate {
             title: title.to_string(),
             entry_list: partial::DirListingPartial::from(contents, layout),
+            header_links: Vec::new(),
+        }
+    }
+
+    pub fn with_header_link(mut self, label: &str, url: String) -> EntryListTemplate {
+        self.header_links.push((label.to_string(), url));
+        self
+    }
+}
+
+#[derive(Template)]
+#[template(path = "surprise_debug.ask.html")]
+pub struct SurpriseDebugTemplate {
+    pub base_path: String,
+    pub profile_name: String,
+    pub profile_names: Vec<String>,
+    pub seed: Option<u64>,
+    pub rows: Vec<(renderers::EntryRenderer, WeightBreakdown)>,
+}
+
+impl SurpriseDebugTemplate {
+    pub fn new(
+        base_path: &str,
+        profile_name: &str,
+        profile_names: Vec<String>,
+        seed: Option<u64>,
+        sampled: &[(Entry, WeightBreakdown)],
+    ) -> SurpriseDebugTemplate {
+        SurpriseDebugTemplate {
+            base_path: base_path.to_string(),
+            profile_name: profile_name.to_string(),
+            profile_names,
+            seed,
+            rows: sampled
+                .iter()
+                .map(|(entry, breakdown)| (renderers::EntryRenderer::from(entry), breakdown.clone()))
+                .collect(),
         }
     }
 }
@@ -35,10 +75,21 @@ pub struct DirIndexTemplate {
     pub entry_editor: edit::EntryEditorPartial,
     pub dir_listing: partial::DirListingPartial,
     pub save_form: save::SaveInlineFragment,
+    pub shelves: Vec<partial::ShelfPartial>,
+    // Shown before dir_listing, which then only has the other entries
+    pub series_listing: Option<partial::SeriesListingPartial>,
+    pub gallery_listing: Option<partial::GalleryListingPartial>,
+    // Images in the order they're listed, for the slideshow
+    pub slideshow: Vec<renderers::EntryLink>,
 }
 
 impl DirIndexTemplate {
-    pub fn new(dir_entry: &Entry, contents: &Vec<Entry>, layout: ListingLayout) -> DirIndexTemplate {
+    pub fn new(
+        dir_entry: &Entry,
+        contents: &Vec<Entry>,
+        layout: ListingLayout,
+        shelves: Vec<partial::ShelfPartial>,
+    ) -> DirIndexTemplate {
         DirIndexTemplate {
             entry: renderers::EntryRenderer::from(&dir_entry),
 
@@ -48,8 +99,32 @@ impl DirIndexTemplate {
             save_form: save::SaveInlineFragment {
                 current_path: dir_entry.fs.repo_path.0.clone(),
             },
+            shelves,
+            series_listing: None,
+            gallery_listing: None,
+            slideshow: slideshow_images(contents),
         }
     }
+
+    pub fn with_series_listing(mut self, episodes: &[Entry], history_db: &mtk::userdata::HistoryDb) -> DirIndexTemplate {
+        self.series_listing = Some(partial::SeriesListingPartial::from(episodes, history_db));
+        self
+    }
+
+    /// `images` are shown first, before the rest of the listing
+    pub fn with_gallery_listing(mut self, images: &[Entry]) -> DirIndexTemplate {
+        self.gallery_listing = Some(partial::GalleryListingPartial::from(images));
+        self.slideshow.splice(0..0, slideshow_images(images));
+        self
+    }
+}
+
+fn slideshow_images(entries: &[Entry]) -> Vec<renderers::EntryLink> {
+    entries
+        .iter()
+        .filter(|entry| mtk::filetype::is_image(&entry.fs.file_path))
+        .map(renderers::EntryLink::from)
+        .collect()
 }
 
 #[derive(Template)]
@@ -62,6 +137,7 @@ pub struct ViewEntryTemplate {
     pub parent_crumbs: partial::ParentCrumbsPartial,
     pub entry_editor: edit::EntryEditorPartial,
     pub history: partial::HistoryPartial,
+    pub similar: partial::ShelfPartial,
 }
 
 impl ViewEntryTemplate {
@@ -70,6 +146,7 @@ impl ViewEntryTemplate {
         entry: &Entry,
         entry_renderer: renderers::EntryRenderer,
         history: mtk::userdata::ViewHistory,
+        similar: partial::ShelfPartial,
     ) -> ViewEntryTemplate {
         ViewEntryTemplate {
             entry: entry_renderer,
@@ -86,6 +163,7 @@ impl ViewEntryTemplate {
             parent_crumbs: partial::ParentCrumbsPartial::from(entry.fs.repo_path.clone()),
             entry_editor: edit::EntryEditorPartial::from(&entry.db),
             history: partial::HistoryPartial { history },
+            similar,
         }
     }
 }
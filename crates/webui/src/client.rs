@@ -0,0 +1,23 @@
+//! Which browser a request came from, so videos can be played from a source it supports
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use mtk::media::video::Client;
+
+/// The requesting browser, from the Sec-CH-UA client hint or the User-Agent. None
+/// if it couldn't be recognised.
+pub struct RequestClient(pub Option<Client>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestClient {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let client = headers
+            .get_one("Sec-CH-UA")
+            .and_then(Client::from_client_hint)
+            .or_else(|| headers.get_one("User-Agent").and_then(Client::from_user_agent));
+        Outcome::Success(RequestClient(client))
+    }
+}
@@ -2,19 +2,28 @@ use std::path::PathBuf;
 
 use askama::Template;
 
-use mtk::catalog::DbEntry;
+use mtk::catalog::{DbEntry, SpecialEntryType};
 use rocket::State;
 use rocket::response::{Redirect, content};
+use rocket::serde::json::Json;
 
+use mtk::browse::sort::{sort_entries, SortOrder};
 use mtk::filetype;
+use mtk::query;
+use mtk::userdata::HistoryDb;
 use mtk::{Entry, RepoPathBuf, Vault};
 
 use crate::askama_tpl;
+use crate::client::RequestClient;
 
-#[get("/entry/<path..>?<layout>")]
+const HOME_SHELF_SIZE: usize = 12;
+
+#[get("/entry/<path..>?<layout>&<sort>")]
 pub async fn view_entry(
     path: PathBuf,
     layout: Option<String>,
+    sort: Option<String>,
+    client: RequestClient,
     stash: &State<Vault>,
 ) -> content::RawHtml<String> {
     let mut catalog = stash.open_catalog().expect("open_catalog");
@@ -32,23 +41,53 @@ pub async fn view_entry(
     };
 
     if entry.fs.file_type.is_dir {
-        let layout =
-            askama_tpl::ListingLayout::from_str(layout.as_deref().unwrap_or("compact-grid"));
+        // Series and galleries get their own listings, unless another layout was picked
+        let use_special_listing = layout.is_none();
+        // Archives are usually comics, so they open straight into the reader
+        let default_layout = if entry.fs.is_archive() { "reader" } else { "compact-grid" };
+        let layout = askama_tpl::ListingLayout::from_str(layout.as_deref().unwrap_or(default_layout));
         println!("Layout: {:?}", layout);
-        render_dir_index(&entry, &file_tree, &mut catalog, layout)
+        let shelves = if entry.fs.repo_path.as_str().is_empty() {
+            home_shelves(&file_tree, &mut catalog, &stash.open_history_db())
+        } else {
+            Vec::new()
+        };
+        let sort = SortOrder::parse(sort.as_deref().unwrap_or("name"));
+        let special_type = if use_special_listing { entry.db.special_type.clone() } else { None };
+        render_dir_index(&entry, stash, &mut catalog, layout, sort, special_type, shelves)
     } else {
         println!("File entry at {}", repo_path);
         let mut history_db = stash.open_history_db();
         let mut entry_renderer = askama_tpl::EntryRenderer::from(&entry);
         if filetype::is_video(&entry.fs.file_path) {
             entry_renderer.video_player =
-                Some(askama_tpl::VideoPlayerRenderer::new(&stash, &entry));
+                Some(askama_tpl::VideoPlayerRenderer::new(stash, &mut catalog, &entry, client.0));
+            if let Some(position) = query::series::series_position(&entry, &file_tree, &mut catalog).expect("series_position") {
+                entry_renderer.series_nav = Some(askama_tpl::SeriesNavRenderer::new(&position, &history_db));
+            }
+        }
+        if filetype::is_image(&entry.fs.file_path) {
+            entry_renderer.image_info = askama_tpl::ImageInfoRenderer::from(&entry);
+            entry_renderer.render_animated_alt(&stash.new_generated_tree());
         }
+        let history = history_db.get(entry.db.id).unwrap();
+        if filetype::is_audio(&entry.fs.file_path) {
+            entry_renderer.audio_player = Some(askama_tpl::AudioPlayerRenderer::new(&entry, &history));
+        }
+        if filetype::is_document(&entry.fs.file_path) {
+            entry_renderer.document_reader = askama_tpl::DocumentReaderRenderer::new(&entry, &history);
+        }
+        let similar = query::similar::read_similar_entries(&entry.db, &file_tree, &catalog, HOME_SHELF_SIZE);
         let template = askama_tpl::ViewEntryTemplate::new(
             &stash.config,
             &entry,
             entry_renderer,
-            history_db.get(entry.db.id).unwrap(),
+            history,
+            askama_tpl::ShelfPartial::from(
+                "More Like This",
+                &format!("/similar/{}", askama_tpl::urlencode_parts(&entry.fs.repo_path.0)),
+                &similar,
+            ),
         );
         if filetype::is_image(&entry.fs.file_path) {
             // TODO(fyhuang): should we do this in JS instead?
@@ -60,10 +99,14 @@ pub async fn view_entry(
 
 fn render_dir_index(
     entry: &Entry,
-    file_tree: &mtk::file_tree::FileTree,
+    stash: &Vault,
     catalog: &mut mtk::catalog::Catalog,
     layout: askama_tpl::ListingLayout,
+    sort: SortOrder,
+    special_type: Option<SpecialEntryType>,
+    shelves: Vec<askama_tpl::ShelfPartial>,
 ) -> content::RawHtml<String> {
+    let file_tree = stash.new_file_tree();
     // TODO: use scan
     let mut dir_entries = Vec::new();
     for child_fs_entry in file_tree.listdir(&entry.fs.repo_path).expect("listdir") {
@@ -82,31 +125,43 @@ fn render_dir_index(
         });
     }
 
-    sort_entries_name(&mut dir_entries);
-
-    let template = askama_tpl::DirIndexTemplate::new(&entry, &dir_entries, layout);
-    content::RawHtml(template.render().unwrap())
-}
-
-fn sort_entries_name(entries: &mut [Entry]) {
-    entries.sort_by(|a, b| {
-        // First, sort folders before files
-        let a_is_dir = a.fs.file_type.is_dir;
-        let b_is_dir = b.fs.file_type.is_dir;
+    sort_entries(&mut dir_entries, sort);
 
-        if a_is_dir && !b_is_dir {
-            return std::cmp::Ordering::Less;
+    let template = match special_type {
+        Some(SpecialEntryType::SeriesDir) => {
+            let episodes = query::series::episodes(&entry.fs.repo_path, &file_tree, catalog).expect("episodes");
+            dir_entries.retain(|child| !filetype::is_video(&child.fs.file_path));
+            askama_tpl::DirIndexTemplate::new(entry, &dir_entries, layout, shelves)
+                .with_series_listing(&episodes, &stash.open_history_db())
         }
-        if !a_is_dir && b_is_dir {
-            return std::cmp::Ordering::Greater;
+        Some(SpecialEntryType::GalleryDir) => {
+            let (images, others): (Vec<Entry>, Vec<Entry>) =
+                dir_entries.into_iter().partition(|child| filetype::is_image(&child.fs.file_path));
+            askama_tpl::DirIndexTemplate::new(entry, &others, layout, shelves)
+                .with_gallery_listing(&images)
         }
+        _ => askama_tpl::DirIndexTemplate::new(entry, &dir_entries, layout, shelves),
+    };
+    content::RawHtml(template.render().unwrap())
+}
 
-        // Then sort by name, case-insensitive
-        let a_name = a.display_title().to_ascii_lowercase();
-        let b_name = b.display_title().to_ascii_lowercase();
-
-        a_name.cmp(&b_name)
-    });
+fn home_shelves(
+    file_tree: &mtk::file_tree::FileTree,
+    catalog: &mut mtk::catalog::Catalog,
+    history_db: &HistoryDb,
+) -> Vec<askama_tpl::ShelfPartial> {
+    let continue_watching =
+        query::feeds::continue_watching(file_tree, catalog, history_db, HOME_SHELF_SIZE);
+    let continue_reading =
+        query::feeds::continue_reading(file_tree, catalog, history_db, HOME_SHELF_SIZE);
+    let recently_added =
+        query::feeds::recently_added(&RepoPathBuf::from(""), file_tree, catalog, HOME_SHELF_SIZE);
+
+    vec![
+        askama_tpl::ShelfPartial::from("Continue Watching", "/continue_watching", &continue_watching),
+        askama_tpl::ShelfPartial::from("Continue Reading", "/continue_reading", &continue_reading),
+        askama_tpl::ShelfPartial::from("Recently Added", "/recent/", &recently_added),
+    ]
 }
 
 fn should_hide_entry(entry: &DbEntry) -> bool {
@@ -127,11 +182,26 @@ pub async fn view_entry_by_id(id: i64, stash: &State<Vault>) -> Redirect {
     let repo_path = catalog.get_by_id(id).expect("get_by_id").repo_path;
     Redirect::to(uri!(view_entry(
         PathBuf::from(repo_path.to_string()),
+        Option::<String>::None,
         Option::<String>::None
     )))
 }
 
+/// The episode after the given one in its series, for autoplaying the next episode.
+/// 404 if it isn't in a series or is the last episode.
+#[get("/api/next_episode/<id>")]
+pub async fn api_next_episode(id: i64, stash: &State<Vault>) -> Option<Json<askama_tpl::EntryLink>> {
+    let mut catalog = stash.open_catalog().expect("open_catalog");
+    let file_tree = stash.new_file_tree();
+    let db_entry = catalog.get_by_id(id)?;
+    let fs_entry = file_tree.get_fs_entry(&db_entry.repo_path).ok()?;
+    let entry = Entry { fs: fs_entry, db: db_entry };
+
+    let position = query::series::series_position(&entry, &file_tree, &mut catalog).expect("series_position")?;
+    position.next().map(|next| Json(askama_tpl::EntryLink::from(next)))
+}
+
 #[get("/")]
 pub async fn index() -> Redirect {
-    Redirect::to(uri!(view_entry("/", Option::<String>::None)))
+    Redirect::to(uri!(view_entry("/", Option::<String>::None, Option::<String>::None)))
 }
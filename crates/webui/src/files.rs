@@ -16,7 +16,7 @@ fn get_raw_file_responder(path: PathBuf, stash: &Vault) -> RawFileResponder {
     let content_type = {
         if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
             match ext {
-                "mkv" => ContentType::new("video", "webm"),
+                "mkv" => ContentType::new("video", "x-matroska"),
                 _ => ContentType::from_extension(&ext).unwrap_or(ContentType::Plain),
             }
         } else {
@@ -33,7 +33,7 @@ fn get_raw_file_responder(path: PathBuf, stash: &Vault) -> RawFileResponder {
     }
 }
 
-fn local_raw_file_responder(path: &Path) -> RawFileResponder {
+pub(crate) fn local_raw_file_responder(path: &Path) -> RawFileResponder {
     let file = std::fs::File::open(path).expect("File::open");
     let metadata = std::fs::metadata(path).expect("metadata");
 
@@ -42,7 +42,7 @@ fn local_raw_file_responder(path: &Path) -> RawFileResponder {
         .unwrap_or("")
         .to_lowercase();
     let content_type = match extension.as_ref() {
-        "mkv" => ContentType::new("video", "webm"),
+        "mkv" => ContentType::new("video", "x-matroska"),
         _ => ContentType::from_extension(&extension).unwrap_or(ContentType::Plain),
     };
 
@@ -72,7 +72,7 @@ pub async fn generated_file_get(
     metadata: String,
     extension: String,
     stash: &State<Vault>,
-) -> RawFileResponder {
+) -> Option<RawFileResponder> {
     // TODO(fyhuang): tokio
     let gen_tree = stash.new_generated_tree();
 
@@ -84,7 +84,11 @@ pub async fn generated_file_get(
         extension: extension.clone(),
     };
     let fs_path = gen_tree.path_to_generated_file(&gfile);
-    local_raw_file_responder(&fs_path)
+    // Not every generated file exists (e.g. a job hasn't run yet)
+    if !fs_path.exists() {
+        return None;
+    }
+    Some(local_raw_file_responder(&fs_path))
 }
 
 #[get("/static/index.js")]
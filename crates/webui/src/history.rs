@@ -33,6 +33,30 @@ pub async fn api_video_history(data: Json<VideoHistoryRequest>, stash: &State<Va
     }
 }
 
+#[derive(Deserialize)]
+pub struct ReadingHistoryRequest {
+    path: String,
+    page: Option<i64>,
+    ratio: f32,
+}
+
+#[post("/api/reading_history", data = "<data>")]
+pub async fn api_reading_history(data: Json<ReadingHistoryRequest>, stash: &State<Vault>) {
+    let catalog = stash.open_catalog().expect("open_catalog");
+    let mut history_db = stash.open_history_db();
+
+    match catalog.path_to_id(&RepoPathBuf::from(&data.path)) {
+        Some(id) => {
+            history_db
+                .mark_read(id, data.page, data.ratio.clamp(0.0, 1.0))
+                .expect("mark_read");
+        }
+        None => {
+            // TODO: return a 404
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ClearHistoryRequest {
     path: String,
@@ -0,0 +1,81 @@
+//! HLS playlists and segments, transcoded on demand
+
+use std::path::PathBuf;
+
+use rocket::http::ContentType;
+use rocket::State;
+
+use mtk::media::video::{self, hls, transcode::TranscodeProfile};
+use mtk::{Entry, Vault};
+
+use crate::raw_file_responder::RawFileResponder;
+
+fn mpegurl_content_type() -> ContentType {
+    ContentType::new("application", "vnd.apple.mpegurl")
+}
+
+/// The video file for an entry, and its (preferably cached) info.
+fn find_video(entry_id: i64, stash: &Vault) -> Option<(PathBuf, video::VideoInfo)> {
+    let mut catalog = stash.open_catalog().expect("open_catalog");
+    let db_entry = catalog.get_by_id(entry_id)?;
+    let fs_entry = stash.new_file_tree().get_fs_entry(&db_entry.repo_path).ok()?;
+    if !mtk::filetype::is_video(&fs_entry.file_path) {
+        return None;
+    }
+
+    let entry = Entry { fs: fs_entry, db: db_entry };
+    let video_info = video::get_video_info_cached(&entry, &mut catalog).ok()?;
+    Some((entry.fs.file_path, video_info))
+}
+
+/// Only the renditions listed in the master playlist can be requested
+fn parse_rendition(profile: &str) -> Option<TranscodeProfile> {
+    hls::hls_renditions()
+        .into_iter()
+        .find(|rendition| rendition.name == profile)
+}
+
+#[get("/hls/<entry_id>/master.m3u8")]
+pub async fn master_playlist(entry_id: i64, stash: &State<Vault>) -> Option<(ContentType, String)> {
+    let (_, video_info) = find_video(entry_id, stash)?;
+    let playlist = hls::master_playlist(&video_info, |profile| format!("{}/index.m3u8", profile.name));
+    Some((mpegurl_content_type(), playlist))
+}
+
+#[get("/hls/<entry_id>/<profile>/index.m3u8")]
+pub async fn media_playlist(entry_id: i64, profile: &str, stash: &State<Vault>) -> Option<(ContentType, String)> {
+    parse_rendition(profile)?;
+    let (_, video_info) = find_video(entry_id, stash)?;
+    let playlist = hls::media_playlist(video_info.duration_secs, |index| format!("{}.ts", index));
+    Some((mpegurl_content_type(), playlist))
+}
+
+#[get("/hls/<entry_id>/<profile>/<segment>")]
+pub async fn segment(entry_id: i64, profile: &str, segment: &str, stash: &State<Vault>) -> Option<RawFileResponder> {
+    let profile = parse_rendition(profile)?;
+    let index: usize = segment.strip_suffix(".ts")?.parse().ok()?;
+    let (video_path, video_info) = find_video(entry_id, stash)?;
+
+    // Transcoding takes a few seconds, so don't block the async runtime
+    let gen_tree = stash.new_generated_tree();
+    let segment_path = tokio::task::spawn_blocking(move || {
+        hls::get_or_transcode_segment(&video_path, &video_info, &gen_tree, entry_id, &profile, index)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .expect("spawn_blocking");
+
+    match segment_path {
+        Ok(segment_path) => {
+            let mut responder = crate::files::local_raw_file_responder(&segment_path);
+            responder.content_type = ContentType::new("video", "mp2t");
+            // Segments never change once transcoded
+            responder.cache_control = Some("max-age=86400".to_string());
+            Some(responder)
+        }
+        Err(e) => {
+            eprintln!("HLS segment {} of entry {}: {}", index, entry_id, e);
+            None
+        }
+    }
+}
@@ -4,6 +4,9 @@
 mod range_limited_file;
 pub mod raw_file_responder;
 
+// Request guards
+pub mod client;
+
 // Templates
 pub mod askama_tpl;
 
@@ -11,6 +14,7 @@ pub mod askama_tpl;
 pub mod files;
 pub mod entry;
 pub mod preview;
+pub mod hls;
 
 pub mod history;
 pub mod edit;
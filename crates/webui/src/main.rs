@@ -2,7 +2,7 @@
 extern crate rocket;
 
 use mtk::Vault;
-use webui::{entry, files, preview, history, query, edit, save};
+use webui::{entry, files, preview, hls, history, query, edit, save};
 
 fn mount_all_routes(
     builder: rocket::Rocket<rocket::Build>,
@@ -14,6 +14,7 @@ fn mount_all_routes(
             routes![
                 entry::view_entry,
                 entry::view_entry_by_id,
+                entry::api_next_episode,
                 entry::index,
             ],
         )
@@ -24,10 +25,23 @@ fn mount_all_routes(
         )
         .mount(prefix, routes![files::raw_file_get, files::raw_file_head])
         .mount(prefix, routes![files::generated_file_get])
-        .mount(prefix, routes![query::surprise, query::search])
+        .mount(prefix, routes![hls::master_playlist, hls::media_playlist, hls::segment])
         .mount(
             prefix,
-            routes![history::api_video_history, history::api_clear_history],
+            routes![
+                query::surprise,
+                query::surprise_debug,
+                query::similar,
+                query::visually_similar,
+                query::search,
+                query::continue_watching,
+                query::continue_reading,
+                query::recently_added,
+            ],
+        )
+        .mount(
+            prefix,
+            routes![history::api_video_history, history::api_reading_history, history::api_clear_history],
         )
         .mount(prefix, routes![edit::edit_entry])
         .mount(prefix, routes![save::download_url])
@@ -41,7 +55,9 @@ async fn main() -> Result<(), rocket::Error> {
         .merge(("address", "0.0.0.0"))
         .merge(("port", 7784));
 
-    let builder = rocket::custom(rocket_conf).manage(stash);
+    let builder = rocket::custom(rocket_conf)
+        .manage(stash)
+        .manage(mtk::query::visual::VisualIndexCache::default());
     let b2 = mount_all_routes(builder, "/");
 
     let _rocket = b2.launch().await?;
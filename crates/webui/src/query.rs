@@ -4,35 +4,128 @@ use askama::Template;
 use rocket::response::content;
 use rocket::State;
 
-use mtk::{RepoPathBuf, Vault};
+use mtk::{Entry, RepoPathBuf, Vault};
 use mtk::query;
 
 use crate::askama_tpl;
 
-#[get("/surprise/<path..>?<mode>")]
+/// Seed for "today's picks", which stays the same for the whole day.
+fn todays_seed() -> u64 {
+    chrono::Local::now().format("%Y%m%d").to_string().parse().unwrap()
+}
+
+fn surprise_url(route: &str, base_path: &RepoPathBuf, mode: &str, seed: u64) -> String {
+    format!(
+        "/{}/{}?mode={}&seed={}",
+        route,
+        askama_tpl::urlencode_parts(&base_path.0),
+        askama_tpl::urlencode_parts(mode),
+        seed
+    )
+}
+
+#[get("/surprise/<path..>?<mode>&<seed>")]
 pub async fn surprise(
     path: PathBuf,
     stash: &State<Vault>,
     mode: Option<String>,
-) -> content::RawHtml<String> {
+    seed: Option<u64>,
+) -> Option<content::RawHtml<String>> {
     let mut catalog = stash.open_catalog().expect("open_catalog");
     let history_db = stash.open_history_db();
-    let recency_weight = if let Some(mode) = mode {
-        mode == "recent"
-    } else {
-        false
-    };
+    let mode = mode.unwrap_or("default".to_string());
+    let profile = stash.config.surprise_profile(&mode)?;
+    // Always sample with a seed, so the page can be linked to
+    let seed = seed.unwrap_or_else(rand::random);
 
     // TODO: migrate
+    let base_path = RepoPathBuf::from(path.as_path());
     let entries = query::surprise::surprise_entries(
-        &RepoPathBuf::from(path.as_path()),
+        &base_path,
+        &stash.new_file_tree(),
+        &mut catalog,
+        &history_db,
+        50,
+        &profile,
+        Some(seed),
+    );
+    let template = askama_tpl::EntryListTemplate::new("Surprise Me", &entries, askama_tpl::ListingLayout::CompactCardGrid)
+        .with_header_link("Permalink", surprise_url("surprise", &base_path, &mode, seed))
+        .with_header_link("Today's picks", surprise_url("surprise", &base_path, &mode, todays_seed()))
+        .with_header_link("Why these?", surprise_url("surprise_debug", &base_path, &mode, seed));
+    Some(content::RawHtml(template.render().unwrap()))
+}
+
+#[get("/surprise_debug/<path..>?<mode>&<seed>")]
+pub async fn surprise_debug(
+    path: PathBuf,
+    stash: &State<Vault>,
+    mode: Option<String>,
+    seed: Option<u64>,
+) -> Option<content::RawHtml<String>> {
+    let mut catalog = stash.open_catalog().expect("open_catalog");
+    let history_db = stash.open_history_db();
+    let profile_name = mode.unwrap_or("default".to_string());
+    let profile = stash.config.surprise_profile(&profile_name)?;
+
+    let base_path = RepoPathBuf::from(path.as_path());
+    let sampled = query::surprise::surprise_entries_explained(
+        &base_path,
         &stash.new_file_tree(),
         &mut catalog,
         &history_db,
         50,
-        recency_weight,
+        &profile,
+        seed,
+    );
+    let template = askama_tpl::SurpriseDebugTemplate::new(
+        &base_path.0,
+        &profile_name,
+        stash.config.surprise_profile_names(),
+        seed,
+        &sampled,
+    );
+    Some(content::RawHtml(template.render().unwrap()))
+}
+
+#[get("/continue_watching")]
+pub async fn continue_watching(stash: &State<Vault>) -> content::RawHtml<String> {
+    let mut catalog = stash.open_catalog().expect("open_catalog");
+    let history_db = stash.open_history_db();
+    let entries = query::feeds::continue_watching(
+        &stash.new_file_tree(),
+        &mut catalog,
+        &history_db,
+        100,
     );
-    let template = askama_tpl::EntryListTemplate::new("Surprise Me", &entries, askama_tpl::ListingLayout::CompactCardGrid);
+    let template = askama_tpl::EntryListTemplate::new("Continue Watching", &entries, askama_tpl::ListingLayout::CompactCardGrid);
+    content::RawHtml(template.render().unwrap())
+}
+
+#[get("/continue_reading")]
+pub async fn continue_reading(stash: &State<Vault>) -> content::RawHtml<String> {
+    let mut catalog = stash.open_catalog().expect("open_catalog");
+    let history_db = stash.open_history_db();
+    let entries = query::feeds::continue_reading(
+        &stash.new_file_tree(),
+        &mut catalog,
+        &history_db,
+        100,
+    );
+    let template = askama_tpl::EntryListTemplate::new("Continue Reading", &entries, askama_tpl::ListingLayout::CompactCardGrid);
+    content::RawHtml(template.render().unwrap())
+}
+
+#[get("/recent/<path..>")]
+pub async fn recently_added(path: PathBuf, stash: &State<Vault>) -> content::RawHtml<String> {
+    let catalog = stash.open_catalog().expect("open_catalog");
+    let entries = query::feeds::recently_added(
+        &RepoPathBuf::from(path.as_path()),
+        &stash.new_file_tree(),
+        &catalog,
+        100,
+    );
+    let template = askama_tpl::EntryListTemplate::new("Recently Added", &entries, askama_tpl::ListingLayout::CompactCardGrid);
     content::RawHtml(template.render().unwrap())
 }
 
@@ -48,3 +141,57 @@ pub async fn search(q: String, stash: &State<Vault>) -> content::RawHtml<String>
     let template = askama_tpl::EntryListTemplate::new("Search Results", &entries, askama_tpl::ListingLayout::CompactCardGrid);
     content::RawHtml(template.render().unwrap())
 }
+
+#[get("/similar/<path..>")]
+pub async fn similar(path: PathBuf, stash: &State<Vault>) -> Option<content::RawHtml<String>> {
+    let catalog = stash.open_catalog().expect("open_catalog");
+    let file_tree = stash.new_file_tree();
+
+    let fs_entry = file_tree.get_fs_entry(&RepoPathBuf::from(path.as_path())).ok()?;
+    let db_entry = catalog.get_by_path(&fs_entry.repo_path)?;
+    let target = Entry { fs: fs_entry, db: db_entry };
+
+    let entries = query::similar::read_similar_entries(
+        &target.db,
+        &file_tree,
+        &catalog,
+        query::similar::NUM_STORED_SIMILAR,
+    );
+    let title = format!("More Like {}", target.display_title());
+    let template = askama_tpl::EntryListTemplate::new(&title, &entries, askama_tpl::ListingLayout::CompactCardGrid);
+    Some(content::RawHtml(template.render().unwrap()))
+}
+
+#[get("/visually_similar/<path..>?<max_distance>")]
+pub async fn visually_similar(
+    path: PathBuf,
+    max_distance: Option<u32>,
+    stash: &State<Vault>,
+    index_cache: &State<query::visual::VisualIndexCache>,
+) -> Option<content::RawHtml<String>> {
+    let catalog = stash.open_catalog().expect("open_catalog");
+    let file_tree = stash.new_file_tree();
+
+    let fs_entry = file_tree.get_fs_entry(&RepoPathBuf::from(path.as_path())).ok()?;
+    let db_entry = catalog.get_by_path(&fs_entry.repo_path)?;
+    let target = Entry { fs: fs_entry, db: db_entry };
+
+    // Entries without a hash yet (the "phash" job hasn't run) have nothing to compare
+    let entries: Vec<Entry> = match query::visual::read_phash(&target.db) {
+        Some(hash) => query::visual::visually_similar(
+            hash,
+            &index_cache.get(&catalog),
+            &file_tree,
+            &catalog,
+            max_distance.unwrap_or(query::visual::DEFAULT_MAX_DISTANCE),
+        )
+            .into_iter()
+            .map(|(entry, _)| entry)
+            .filter(|entry| entry.db.id != target.db.id)
+            .collect(),
+        None => Vec::new(),
+    };
+    let title = format!("Visually Similar to {}", target.display_title());
+    let template = askama_tpl::EntryListTemplate::new(&title, &entries, askama_tpl::ListingLayout::CompactCardGrid);
+    Some(content::RawHtml(template.render().unwrap()))
+}
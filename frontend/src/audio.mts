@@ -0,0 +1,36 @@
+import { markViewed } from './history.mts';
+
+export function setupAudioPlayer(): void {
+    const mainAudio = document.querySelector('#mainaudio') as HTMLAudioElement;
+
+    // Resume where we left off, unless that was the very end
+    const resume = mainAudio.dataset.resume;
+    if (resume !== undefined) {
+        mainAudio.addEventListener('loadedmetadata', () => {
+            const resumeSecs = parseFloat(resume);
+            if (resumeSecs < mainAudio.duration - 5) {
+                mainAudio.currentTime = resumeSecs;
+            }
+        }, { once: true });
+    }
+
+    // Update history
+    mainAudio.addEventListener('timeupdate', () => {
+        markViewed(mainAudio.currentTime, mainAudio.currentTime / mainAudio.duration);
+    });
+
+    // Same keyboard shortcuts as the video player
+    document.addEventListener('keyup', (event) => {
+        if (event.key === 'k') {
+            if (mainAudio.paused) {
+                mainAudio.play();
+            } else {
+                mainAudio.pause();
+            }
+        } else if (event.key === 'j') {
+            mainAudio.currentTime -= 10;
+        } else if (event.key === 'l') {
+            mainAudio.currentTime += 10;
+        }
+    });
+}
@@ -1,8 +1,11 @@
 // This is just a copy of EntryRenderer that can be accessed from JS
 interface RenderedEntry {
     repo_path: string;
+    entry_id: number;
 
     is_video: boolean;
+    is_audio: boolean;
+    is_document: boolean;
 }
 
 export function currEntry(): RenderedEntry | null {
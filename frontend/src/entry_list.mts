@@ -6,6 +6,13 @@ export function setupPreviewFromCcGrid(): void {
     const cards = document.querySelectorAll<HTMLDivElement>('.cc-card');
     const floatPreviewBox = document.getElementById('float-preview-box') as HTMLDivElement;
     const floatPreviewImage = document.getElementById('float-preview-img') as HTMLImageElement;
+    const floatPreviewVideo = document.getElementById('float-preview-vid') as HTMLVideoElement | null;
+
+    // Swap the preview image for the minivid once (if) it loads
+    floatPreviewVideo?.addEventListener('loadeddata', () => {
+        floatPreviewImage.style.display = 'none';
+        floatPreviewVideo.style.display = 'block';
+    });
 
     cards.forEach(card => {
         const cardPreview = card.querySelector<HTMLImageElement>('img.cc-card-preview');
@@ -32,13 +39,80 @@ export function setupPreviewFromCcGrid(): void {
             // Set the preview image source
             floatPreviewImage.src = cardPreviewSrc;
 
+            const minividSrc = card.dataset.minivid;
+            if (floatPreviewVideo?.dataset.src === undefined) {
+                floatPreviewImage.style.display = 'block';
+            }
+            if (floatPreviewVideo && minividSrc && floatPreviewVideo.dataset.src !== minividSrc) {
+                floatPreviewVideo.dataset.src = minividSrc;
+                floatPreviewVideo.src = minividSrc;
+                floatPreviewVideo.play().catch(() => { /* no minivid yet */ });
+            }
+
             // Show the preview box
             floatPreviewBox.style.display = 'block';
         });
 
-        card.addEventListener('mouseout', () => {
+        card.addEventListener('mouseout', (event) => {
             // Hide the preview box
             floatPreviewBox.style.display = 'none';
+            // Keep the minivid playing while moving between elements inside the card
+            if (floatPreviewVideo && !card.contains(event.relatedTarget as Node | null)) {
+                stopMinivid(floatPreviewVideo);
+            }
+        });
+    });
+}
+
+function stopMinivid(video: HTMLVideoElement): void {
+    video.pause();
+    video.style.display = 'none';
+    video.removeAttribute('src');
+    delete video.dataset.src;
+    video.load();
+}
+
+/// Play the minivid in place of the preview image while hovering over a gallery grid entry
+export function setupMinividFromGalleryGrid(): void {
+    const gridEntries = document.querySelectorAll<HTMLDivElement>('.grid_entry[data-minivid]');
+
+    gridEntries.forEach(gridEntry => {
+        const previewImage = gridEntry.querySelector<HTMLImageElement>('img.preview_img');
+        const minividSrc = gridEntry.dataset.minivid;
+        if (!previewImage || !minividSrc) {
+            return;
+        }
+
+        let video: HTMLVideoElement | null = null;
+
+        gridEntry.addEventListener('mouseenter', () => {
+            video = document.createElement('video');
+            video.className = 'preview_img preview_minivid';
+            video.muted = true;
+            video.loop = true;
+            video.playsInline = true;
+            video.width = previewImage.clientWidth;
+            video.height = previewImage.clientHeight;
+            video.style.display = 'none';
+
+            const thisVideo = video;
+            video.addEventListener('loadeddata', () => {
+                previewImage.style.display = 'none';
+                thisVideo.style.display = 'block';
+            });
+            // No minivid generated yet: keep showing the image
+
+            previewImage.insertAdjacentElement('afterend', video);
+            video.src = minividSrc;
+            video.play().catch(() => {});
+        });
+
+        gridEntry.addEventListener('mouseleave', () => {
+            if (video) {
+                video.remove();
+                video = null;
+            }
+            previewImage.style.display = '';
         });
     });
 }
\ No newline at end of file
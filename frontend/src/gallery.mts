@@ -0,0 +1,150 @@
+import { markImageViewed } from './history.mts';
+
+// Remembered across folders
+const INTERVAL_KEY = 'mtk.slideshowInterval';
+// Horizontal distance that counts as a swipe
+const SWIPE_MIN_PX = 50;
+
+interface SlideshowImage {
+    entryId: string;
+    repoPath: string;
+    src: string;
+    href: string;
+    title: string;
+}
+
+/// Full-screen slideshow over the images in a directory listing. Gallery folders open
+/// it when an image is clicked; other folders have a button to start it.
+export function setupLightbox(): void {
+    const lightbox = document.querySelector('#lightbox') as HTMLElement | null;
+    const list = document.querySelector('#slideshow-images');
+    if (lightbox === null || list === null) {
+        return;
+    }
+    const images: SlideshowImage[] = Array.from(list.querySelectorAll('li')).map(li => ({
+        entryId: li.dataset.entryId!,
+        repoPath: li.dataset.repoPath!,
+        src: li.dataset.src!,
+        href: li.dataset.href!,
+        title: li.textContent ?? '',
+    }));
+
+    const img = lightbox.querySelector('#lightbox-img') as HTMLImageElement;
+    const link = lightbox.querySelector('#lightbox-link') as HTMLAnchorElement;
+    const position = lightbox.querySelector('#lightbox-position') as HTMLElement;
+    const intervalSelect = lightbox.querySelector('#lightbox-interval') as HTMLSelectElement;
+
+    let current = 0;
+    let timer: number | null = null;
+
+    const preload = (index: number) => {
+        if (index >= 0 && index < images.length) {
+            new Image().src = images[index].src;
+        }
+    };
+
+    const show = (index: number) => {
+        current = (index + images.length) % images.length;
+        const image = images[current];
+        img.src = image.src;
+        img.alt = image.title;
+        link.href = image.href;
+        position.textContent = `${current + 1} / ${images.length}`;
+        preload(current + 1);
+        preload(current - 1);
+        markImageViewed(image.repoPath);
+    };
+
+    const restartTimer = () => {
+        if (timer !== null) {
+            clearInterval(timer);
+            timer = null;
+        }
+        const secs = parseInt(intervalSelect.value);
+        if (!lightbox.hidden && secs > 0) {
+            timer = setInterval(() => show(current + 1), secs * 1000);
+        }
+    };
+
+    const open = (index: number) => {
+        lightbox.hidden = false;
+        lightbox.requestFullscreen?.().catch(() => {});
+        show(index);
+        restartTimer();
+    };
+
+    const close = () => {
+        lightbox.hidden = true;
+        img.src = '';
+        restartTimer();
+        if (document.fullscreenElement === lightbox) {
+            document.exitFullscreen();
+        }
+    };
+
+    // Moving by hand restarts the countdown, so the next image gets its full time
+    const step = (delta: number) => {
+        show(current + delta);
+        restartTimer();
+    };
+
+    document.querySelector('#slideshow-start')?.addEventListener('click', () => open(0));
+    document.querySelectorAll<HTMLAnchorElement>('.gallery-item').forEach(item => {
+        item.addEventListener('click', (event) => {
+            // Still let the link open in a new tab
+            if (event.ctrlKey || event.metaKey || event.shiftKey) {
+                return;
+            }
+            const index = images.findIndex(image => image.entryId === item.dataset.entryId);
+            if (index >= 0) {
+                event.preventDefault();
+                open(index);
+            }
+        });
+    });
+
+    lightbox.querySelector('#lightbox-prev')!.addEventListener('click', () => step(-1));
+    lightbox.querySelector('#lightbox-next')!.addEventListener('click', () => step(1));
+    lightbox.querySelector('#lightbox-close')!.addEventListener('click', close);
+    img.addEventListener('click', () => step(1));
+
+    intervalSelect.value = localStorage.getItem(INTERVAL_KEY) ?? '0';
+    intervalSelect.addEventListener('change', () => {
+        localStorage.setItem(INTERVAL_KEY, intervalSelect.value);
+        restartTimer();
+    });
+
+    document.addEventListener('keyup', (event) => {
+        if (lightbox.hidden) {
+            return;
+        }
+        if (event.key === 'ArrowLeft') {
+            step(-1);
+        } else if (event.key === 'ArrowRight' || event.key === ' ') {
+            step(1);
+        } else if (event.key === 'Escape') {
+            close();
+        }
+    });
+    // Leaving full screen (e.g. with Esc) closes the slideshow too
+    document.addEventListener('fullscreenchange', () => {
+        if (document.fullscreenElement === null && !lightbox.hidden) {
+            close();
+        }
+    });
+
+    let touchStartX: number | null = null;
+    lightbox.addEventListener('touchstart', (event) => {
+        touchStartX = event.touches[0].clientX;
+    }, { passive: true });
+    lightbox.addEventListener('touchend', (event) => {
+        if (touchStartX === null) {
+            return;
+        }
+        const dx = event.changedTouches[0].clientX - touchStartX;
+        touchStartX = null;
+        if (Math.abs(dx) >= SWIPE_MIN_PX) {
+            step(dx < 0 ? 1 : -1);
+        }
+    });
+}
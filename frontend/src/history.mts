@@ -2,9 +2,9 @@ import { currEntry } from './entry.mts';
 
 export function markViewed(progress: number, ratio: number): void {
     // TODO: implement new sessions API
-    // TODO: work for more than video
+    // TODO: work for more than video and audio
     const entry = currEntry();
-    if (entry === null || !entry.is_video) {
+    if (entry === null || !(entry.is_video || entry.is_audio)) {
         return;
     }
 
@@ -21,4 +21,40 @@ export function markViewed(progress: number, ratio: number): void {
             contentType: "application/json",
         },
     );
-}
\ No newline at end of file
+}
+
+/// Record the reading position in a document. `page` is null for documents that
+/// scroll rather than having pages.
+export function markRead(page: number | null, ratio: number): void {
+    const entry = currEntry();
+    if (entry === null || !entry.is_document) {
+        return;
+    }
+
+    jQuery.ajax(
+        '/api/reading_history',
+        {
+            type: "POST",
+            data: JSON.stringify({
+                "path": entry.repo_path,
+                "page": page,
+                "ratio": ratio,
+            }),
+            contentType: "application/json",
+        },
+    );
+}
+
+/// Record that an image was looked at, e.g. in a slideshow on a directory page
+export function markImageViewed(repoPath: string): void {
+    jQuery.ajax(
+        '/api/video_history',
+        {
+            type: "POST",
+            data: JSON.stringify({
+                "path": repoPath,
+            }),
+            contentType: "application/json",
+        },
+    );
+}
@@ -1,11 +1,16 @@
 import { currEntry } from './entry.mts';
 import * as entry_list from './entry_list.mts';
+import * as audio from './audio.mts';
+import * as gallery from './gallery.mts';
+import * as reader from './reader.mts';
 import * as video from './video.mts';
 import * as save from './save.mts';
 
 addEventListener('DOMContentLoaded', () => {
     // TODO: only if the layout is cc-grid
     entry_list.setupPreviewFromCcGrid();
+    entry_list.setupMinividFromGalleryGrid();
+    gallery.setupLightbox();
 });
 
 // Set up video stuff
@@ -17,6 +22,29 @@ addEventListener('DOMContentLoaded', () => {
     video.setupVideoPlayer();
 });
 
+// Set up audio stuff
+addEventListener('DOMContentLoaded', () => {
+    const entry = currEntry();
+    if (entry === null || !entry.is_audio) {
+        return;
+    }
+    audio.setupAudioPlayer();
+});
+
+// Set up the document reader
+addEventListener('DOMContentLoaded', () => {
+    const entry = currEntry();
+    if (entry === null || !entry.is_document) {
+        return;
+    }
+    reader.setupDocumentReader();
+});
+
+// Set up the page reader for archives, which are listed like directories
+addEventListener('DOMContentLoaded', () => {
+    reader.setupPageReader();
+});
+
 addEventListener('DOMContentLoaded', () => {
     save.setupSaveForm();
 });
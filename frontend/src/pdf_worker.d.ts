@@ -0,0 +1,2 @@
+// Imported only for its side effect of registering the worker on the main thread
+declare module 'pdfjs-dist/build/pdf.worker.min.mjs';
@@ -0,0 +1,185 @@
+import { markRead } from './history.mts';
+
+// Characters per EPUB "page", for positions that don't depend on the window size
+const EPUB_LOCATION_CHARS = 1600;
+
+export function setupDocumentReader(): void {
+    const reader = document.querySelector('#docreader') as HTMLElement | null;
+    if (reader === null) {
+        return;
+    }
+
+    const resumePage = reader.dataset.resumePage !== undefined ? parseInt(reader.dataset.resumePage) : null;
+    const resumeRatio = reader.dataset.resumeRatio !== undefined ? parseFloat(reader.dataset.resumeRatio) : null;
+
+    const kind = reader.dataset.kind;
+    if (kind === 'pdf') {
+        setupPdfReader(reader, reader.dataset.src!, resumePage);
+    } else if (kind === 'epub') {
+        setupEpubReader(reader, reader.dataset.src!, resumePage);
+    } else {
+        setupScrollReader(resumeRatio);
+    }
+}
+
+/// Markdown and text just scroll, so the position is how far down the page is
+function setupScrollReader(resumeRatio: number | null): void {
+    const scrollableHeight = () => document.documentElement.scrollHeight - window.innerHeight;
+    if (resumeRatio !== null) {
+        window.scrollTo(0, resumeRatio * scrollableHeight());
+    }
+
+    let pending = false;
+    window.addEventListener('scroll', () => {
+        // Don't send an update for every scroll event
+        if (pending) {
+            return;
+        }
+        pending = true;
+        setTimeout(() => {
+            pending = false;
+            const height = scrollableHeight();
+            markRead(null, height > 0 ? window.scrollY / height : 1);
+        }, 1000);
+    });
+}
+
+/// Previous/next buttons, arrow keys, and the position label
+function setupPageControls(onPrev: () => void, onNext: () => void): (label: string) => void {
+    document.querySelector('#docprev')?.addEventListener('click', onPrev);
+    document.querySelector('#docnext')?.addEventListener('click', onNext);
+    document.addEventListener('keyup', (event) => {
+        if (event.key === 'ArrowLeft') {
+            onPrev();
+        } else if (event.key === 'ArrowRight') {
+            onNext();
+        }
+    });
+
+    const position = document.querySelector('#docposition') as HTMLElement;
+    return (label: string) => {
+        position.textContent = label;
+    };
+}
+
+async function setupPdfReader(reader: HTMLElement, src: string, resumePage: number | null): Promise<void> {
+    // The worker is bundled too rather than served from its own URL, so pdf.js
+    // runs it on the main thread
+    await import('pdfjs-dist/build/pdf.worker.min.mjs');
+    const pdfjs = await import('pdfjs-dist');
+    const pdf = await pdfjs.getDocument(src).promise;
+
+    const pages = reader.querySelector('#docpages') as HTMLElement;
+    const canvas = document.createElement('canvas');
+    canvas.classList.add('doc-page');
+    pages.appendChild(canvas);
+
+    let currentPage = Math.min(Math.max(resumePage ?? 1, 1), pdf.numPages);
+    let rendering: Promise<void> = Promise.resolve();
+
+    const renderPage = async (pageNum: number) => {
+        const page = await pdf.getPage(pageNum);
+        // Fit the page to the frame, rendering at the screen's resolution
+        const unscaled = page.getViewport({ scale: 1 });
+        const scale = Math.min(pages.clientWidth / unscaled.width, pages.clientHeight / unscaled.height);
+        const viewport = page.getViewport({ scale: scale * window.devicePixelRatio });
+        canvas.width = viewport.width;
+        canvas.height = viewport.height;
+        canvas.style.width = `${viewport.width / window.devicePixelRatio}px`;
+        canvas.style.height = `${viewport.height / window.devicePixelRatio}px`;
+        await page.render({ canvasContext: canvas.getContext('2d')!, viewport }).promise;
+    };
+
+    const setLabel = setupPageControls(
+        () => goTo(currentPage - 1),
+        () => goTo(currentPage + 1),
+    );
+    const goTo = (pageNum: number) => {
+        if (pageNum < 1 || pageNum > pdf.numPages) {
+            return;
+        }
+        currentPage = pageNum;
+        setLabel(`${pageNum} / ${pdf.numPages}`);
+        // Render one page at a time, since pdf.js can't render to a canvas twice at once
+        rendering = rendering.then(() => renderPage(pageNum));
+        markRead(pageNum, pageNum / pdf.numPages);
+    };
+    goTo(currentPage);
+}
+
+async function setupEpubReader(reader: HTMLElement, src: string, resumePage: number | null): Promise<void> {
+    const { default: ePub } = await import('epubjs');
+
+    const book: any = ePub(src);
+    const rendition = book.renderTo(reader.querySelector('#docpages'), {
+        width: '100%',
+        height: '100%',
+        flow: 'paginated',
+        spread: 'auto',
+    });
+
+    const setLabel = setupPageControls(() => rendition.prev(), () => rendition.next());
+
+    // Locations are a fixed number of characters, so they stay the same whatever the
+    // window size. Generating them means reading the whole book, so this can be slow.
+    await book.ready;
+    await book.locations.generate(EPUB_LOCATION_CHARS);
+    const numLocations = book.locations.length();
+
+    rendition.on('relocated', (location: any) => {
+        const page = book.locations.locationFromCfi(location.start.cfi);
+        setLabel(`${page + 1} / ${numLocations}`);
+        markRead(page, book.locations.percentageFromCfi(location.start.cfi));
+    });
+    await rendition.display(resumePage !== null ? book.locations.cfiFromLocation(resumePage) : undefined);
+}
+
+/// Images in an archive, shown one at a time. They're loaded as they come up, with
+/// the next page fetched early so that turning to it is quick.
+export function setupPageReader(): void {
+    const reader = document.querySelector('#docreader[data-kind="pages"]') as HTMLElement | null;
+    if (reader === null) {
+        return;
+    }
+
+    const pages = Array.from(reader.querySelectorAll('img.doc-page')) as HTMLImageElement[];
+    if (pages.length === 0) {
+        return;
+    }
+
+    const load = (img: HTMLImageElement | undefined) => {
+        if (img !== undefined && !img.src) {
+            img.src = img.dataset.src!;
+        }
+    };
+
+    let currentPage = 0;
+    const setLabel = setupPageControls(
+        () => goTo(currentPage - 1),
+        () => goTo(currentPage + 1),
+    );
+    const goTo = (pageIndex: number) => {
+        if (pageIndex < 0 || pageIndex >= pages.length) {
+            return;
+        }
+        pages[currentPage].hidden = true;
+        currentPage = pageIndex;
+        load(pages[currentPage]);
+        load(pages[currentPage + 1]);
+        pages[currentPage].hidden = false;
+        setLabel(`${currentPage + 1} / ${pages.length}`);
+    };
+
+    // Clicking the left side of a page goes back, like turning a page
+    for (const img of pages) {
+        img.addEventListener('click', (event) => {
+            const rect = img.getBoundingClientRect();
+            if (event.clientX - rect.left < rect.width / 3) {
+                goTo(currentPage - 1);
+            } else {
+                goTo(currentPage + 1);
+            }
+        });
+    }
+    goTo(0);
+}
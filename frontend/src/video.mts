@@ -1,13 +1,25 @@
+import { currEntry } from './entry.mts';
 import { markViewed } from './history.mts';
 
+// Remembered across pages, since it's usually wanted for a whole series
+const AUTOPLAY_NEXT_KEY = 'mtk.autoplayNext';
+const AUTOPLAY_NEXT_DELAY_SECS = 5;
+
 export function setupVideoPlayer(): void {
     const mainVideo = document.querySelector('#mainvideo') as HTMLVideoElement;
 
+    setupHls(mainVideo);
+
     // Update history
     mainVideo.addEventListener('timeupdate', (event) => {
         markViewed(mainVideo.currentTime, mainVideo.currentTime / mainVideo.duration);
     });
 
+    setupScrubBar(mainVideo);
+    setupPinPreview(mainVideo);
+    setupChapters(mainVideo);
+    setupAutoplayNext(mainVideo);
+
     // YouTube-style keyboard shortcuts
     document.addEventListener('keyup', (event) => {
         // Play/pause/seek
@@ -33,3 +45,183 @@ export function setupVideoPlayer(): void {
         }
     });
 }
+
+/// Seek bar that shows sprite sheet thumbnails from the "thumbnails" metadata track on hover
+function setupScrubBar(mainVideo: HTMLVideoElement): void {
+    const scrubBar = document.getElementById('scrubbar') as HTMLDivElement | null;
+    const track = mainVideo.querySelector<HTMLTrackElement>('track[label="thumbnails"]');
+    if (scrubBar === null || track === null) {
+        return;
+    }
+    const progress = document.getElementById('scrubbar-progress') as HTMLDivElement;
+    const preview = document.getElementById('scrubbar-preview') as HTMLDivElement;
+
+    // Metadata tracks only load their cues when not disabled
+    track.track.mode = 'hidden';
+
+    const timeAt = (event: MouseEvent): number => {
+        const rect = scrubBar.getBoundingClientRect();
+        const fraction = Math.min(Math.max((event.clientX - rect.left) / rect.width, 0), 1);
+        return fraction * mainVideo.duration;
+    };
+
+    mainVideo.addEventListener('timeupdate', () => {
+        progress.style.width = `${100 * mainVideo.currentTime / mainVideo.duration}%`;
+    });
+
+    scrubBar.addEventListener('click', (event) => {
+        mainVideo.currentTime = timeAt(event);
+    });
+
+    scrubBar.addEventListener('mousemove', (event) => {
+        const time = timeAt(event);
+        const cues = Array.from(track.track.cues ?? []) as VTTCue[];
+        const cue = cues.find(c => c.startTime <= time && time < c.endTime);
+        if (cue === undefined) {
+            preview.style.display = 'none';
+            return;
+        }
+
+        // Cue text is "<sprite url>#xywh=x,y,w,h", relative to the track
+        const [spriteUrl, fragment] = cue.text.split('#xywh=');
+        const [x, y, w, h] = fragment.split(',').map(Number);
+        preview.style.backgroundImage = `url("${new URL(spriteUrl, track.src)}")`;
+        preview.style.backgroundPosition = `-${x}px -${y}px`;
+        preview.style.width = `${w}px`;
+        preview.style.height = `${h}px`;
+
+        const rect = scrubBar.getBoundingClientRect();
+        const left = Math.min(Math.max(event.clientX - rect.left - w / 2, 0), rect.width - w);
+        preview.style.left = `${left}px`;
+        preview.style.display = 'block';
+    });
+
+    scrubBar.addEventListener('mouseleave', () => {
+        preview.style.display = 'none';
+    });
+}
+
+/// "Use frame as preview" button, which pins the current time in the "preview_timestamp" note
+function setupPinPreview(mainVideo: HTMLVideoElement): void {
+    const pinForm = document.getElementById('pin_preview') as HTMLFormElement | null;
+    const pinGroup = document.getElementById('pin_preview_group') as HTMLDivElement | null;
+    const timestampInput = document.getElementById('pin_preview_timestamp') as HTMLInputElement | null;
+    if (pinForm === null || pinGroup === null || timestampInput === null) {
+        return;
+    }
+
+    pinGroup.style.display = '';
+    pinForm.addEventListener('submit', () => {
+        timestampInput.value = mainVideo.currentTime.toFixed(3);
+    });
+}
+
+/// Seek to a chapter when it's clicked in the chapter list, and highlight the current one
+function setupChapters(mainVideo: HTMLVideoElement): void {
+    const links = Array.from(document.querySelectorAll('#chapters a[data-start]')) as HTMLAnchorElement[];
+    if (links.length === 0) {
+        return;
+    }
+
+    for (const link of links) {
+        link.addEventListener('click', (event) => {
+            event.preventDefault();
+            mainVideo.currentTime = parseFloat(link.dataset.start!);
+            mainVideo.play();
+        });
+    }
+
+    mainVideo.addEventListener('timeupdate', () => {
+        // Chapters are in order, so the current one is the last that has started
+        let current: HTMLAnchorElement | null = null;
+        for (const link of links) {
+            if (parseFloat(link.dataset.start!) <= mainVideo.currentTime) {
+                current = link;
+            }
+        }
+        for (const link of links) {
+            link.classList.toggle('current-chapter', link === current);
+        }
+    });
+}
+
+/// Stream videos that the browser can't play directly over HLS, using hls.js if
+/// needed (Safari plays HLS natively)
+async function setupHls(mainVideo: HTMLVideoElement): Promise<void> {
+    const hlsUrl = mainVideo.dataset.hls;
+    if (hlsUrl === undefined) {
+        return;
+    }
+
+    // Bundled, but only evaluated for videos that need it
+    const { default: Hls } = await import('hls.js');
+    if (Hls.isSupported()) {
+        const hls = new Hls();
+        hls.loadSource(hlsUrl);
+        hls.attachMedia(mainVideo);
+    } else if (mainVideo.canPlayType('application/vnd.apple.mpegurl') !== '') {
+        mainVideo.src = hlsUrl;
+    }
+}
+
+/// Go to the next episode in the series when the video ends, if enabled
+function setupAutoplayNext(mainVideo: HTMLVideoElement): void {
+    const checkbox = document.getElementById('autoplay_next') as HTMLInputElement | null;
+    const notice = document.getElementById('autoplay_notice') as HTMLSpanElement | null;
+    const entry = currEntry();
+    if (checkbox === null || notice === null || entry === null) {
+        return;
+    }
+
+    checkbox.checked = localStorage.getItem(AUTOPLAY_NEXT_KEY) === 'true';
+
+    // Reached from the previous episode's countdown, so keep playing
+    const params = new URLSearchParams(window.location.search);
+    if (params.get('autoplay') === '1' && checkbox.checked) {
+        // Browsers may refuse to play with sound before the user interacts with the page
+        mainVideo.play().catch(() => {});
+    }
+
+    let countdown: number | null = null;
+    const cancel = () => {
+        if (countdown !== null) {
+            clearInterval(countdown);
+            countdown = null;
+        }
+        notice.textContent = '';
+    };
+    checkbox.addEventListener('change', () => {
+        localStorage.setItem(AUTOPLAY_NEXT_KEY, String(checkbox.checked));
+        if (!checkbox.checked) {
+            cancel();
+        }
+    });
+
+    mainVideo.addEventListener('ended', async () => {
+        if (!checkbox.checked) {
+            return;
+        }
+        const response = await fetch(`/api/next_episode/${entry.entry_id}`);
+        if (!response.ok) {
+            return;
+        }
+        const next = await response.json();
+
+        // Give a few seconds to cancel, e.g. by unticking the checkbox or replaying
+        let remaining = AUTOPLAY_NEXT_DELAY_SECS;
+        const update = () => {
+            notice.textContent = `Playing ${next.display_title} in ${remaining}s`;
+        };
+        update();
+        countdown = setInterval(() => {
+            remaining -= 1;
+            if (remaining > 0) {
+                update();
+                return;
+            }
+            cancel();
+            window.location.href = `/entry/${next.repo_path.split('/').map(encodeURIComponent).join('/')}?autoplay=1`;
+        }, 1000);
+    });
+    mainVideo.addEventListener('play', cancel);
+}